@@ -0,0 +1,39 @@
+; every check-sat answered with unsat is a condition that holds
+
+(push 1)
+; the loop preserves its invariant
+(declare-const a Int)
+(declare-const b Int)
+(declare-const q Int)
+(declare-const r Int)
+(assert (not (or (not (and (and (and (<= 0 a) (<= 1 b)) (and (= a (+ (* q b) r)) (<= 0 r))) (<= b r))) (and (= a (+ (* (+ q 1) b) (- r b))) (<= 0 (- r b))))))
+(check-sat)
+(pop 1)
+
+(push 1)
+; the invariant holds what follows the loop
+(declare-const a Int)
+(declare-const b Int)
+(declare-const q Int)
+(declare-const r Int)
+(assert (not (or (not (and (and (and (<= 0 a) (<= 1 b)) (and (= a (+ (* q b) r)) (<= 0 r))) (not (<= b r)))) (and (and (= a (+ (* q b) r)) (<= 0 r)) (not (<= b r))))))
+(check-sat)
+(pop 1)
+
+(push 1)
+; procedure div meets its specification
+(declare-const a Int)
+(declare-const b Int)
+(assert (not (or (not (and (<= 0 a) (<= 1 b))) (and (= a (+ (* 0 b) a)) (<= 0 a)))))
+(check-sat)
+(pop 1)
+
+(push 1)
+; program division meets its specification
+(declare-const x Int)
+(declare-const y Int)
+(declare-const |q'1| Int)
+(declare-const |r'2| Int)
+(assert (not (or (not (and (<= 0 x) (<= 1 y))) (and (and (<= 0 x) (<= 1 y)) (or (not (and (and (= x (+ (* |q'1| y) |r'2|)) (<= 0 |r'2|)) (not (<= y |r'2|)))) (and (= x (+ (* |q'1| y) |r'2|)) (not (<= y |r'2|))))))))
+(check-sat)
+(pop 1)
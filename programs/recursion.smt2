@@ -0,0 +1,31 @@
+; every check-sat answered with unsat is a condition that holds
+
+(push 1)
+; procedure fact meets its specification
+(declare-const n Int)
+(declare-const |r'1| Int)
+(assert (not (and (or (not (<= n 0)) (<= 1 1)) (or (not (not (<= n 0))) (or (not (<= 1 |r'1|)) (<= 1 (* n |r'1|)))))))
+(check-sat)
+(pop 1)
+
+(push 1)
+; procedure even meets its specification
+(declare-const n Int)
+(assert (not (or (not (<= 0 n)) (or (not (not (= n 0))) (<= 0 (- n 1))))))
+(check-sat)
+(pop 1)
+
+(push 1)
+; procedure odd meets its specification
+(declare-const n Int)
+(assert (not (or (not (<= 0 n)) (or (not (not (= n 0))) (<= 0 (- n 1))))))
+(check-sat)
+(pop 1)
+
+(push 1)
+; program recursion meets its specification
+(declare-const n Int)
+(declare-const |f'5| Int)
+(assert (not (or (not (<= 0 n)) (or (not (<= 1 |f'5|)) (and (<= 0 n) (<= 1 |f'5|))))))
+(check-sat)
+(pop 1)
@@ -0,0 +1,1504 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Debug, Display},
+};
+
+use crate::{
+    diagnostic::{Diagnostic, Diagnostics},
+    eval::Defaults,
+    integer::{Arithmetic, Integer, Overflow, Unbounded},
+    typecheck::{Signature, BOOL_SORT},
+    types::*,
+};
+
+// the bounds -∞ and +∞, which no value is equal to
+pub(crate) const MIN: i64 = i64::MIN;
+pub(crate) const MAX: i64 = i64::MAX;
+
+// calls nested deeper than this are not inlined, recursion is cut off there
+static INLINE_DEPTH: usize = 6;
+// loop iterations that join before widening sets in, and that narrow afterwards
+static WIDENING_DELAY: usize = 2;
+static NARROWING: usize = 2;
+
+// an abstraction of sets of integers, bounds are i64 values or infinite
+pub trait Domain: Clone + PartialEq + Debug + Display {
+    // the least element containing every value from low to high, ⊥ if there is none
+    fn range(low: i64, high: i64) -> Self;
+    // the least and the greatest value, none for ⊥
+    fn bounds(&self) -> Option<(i64, i64)>;
+    fn join(&self, other: &Self) -> Self;
+    fn meet(&self, other: &Self) -> Self;
+
+    // an upper bound of both with which every increasing sequence stabilizes
+    fn widen(&self, other: &Self) -> Self {
+        self.join(other)
+    }
+
+    fn top() -> Self {
+        Self::range(MIN, MAX)
+    }
+
+    fn bottom() -> Self {
+        Self::range(1, 0)
+    }
+
+    fn constant(value: i64) -> Self {
+        Self::range(value, value)
+    }
+
+    fn is_bottom(&self) -> bool {
+        self.bounds().is_none()
+    }
+
+    fn may_be(&self, value: i64) -> bool {
+        !self.meet(&Self::constant(value)).is_bottom()
+    }
+
+    // whether it contains a value other than `value`
+    fn may_differ(&self, value: i64) -> bool {
+        !self.meet(&Self::range(MIN, value - 1)).is_bottom()
+            || !self.meet(&Self::range(value + 1, MAX)).is_bottom()
+    }
+}
+
+// intervals [low, high] of integers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interval {
+    Empty,
+    Between(i64, i64),
+}
+
+impl Domain for Interval {
+    fn range(low: i64, high: i64) -> Interval {
+        if low > high || low == MAX || high == MIN {
+            Interval::Empty
+        } else {
+            Interval::Between(low, high)
+        }
+    }
+
+    fn bounds(&self) -> Option<(i64, i64)> {
+        match self {
+            Interval::Empty => None,
+            Interval::Between(low, high) => Some((*low, *high)),
+        }
+    }
+
+    fn join(&self, other: &Interval) -> Interval {
+        match (self.bounds(), other.bounds()) {
+            (Some((l1, h1)), Some((l2, h2))) => Interval::Between(l1.min(l2), h1.max(h2)),
+            (None, _) => *other,
+            (_, None) => *self,
+        }
+    }
+
+    fn meet(&self, other: &Interval) -> Interval {
+        match (self.bounds(), other.bounds()) {
+            (Some((l1, h1)), Some((l2, h2))) => Interval::range(l1.max(l2), h1.min(h2)),
+            _ => Interval::Empty,
+        }
+    }
+
+    // bounds that keep moving jump to infinity
+    fn widen(&self, other: &Interval) -> Interval {
+        match (self.bounds(), other.bounds()) {
+            (Some((l1, h1)), Some((l2, h2))) => Interval::Between(
+                if l2 < l1 { MIN } else { l1 },
+                if h2 > h1 { MAX } else { h1 },
+            ),
+            _ => self.join(other),
+        }
+    }
+}
+
+// which of negative, zero and positive values are possible
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sign {
+    pub negative: bool,
+    pub zero: bool,
+    pub positive: bool,
+}
+
+impl Domain for Sign {
+    fn range(low: i64, high: i64) -> Sign {
+        if low > high || low == MAX || high == MIN {
+            return Sign {
+                negative: false,
+                zero: false,
+                positive: false,
+            };
+        }
+        Sign {
+            negative: low < 0,
+            zero: low <= 0 && 0 <= high,
+            positive: 0 < high,
+        }
+    }
+
+    fn bounds(&self) -> Option<(i64, i64)> {
+        let low = match self {
+            Sign { negative: true, .. } => MIN,
+            Sign { zero: true, .. } => 0,
+            Sign { positive: true, .. } => 1,
+            _ => return None,
+        };
+        let high = match self {
+            Sign { positive: true, .. } => MAX,
+            Sign { zero: true, .. } => 0,
+            _ => -1,
+        };
+        Some((low, high))
+    }
+
+    fn join(&self, other: &Sign) -> Sign {
+        Sign {
+            negative: self.negative || other.negative,
+            zero: self.zero || other.zero,
+            positive: self.positive || other.positive,
+        }
+    }
+
+    fn meet(&self, other: &Sign) -> Sign {
+        Sign {
+            negative: self.negative && other.negative,
+            zero: self.zero && other.zero,
+            positive: self.positive && other.positive,
+        }
+    }
+}
+
+// which truth values are possible
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Truth {
+    pub can_be_true: bool,
+    pub can_be_false: bool,
+}
+
+impl Truth {
+    pub const TRUE: Truth = Truth::of(true);
+    pub const FALSE: Truth = Truth::of(false);
+    pub const UNKNOWN: Truth = Truth {
+        can_be_true: true,
+        can_be_false: true,
+    };
+    pub const NONE: Truth = Truth {
+        can_be_true: false,
+        can_be_false: false,
+    };
+
+    pub const fn of(value: bool) -> Truth {
+        Truth {
+            can_be_true: value,
+            can_be_false: !value,
+        }
+    }
+
+    pub fn can_be(&self, value: bool) -> bool {
+        if value {
+            self.can_be_true
+        } else {
+            self.can_be_false
+        }
+    }
+
+    fn join(&self, other: &Truth) -> Truth {
+        Truth {
+            can_be_true: self.can_be_true || other.can_be_true,
+            can_be_false: self.can_be_false || other.can_be_false,
+        }
+    }
+
+    fn not(&self) -> Truth {
+        Truth {
+            can_be_true: self.can_be_false,
+            can_be_false: self.can_be_true,
+        }
+    }
+}
+
+// the abstract value of a variable, Bool variables are tracked by their truth values
+#[derive(Debug, Clone, PartialEq)]
+pub enum Abstract<D> {
+    Numeral(D),
+    Truth(Truth),
+}
+
+impl<D: Domain> Abstract<D> {
+    fn top(sort: Sort) -> Abstract<D> {
+        if sort == BOOL_SORT {
+            Abstract::Truth(Truth::UNKNOWN)
+        } else {
+            Abstract::Numeral(D::top())
+        }
+    }
+
+    // a variable the store has just allocated, with the default value of its sort, ⊥ if
+    // there is none and reading the variable fails
+    fn fresh(sort: Sort, default: Option<&Value>) -> Abstract<D> {
+        match default {
+            Some(Value::Numeral(numeral)) => Abstract::Numeral(self::numeral(numeral, None).0),
+            Some(Value::True) => Abstract::Truth(Truth::TRUE),
+            Some(Value::False) => Abstract::Truth(Truth::FALSE),
+            None if sort == BOOL_SORT => Abstract::Truth(Truth::NONE),
+            None => Abstract::Numeral(D::bottom()),
+        }
+    }
+
+    // the top element of the same kind
+    fn havoc(&self) -> Abstract<D> {
+        match self {
+            Abstract::Numeral(_) => Abstract::Numeral(D::top()),
+            Abstract::Truth(_) => Abstract::Truth(Truth::UNKNOWN),
+        }
+    }
+
+    fn is_bottom(&self) -> bool {
+        match self {
+            Abstract::Numeral(numeral) => numeral.is_bottom(),
+            Abstract::Truth(truth) => *truth == Truth::NONE,
+        }
+    }
+
+    fn numeral(&self) -> D {
+        match self {
+            Abstract::Numeral(numeral) => numeral.clone(),
+            Abstract::Truth(_) => D::top(),
+        }
+    }
+
+    fn truth(&self) -> Truth {
+        match self {
+            Abstract::Truth(truth) => *truth,
+            Abstract::Numeral(_) => Truth::UNKNOWN,
+        }
+    }
+
+    fn combine(&self, other: &Abstract<D>, numerals: impl Fn(&D, &D) -> D) -> Abstract<D> {
+        match (self, other) {
+            (Abstract::Numeral(left), Abstract::Numeral(right)) => {
+                Abstract::Numeral(numerals(left, right))
+            }
+            (Abstract::Truth(left), Abstract::Truth(right)) => Abstract::Truth(Truth {
+                can_be_true: left.can_be_true || right.can_be_true,
+                can_be_false: left.can_be_false || right.can_be_false,
+            }),
+            _ => self.havoc(),
+        }
+    }
+
+    fn join(&self, other: &Abstract<D>) -> Abstract<D> {
+        self.combine(other, D::join)
+    }
+
+    fn widen(&self, other: &Abstract<D>) -> Abstract<D> {
+        self.combine(other, D::widen)
+    }
+
+    fn meet(&self, other: &Abstract<D>) -> Abstract<D> {
+        match (self, other) {
+            (Abstract::Numeral(left), Abstract::Numeral(right)) => {
+                Abstract::Numeral(left.meet(right))
+            }
+            (Abstract::Truth(left), Abstract::Truth(right)) => Abstract::Truth(Truth {
+                can_be_true: left.can_be_true && right.can_be_true,
+                can_be_false: left.can_be_false && right.can_be_false,
+            }),
+            _ => self.clone(),
+        }
+    }
+}
+
+// the abstract values of the variables visible at a program point, none if no execution
+// reaches it
+#[derive(Debug, Clone, PartialEq)]
+pub struct State<'a, D>(pub Option<Vec<(Identifier<'a>, Abstract<D>)>>);
+
+impl<'a, D: Domain> State<'a, D> {
+    fn new(scope: &Scope<'a>, memory: &Memory<D>) -> State<'a, D> {
+        State(memory.as_ref().map(|slots| {
+            let visible = scope.iter().enumerate().filter(|(index, (identifier, _))| {
+                !scope[index + 1..]
+                    .iter()
+                    .any(|(other, _)| other == identifier)
+            });
+            visible
+                .map(|(_, (identifier, slot))| (*identifier, slots[*slot].clone()))
+                .collect()
+        }))
+    }
+
+    pub fn is_reachable(&self) -> bool {
+        self.0.is_some()
+    }
+
+    pub fn lookup(&self, identifier: Identifier<'a>) -> Option<&Abstract<D>> {
+        let bindings = self.0.as_ref()?;
+        let (_, value) = bindings.iter().find(|(other, _)| *other == identifier)?;
+        Some(value)
+    }
+
+    fn join(&self, other: &State<'a, D>) -> State<'a, D> {
+        match (&self.0, &other.0) {
+            (Some(left), Some(right)) => State(Some(
+                left.iter()
+                    .zip(right)
+                    .map(|((identifier, left), (_, right))| (*identifier, left.join(right)))
+                    .collect(),
+            )),
+            (None, _) => other.clone(),
+            (_, None) => self.clone(),
+        }
+    }
+}
+
+// a command and the state before it, joined over every way the analysis reached it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point<'p, 'a, D> {
+    pub command: &'p Command<'a>,
+    pub state: State<'a, D>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis<'p, 'a, D> {
+    // in the order the analysis first reached them
+    pub points: Vec<Point<'p, 'a, D>>,
+    pub exit: State<'a, D>,
+    pub warnings: Diagnostics<Warning>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    // `always` if every execution that reaches the expression fails
+    DivisionByZero { always: bool },
+    Overflow { always: bool },
+    ConstantCondition(bool),
+}
+
+impl<'a> Program<'a> {
+    // the program has to have been typechecked, calls that weren't resolved can change
+    // any variable
+    pub fn analyze<D: Domain>(&self) -> Analysis<'_, 'a, D> {
+        self.analyze_in(Unbounded, Some(&Defaults::default()))
+    }
+
+    // `Program::analyze` of the evaluation with the integers of `arithmetic` and the
+    // `defaults` of a store, none for a strict one, see `Program::eval_in`
+    pub fn analyze_in<D: Domain, A: Arithmetic>(
+        &self,
+        arithmetic: A,
+        defaults: Option<&Defaults>,
+    ) -> Analysis<'_, 'a, D> {
+        let Program(declarations, _, parameters, body, _) = self;
+        let mut analyzer = Analyzer::new(declarations, arithmetic.overflow(), defaults.cloned());
+        let mut slots: Vec<Abstract<D>> = analyzer
+            .global_sorts
+            .iter()
+            .map(|sort| analyzer.fresh(*sort))
+            .collect();
+        let mut scope = analyzer.globals.clone();
+        for (parameter, sort) in parameters.identifiers().into_iter().zip(parameters.sorts()) {
+            scope.push((parameter, slots.len()));
+            slots.push(Abstract::top(sort));
+        }
+        let exit = analyzer.command(body, &scope, Some(slots));
+        Analysis {
+            exit: State::new(&scope, &exit),
+            warnings: analyzer.warnings(),
+            points: analyzer.points,
+        }
+    }
+}
+
+// the abstract store, indexed like addresses, none if unreachable
+type Memory<D> = Option<Vec<Abstract<D>>>;
+// names and the slot they are stored in, later entries shadow earlier ones
+type Scope<'a> = Vec<(Identifier<'a>, usize)>;
+
+type Body<'p, 'a> = (&'p Parameters<'a>, &'p Parameters<'a>, &'p Command<'a>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Check {
+    Divisor,
+    Overflow,
+    Condition,
+}
+
+struct Analyzer<'p, 'a, D> {
+    procedures: HashMap<Signature<'a>, Body<'p, 'a>>,
+    // the globals occupy the first slots
+    globals: Scope<'a>,
+    global_sorts: Vec<Sort<'a>>,
+    // how the integers are represented, none if they are unbounded
+    overflow: Option<Overflow>,
+    // the values of fresh variables, none if the store is strict
+    defaults: Option<Defaults>,
+    depth: usize,
+    // procedures analyzed once from an unknown state where recursion was cut off
+    summarized: HashSet<Signature<'a>>,
+    // off while a loop is iterated towards its invariant
+    recording: bool,
+    // of the innermost node that has one
+    span: Option<Span>,
+    points: Vec<Point<'p, 'a, D>>,
+    point_indices: HashMap<*const Command<'a>, usize>,
+    // whether a divisor is 0, an operation overflows or a condition holds, joined over
+    // every time the analysis reached the expression
+    observations: Vec<(Check, Option<Span>, Truth)>,
+    observation_indices: HashMap<(*const Expression<'a>, Check), usize>,
+}
+
+impl<'p, 'a, D: Domain> Analyzer<'p, 'a, D> {
+    fn new(
+        declarations: &'p Declarations<'a>,
+        overflow: Option<Overflow>,
+        defaults: Option<Defaults>,
+    ) -> Analyzer<'p, 'a, D> {
+        let mut procedures = HashMap::new();
+        let (mut globals, mut global_sorts) = (Vec::new(), Vec::new());
+        for declaration in declarations.sequence() {
+            match declaration.unspanned() {
+                Declaration::Variable(identifier, sort) => {
+                    globals.push((*identifier, globals.len()));
+                    global_sorts.push(*sort);
+                }
+                Declaration::Procedure(_, in_params, out_params, body, _) => {
+                    if let Some(signature) = declaration.signature() {
+                        procedures.insert(signature, (in_params, out_params, body));
+                    }
+                }
+                Declaration::Spanned(..) => {}
+            }
+        }
+        Analyzer {
+            procedures,
+            globals,
+            global_sorts,
+            overflow,
+            defaults,
+            depth: 0,
+            summarized: HashSet::new(),
+            recording: true,
+            span: None,
+            points: Vec::new(),
+            point_indices: HashMap::new(),
+            observations: Vec::new(),
+            observation_indices: HashMap::new(),
+        }
+    }
+
+    fn command(
+        &mut self,
+        command: &'p Command<'a>,
+        scope: &Scope<'a>,
+        memory: Memory<D>,
+    ) -> Memory<D> {
+        let slots = memory.as_ref()?;
+        if !matches!(command, Command::Sequence(..) | Command::Spanned(..)) {
+            self.point(command, scope, &memory);
+        }
+        match command {
+            Command::Assign(identifier, expression) => {
+                self.check(expression, scope, slots);
+                let value = value(expression, scope, slots, self.overflow);
+                let Some(slot) = lookup(scope, *identifier) else {
+                    return memory;
+                };
+                if value.is_bottom() {
+                    return None;
+                }
+                let mut slots = memory?;
+                slots[slot] = value;
+                Some(slots)
+            }
+            Command::Var(identifier, sort, body) => {
+                let mut scope = scope.clone();
+                let mut slots = memory?;
+                let length = slots.len();
+                scope.push((*identifier, length));
+                slots.push(self.fresh(*sort));
+                let mut slots = self.command(body, &scope, Some(slots))?;
+                slots.truncate(length);
+                Some(slots)
+            }
+            Command::Sequence(first, second) => {
+                let memory = self.command(first, scope, memory);
+                self.command(second, scope, memory)
+            }
+            Command::IfElse(condition, if_branch, else_branch) => {
+                self.condition(condition, scope, slots);
+                let if_memory = assume(condition, true, scope, memory.clone(), self.overflow);
+                let else_memory = assume(condition, false, scope, memory, self.overflow);
+                let if_memory = self.command(if_branch, scope, if_memory);
+                let else_memory = self.command(else_branch, scope, else_memory);
+                join(&if_memory, &else_memory)
+            }
+            Command::If(condition, if_branch) => {
+                self.condition(condition, scope, slots);
+                let if_memory = assume(condition, true, scope, memory.clone(), self.overflow);
+                let else_memory = assume(condition, false, scope, memory, self.overflow);
+                let if_memory = self.command(if_branch, scope, if_memory);
+                join(&if_memory, &else_memory)
+            }
+            Command::While(condition, body, _) => self.repeat(condition, body, scope, memory),
+            Command::Call(_, expressions, variables, signature) => {
+                self.call(expressions, variables, signature, scope, memory)
+            }
+            Command::Spanned(span, command) => {
+                let outer = self.span.replace(*span);
+                let memory = self.command(command, scope, memory);
+                self.span = outer;
+                memory
+            }
+        }
+    }
+
+    // iterates the loop from the state it is entered in until the state before the
+    // condition is tested stops changing, the body is analyzed once more from there
+    fn repeat(
+        &mut self,
+        condition: &'p Expression<'a>,
+        body: &'p Command<'a>,
+        scope: &Scope<'a>,
+        memory: Memory<D>,
+    ) -> Memory<D> {
+        let recording = std::mem::replace(&mut self.recording, false);
+        let mut head = memory.clone();
+        for iteration in 0.. {
+            let after = self.command(
+                body,
+                scope,
+                assume(condition, true, scope, head.clone(), self.overflow),
+            );
+            let next = join(&head, &join(&memory, &after));
+            let next = if iteration < WIDENING_DELAY {
+                next
+            } else {
+                widen(&head, &next)
+            };
+            if next == head {
+                break;
+            }
+            head = next;
+        }
+        for _ in 0..NARROWING {
+            let after = self.command(
+                body,
+                scope,
+                assume(condition, true, scope, head.clone(), self.overflow),
+            );
+            head = join(&memory, &after);
+        }
+        self.recording = recording;
+
+        if let Some(slots) = &head {
+            self.condition(condition, scope, slots);
+        }
+        self.command(
+            body,
+            scope,
+            assume(condition, true, scope, head.clone(), self.overflow),
+        );
+        assume(condition, false, scope, head, self.overflow)
+    }
+
+    // calls are inlined, out parameters share the slots of the variables passed for them
+    fn call(
+        &mut self,
+        expressions: &'p Expressions<'a>,
+        variables: &Variables<'a>,
+        signature: &Option<Signature<'a>>,
+        scope: &Scope<'a>,
+        memory: Memory<D>,
+    ) -> Memory<D> {
+        let slots = memory.as_ref()?;
+        let mut values = Vec::new();
+        for argument in arguments(expressions) {
+            self.check(argument, scope, slots);
+            let value = value(argument, scope, slots, self.overflow);
+            if value.is_bottom() {
+                return None;
+            }
+            values.push(value);
+        }
+        let targets: Vec<_> = variables
+            .sequence()
+            .into_iter()
+            .map(|variable| lookup(scope, variable))
+            .collect();
+        let procedure = signature
+            .as_ref()
+            .and_then(|signature| Some((signature, *self.procedures.get(signature)?)));
+
+        let mut slots = memory?;
+        let length = slots.len();
+        match procedure {
+            Some((_, (in_params, out_params, body))) if self.depth < INLINE_DEPTH => {
+                let mut callee_scope = self.globals.clone();
+                for (parameter, value) in in_params.identifiers().into_iter().zip(values) {
+                    callee_scope.push((parameter, slots.len()));
+                    slots.push(value);
+                }
+                for (parameter, target) in out_params.identifiers().into_iter().zip(&targets) {
+                    callee_scope.extend(target.map(|slot| (parameter, slot)));
+                }
+                self.depth += 1;
+                let slots = self.command(body, &callee_scope, Some(slots));
+                self.depth -= 1;
+                let mut slots = slots?;
+                slots.truncate(length);
+                return Some(slots);
+            }
+            // the body still has to be checked for every state it may run in
+            Some((signature, (in_params, out_params, body)))
+                if self.recording && self.summarized.insert(signature.clone()) =>
+            {
+                let mut entry: Vec<_> = slots.iter().map(Abstract::havoc).collect();
+                let mut callee_scope = self.globals.clone();
+                let parameters = [in_params, out_params];
+                for parameters in parameters {
+                    for (parameter, sort) in
+                        parameters.identifiers().into_iter().zip(parameters.sorts())
+                    {
+                        callee_scope.push((parameter, entry.len()));
+                        entry.push(Abstract::top(sort));
+                    }
+                }
+                self.command(body, &callee_scope, Some(entry));
+            }
+            _ => {}
+        }
+        // what the call changes is unknown
+        for slot in (0..self.globals.len()).chain(targets.into_iter().flatten()) {
+            slots[slot] = slots[slot].havoc();
+        }
+        Some(slots)
+    }
+
+    fn fresh(&self, sort: Sort) -> Abstract<D> {
+        let defaults = self.defaults.as_ref();
+        Abstract::fresh(sort, defaults.and_then(|defaults| defaults.of(sort)))
+    }
+
+    fn point(&mut self, command: &'p Command<'a>, scope: &Scope<'a>, memory: &Memory<D>) {
+        if !self.recording {
+            return;
+        }
+        let state = State::new(scope, memory);
+        match self.point_indices.get(&(command as *const _)) {
+            Some(index) => {
+                let point = &mut self.points[*index];
+                point.state = point.state.join(&state);
+            }
+            None => {
+                self.point_indices
+                    .insert(command as *const _, self.points.len());
+                self.points.push(Point { command, state });
+            }
+        }
+    }
+
+    fn condition(
+        &mut self,
+        condition: &'p Expression<'a>,
+        scope: &Scope<'a>,
+        slots: &[Abstract<D>],
+    ) {
+        self.check(condition, scope, slots);
+        let truth = value(condition, scope, slots, self.overflow).truth();
+        self.observe(condition, Check::Condition, truth);
+    }
+
+    // records whether the divisions and operations in the expression may fail
+    fn check(&mut self, expression: &'p Expression<'a>, scope: &Scope<'a>, slots: &[Abstract<D>]) {
+        match expression {
+            Expression::Value(Value::Numeral(numeral)) => {
+                let (_, overflow) = self::numeral::<D>(numeral, self.overflow);
+                self.observe(expression, Check::Overflow, overflow);
+            }
+            Expression::Value(_) | Expression::Variable(_) => {}
+            Expression::Sum(left, right)
+            | Expression::Difference(left, right)
+            | Expression::Product(left, right)
+            | Expression::Division(left, right)
+            | Expression::Remainder(left, right) => {
+                self.check(left, scope, slots);
+                self.check(right, scope, slots);
+                let left = value(left, scope, slots, self.overflow).numeral();
+                let right = value(right, scope, slots, self.overflow).numeral();
+                if let Expression::Division(..) | Expression::Remainder(..) = expression {
+                    let zero = Truth {
+                        can_be_true: right.may_be(0),
+                        can_be_false: right.may_differ(0),
+                    };
+                    self.observe(expression, Check::Divisor, zero);
+                }
+                let (_, overflow) = arithmetic(expression, &left, &right, self.overflow);
+                self.observe(expression, Check::Overflow, overflow);
+            }
+            Expression::Negative(operand) => {
+                self.check(operand, scope, slots);
+                let operand = value(operand, scope, slots, self.overflow).numeral();
+                let (_, overflow) =
+                    arithmetic(expression, &D::constant(0), &operand, self.overflow);
+                self.observe(expression, Check::Overflow, overflow);
+            }
+            Expression::Equal(left, right)
+            | Expression::NotEqual(left, right)
+            | Expression::LessThan(left, right)
+            | Expression::LessThanOrEqual(left, right)
+            | Expression::GreaterThan(left, right)
+            | Expression::GreaterThanOrEqual(left, right)
+            | Expression::Equivalent(left, right) => {
+                self.check(left, scope, slots);
+                self.check(right, scope, slots);
+            }
+            Expression::Implies(left, right) => {
+                self.check(left, scope, slots);
+                if let Some(slots) = assume(left, true, scope, Some(slots.to_vec()), self.overflow)
+                {
+                    self.check(right, scope, &slots);
+                }
+            }
+            // the right operand is only evaluated if the left one doesn't decide
+            Expression::And(left, right) | Expression::Or(left, right) => {
+                self.check(left, scope, slots);
+                let decided = matches!(expression, Expression::Or(..));
+                let memory = assume(left, !decided, scope, Some(slots.to_vec()), self.overflow);
+                if let Some(slots) = memory {
+                    self.check(right, scope, &slots);
+                }
+            }
+            Expression::Not(operand) => self.check(operand, scope, slots),
+            Expression::Spanned(span, expression) => {
+                let outer = self.span.replace(*span);
+                self.check(expression, scope, slots);
+                self.span = outer;
+            }
+        }
+    }
+
+    fn observe(&mut self, expression: &'p Expression<'a>, check: Check, truth: Truth) {
+        if !self.recording {
+            return;
+        }
+        let key = (expression as *const _, check);
+        match self.observation_indices.get(&key) {
+            Some(index) => {
+                let (_, _, observed) = &mut self.observations[*index];
+                *observed = observed.join(&truth);
+            }
+            None => {
+                self.observation_indices
+                    .insert(key, self.observations.len());
+                self.observations
+                    .push((check, expression.span().or(self.span), truth));
+            }
+        }
+    }
+
+    fn warnings(&self) -> Diagnostics<Warning> {
+        let mut warnings = Diagnostics::new();
+        for (check, span, truth) in &self.observations {
+            let warning = match check {
+                Check::Divisor if truth.can_be_true => Warning::DivisionByZero {
+                    always: !truth.can_be_false,
+                },
+                Check::Overflow if truth.can_be_true => Warning::Overflow {
+                    always: !truth.can_be_false,
+                },
+                Check::Condition if *truth == Truth::TRUE || *truth == Truth::FALSE => {
+                    Warning::ConstantCondition(truth.can_be_true)
+                }
+                _ => continue,
+            };
+            warnings.push(Diagnostic {
+                error: warning,
+                span: *span,
+            });
+        }
+        warnings
+    }
+}
+
+fn arguments<'p, 'a>(expressions: &'p Expressions<'a>) -> Vec<&'p Expression<'a>> {
+    match expressions {
+        Expressions::Empty => Vec::new(),
+        Expressions::Sequence(first, rest) => {
+            let mut arguments = vec![first];
+            arguments.extend(self::arguments(rest));
+            arguments
+        }
+    }
+}
+
+fn lookup<'a>(scope: &Scope<'a>, identifier: Identifier<'a>) -> Option<usize> {
+    let (_, slot) = scope.iter().rev().find(|(other, _)| *other == identifier)?;
+    Some(*slot)
+}
+
+fn join<D: Domain>(left: &Memory<D>, right: &Memory<D>) -> Memory<D> {
+    combine(left, right, Abstract::join)
+}
+
+fn widen<D: Domain>(left: &Memory<D>, right: &Memory<D>) -> Memory<D> {
+    combine(left, right, Abstract::widen)
+}
+
+fn combine<D: Domain>(
+    left: &Memory<D>,
+    right: &Memory<D>,
+    values: impl Fn(&Abstract<D>, &Abstract<D>) -> Abstract<D>,
+) -> Memory<D> {
+    match (left, right) {
+        (Some(left), Some(right)) => Some(
+            left.iter()
+                .zip(right)
+                .map(|(left, right)| values(left, right))
+                .collect(),
+        ),
+        (None, _) => right.clone(),
+        (_, None) => left.clone(),
+    }
+}
+
+// the result of an arithmetic expression as represented with `overflow`, and whether
+// computing it overflows
+fn arithmetic<D: Domain>(
+    expression: &Expression,
+    left: &D,
+    right: &D,
+    overflow: Option<Overflow>,
+) -> (D, Truth) {
+    let (Some((a, b)), Some((c, d))) = (left.bounds(), right.bounds()) else {
+        return (D::bottom(), Truth::NONE);
+    };
+    // the operands have already been represented, as i32 values if they are bounded,
+    // whatever bounds the domain can express
+    let (a, b, c, d) = match overflow {
+        Some(_) => {
+            let (min, max) = (i32::MIN as i64, i32::MAX as i64);
+            (a.max(min), b.min(max), c.max(min), d.min(max))
+        }
+        None => (a, b, c, d),
+    };
+    let corners = |f: fn(i64, i64) -> i128, (a, b): (i64, i64), (c, d): (i64, i64)| {
+        let values = [f(a, c), f(a, d), f(b, c), f(b, d)];
+        (*values.iter().min().unwrap(), *values.iter().max().unwrap())
+    };
+    let bounds = match expression {
+        Expression::Sum(..) => Some((add(a, c), add(b, d))),
+        Expression::Difference(..) | Expression::Negative(_) => {
+            Some((add(a, negate(d)), add(b, negate(c))))
+        }
+        Expression::Product(..) => Some(corners(multiply, (a, b), (c, d))),
+        // truncating division is monotone in both operands as long as the divisor keeps
+        // its sign, a divisor of 0 fails rather than producing a value
+        Expression::Division(..) => [(c, d.min(-1)), (c.max(1), d)]
+            .into_iter()
+            .filter(|(c, d)| c <= d)
+            .map(|divisor| corners(divide, (a, b), divisor))
+            .reduce(|(l1, h1), (l2, h2)| (l1.min(l2), h1.max(h2))),
+        // smaller in magnitude than both the dividend and the divisor, with the sign of
+        // the dividend
+        Expression::Remainder(..) if (c, d) != (0, 0) => {
+            let bound = match (c, d) {
+                (MIN, _) | (_, MAX) => MAX,
+                (c, d) => c.unsigned_abs().max(d.unsigned_abs()) as i64 - 1,
+            };
+            let low = a.max(negate(bound)).min(0);
+            let high = b.min(bound).max(0);
+            Some((low as i128, high as i128))
+        }
+        _ => None,
+    };
+    let Some((low, high)) = bounds else {
+        return (D::bottom(), Truth::NONE);
+    };
+    represent(rounded(low, high), overflow)
+}
+
+// a numeral as represented with `overflow`, and whether it overflows
+fn numeral<D: Domain>(numeral: &Integer, overflow: Option<Overflow>) -> (D, Truth) {
+    let bounds = match numeral.to_i64() {
+        Some(value) => (value as i128, value as i128),
+        None if numeral.is_negative() => (MIN as i128, MIN as i128),
+        None => (MAX as i128, MAX as i128),
+    };
+    represent(rounded(bounds.0, bounds.1), overflow)
+}
+
+// the values from low to high as `Program::eval_in` represents them with `overflow`, and
+// whether one of them overflows, which only fails with checked i32 arithmetic
+fn represent<D: Domain>((low, high): (i64, i64), overflow: Option<Overflow>) -> (D, Truth) {
+    let (min, max) = (i32::MIN as i64, i32::MAX as i64);
+    let overflows = Truth {
+        can_be_true: low < min || high > max,
+        can_be_false: low <= max && high >= min,
+    };
+    match overflow {
+        None => (D::range(low, high), Truth::FALSE),
+        Some(Overflow::Checked) => (D::range(low.max(min), high.min(max)), overflows),
+        Some(Overflow::Saturating) => (
+            D::range(low.clamp(min, max), high.clamp(min, max)),
+            Truth::FALSE,
+        ),
+        // the values stay contiguous unless the interval spans a multiple of 2^32
+        Some(Overflow::Wrapping) => {
+            let (wrapped_low, wrapped_high) = (low as i32 as i64, high as i32 as i64);
+            let width = high as i128 - low as i128;
+            let contiguous =
+                low != MIN && high != MAX && width == (wrapped_high - wrapped_low) as i128;
+            if contiguous {
+                (D::range(wrapped_low, wrapped_high), Truth::FALSE)
+            } else {
+                (D::range(min, max), Truth::FALSE)
+            }
+        }
+    }
+}
+
+// exact bounds rounded outwards to the finite i64 values or to infinity
+fn rounded(low: i128, high: i128) -> (i64, i64) {
+    let low = if low <= MIN as i128 {
+        MIN
+    } else {
+        low.min(MAX as i128 - 1) as i64
+    };
+    let high = if high >= MAX as i128 {
+        MAX
+    } else {
+        high.max(MIN as i128 + 1) as i64
+    };
+    (low, high)
+}
+
+// arithmetic on bounds, where an infinite operand gives an infinite result unless the
+// other one makes it 0
+fn add(x: i64, y: i64) -> i128 {
+    match (x, y) {
+        (MIN | MAX, _) => x as i128,
+        (_, MIN | MAX) => y as i128,
+        _ => x as i128 + y as i128,
+    }
+}
+
+fn negate(x: i64) -> i64 {
+    match x {
+        MIN => MAX,
+        MAX => MIN,
+        x => -x,
+    }
+}
+
+fn multiply(x: i64, y: i64) -> i128 {
+    match (x, y) {
+        (0, _) | (_, 0) => 0,
+        (MIN | MAX, _) | (_, MIN | MAX) if (x < 0) == (y < 0) => MAX as i128,
+        (MIN | MAX, _) | (_, MIN | MAX) => MIN as i128,
+        _ => x as i128 * y as i128,
+    }
+}
+
+// y isn't 0
+fn divide(x: i64, y: i64) -> i128 {
+    match (x, y) {
+        (_, MIN | MAX) => 0,
+        (MIN | MAX, _) if (x < 0) == (y < 0) => MAX as i128,
+        (MIN | MAX, _) => MIN as i128,
+        _ => x as i128 / y as i128,
+    }
+}
+
+// the bounds of the values above or below a bound, infinite ones stay where they are
+fn successor(x: i64) -> i64 {
+    match x {
+        MIN | MAX => x,
+        x => (x + 1).min(MAX - 1),
+    }
+}
+
+fn predecessor(x: i64) -> i64 {
+    match x {
+        MIN | MAX => x,
+        x => (x - 1).max(MIN + 1),
+    }
+}
+
+fn value<'a, D: Domain>(
+    expression: &Expression<'a>,
+    scope: &Scope<'a>,
+    slots: &[Abstract<D>],
+    overflow: Option<Overflow>,
+) -> Abstract<D> {
+    let numeral = |expression: &Expression<'a>| value(expression, scope, slots, overflow).numeral();
+    match expression {
+        Expression::Value(Value::Numeral(numeral)) => {
+            Abstract::Numeral(self::numeral(numeral, overflow).0)
+        }
+        Expression::Value(Value::True) => Abstract::Truth(Truth::TRUE),
+        Expression::Value(Value::False) => Abstract::Truth(Truth::FALSE),
+        Expression::Variable(identifier) => match lookup(scope, *identifier) {
+            Some(slot) => slots[slot].clone(),
+            None => Abstract::Numeral(D::top()),
+        },
+        Expression::Sum(left, right)
+        | Expression::Difference(left, right)
+        | Expression::Product(left, right)
+        | Expression::Division(left, right)
+        | Expression::Remainder(left, right) => {
+            Abstract::Numeral(arithmetic(expression, &numeral(left), &numeral(right), overflow).0)
+        }
+        Expression::Negative(operand) => Abstract::Numeral(
+            arithmetic(expression, &D::constant(0), &numeral(operand), overflow).0,
+        ),
+        Expression::Equal(left, right) => {
+            let left = value(left, scope, slots, overflow);
+            let right = value(right, scope, slots, overflow);
+            let truth = match (&left, &right) {
+                (Abstract::Numeral(l), Abstract::Numeral(r)) => Truth {
+                    can_be_true: !l.meet(r).is_bottom(),
+                    can_be_false: l.bounds() != r.bounds()
+                        || l.bounds().is_some_and(|(a, b)| a != b),
+                },
+                (Abstract::Truth(l), Abstract::Truth(r)) => Truth {
+                    can_be_true: l.can_be_true && r.can_be_true || l.can_be_false && r.can_be_false,
+                    can_be_false: l.can_be_true && r.can_be_false
+                        || l.can_be_false && r.can_be_true,
+                },
+                _ => Truth::UNKNOWN,
+            };
+            if left.is_bottom() || right.is_bottom() {
+                Abstract::Truth(Truth::NONE)
+            } else {
+                Abstract::Truth(truth)
+            }
+        }
+        Expression::LessThanOrEqual(left, right) => {
+            let truth = match (numeral(left).bounds(), numeral(right).bounds()) {
+                (Some((a, b)), Some((c, d))) => Truth {
+                    can_be_true: a <= d,
+                    can_be_false: b > c,
+                },
+                _ => Truth::NONE,
+            };
+            Abstract::Truth(truth)
+        }
+        // the right operand is only evaluated if the left one doesn't decide
+        Expression::And(left, right) | Expression::Or(left, right) => {
+            let decided = matches!(expression, Expression::Or(..));
+            let left_truth = value(left, scope, slots, overflow).truth();
+            let memory = assume(left, !decided, scope, Some(slots.to_vec()), overflow);
+            let right_truth = memory.map_or(Truth::NONE, |slots| {
+                value(right, scope, &slots, overflow).truth()
+            });
+            let truth = if decided {
+                Truth {
+                    can_be_true: left_truth.can_be_true || right_truth.can_be_true,
+                    can_be_false: right_truth.can_be_false,
+                }
+            } else {
+                Truth {
+                    can_be_true: right_truth.can_be_true,
+                    can_be_false: left_truth.can_be_false || right_truth.can_be_false,
+                }
+            };
+            Abstract::Truth(truth)
+        }
+        Expression::Not(operand) => {
+            Abstract::Truth(value(operand, scope, slots, overflow).truth().not())
+        }
+        Expression::NotEqual(..)
+        | Expression::LessThan(..)
+        | Expression::GreaterThan(..)
+        | Expression::GreaterThanOrEqual(..)
+        | Expression::Implies(..)
+        | Expression::Equivalent(..) => {
+            let expression = expression.desugared().expect("has a desugared form");
+            value(&expression, scope, slots, overflow)
+        }
+        Expression::Spanned(_, expression) => value(expression, scope, slots, overflow),
+    }
+}
+
+// the part of the memory in which the condition evaluates to `truth`
+fn assume<'a, D: Domain>(
+    condition: &Expression<'a>,
+    truth: bool,
+    scope: &Scope<'a>,
+    memory: Memory<D>,
+    overflow: Option<Overflow>,
+) -> Memory<D> {
+    if let Some(condition) = condition.desugared() {
+        return assume(&condition, truth, scope, memory, overflow);
+    }
+    let mut slots = memory?;
+    if !value(condition, scope, &slots, overflow)
+        .truth()
+        .can_be(truth)
+    {
+        return None;
+    }
+    let mut possible = true;
+    match condition {
+        Expression::Spanned(_, condition) => {
+            return assume(condition, truth, scope, Some(slots), overflow)
+        }
+        Expression::Not(condition) => {
+            return assume(condition, !truth, scope, Some(slots), overflow)
+        }
+        Expression::And(left, right) | Expression::Or(left, right) => {
+            let decided = matches!(condition, Expression::Or(..));
+            let undecided = assume(left, !decided, scope, Some(slots.clone()), overflow);
+            if truth == decided {
+                // either the left operand decides or the right one has the value
+                let left = assume(left, decided, scope, Some(slots), overflow);
+                return join(&left, &assume(right, decided, scope, undecided, overflow));
+            }
+            return assume(right, truth, scope, undecided, overflow);
+        }
+        Expression::Variable(_) => {
+            let value = Abstract::Truth(Truth::of(truth));
+            possible &= refine(condition, value, scope, &mut slots);
+        }
+        Expression::LessThanOrEqual(left, right) => {
+            let (l, r) = (
+                value(left, scope, &slots, overflow).numeral(),
+                value(right, scope, &slots, overflow).numeral(),
+            );
+            if let (Some((a, b)), Some((c, d))) = (l.bounds(), r.bounds()) {
+                let (l, r) = if truth {
+                    (D::range(MIN, d), D::range(a, MAX))
+                } else {
+                    (D::range(successor(c), MAX), D::range(MIN, predecessor(b)))
+                };
+                possible &= refine(left, Abstract::Numeral(l), scope, &mut slots);
+                possible &= refine(right, Abstract::Numeral(r), scope, &mut slots);
+            }
+        }
+        Expression::Equal(left, right) => {
+            let (l, r) = (
+                value(left, scope, &slots, overflow),
+                value(right, scope, &slots, overflow),
+            );
+            let (l, r) = if truth {
+                (r, l)
+            } else {
+                (excluding(&l, &r), excluding(&r, &l))
+            };
+            possible &= refine(left, l, scope, &mut slots);
+            possible &= refine(right, r, scope, &mut slots);
+        }
+        _ => {}
+    }
+    possible.then_some(slots)
+}
+
+// narrows the value of a variable, false if none is left; variables that are ⊥ because
+// they are unassigned aren't narrowed, reading them already fails
+fn refine<'a, D: Domain>(
+    expression: &Expression<'a>,
+    value: Abstract<D>,
+    scope: &Scope<'a>,
+    slots: &mut [Abstract<D>],
+) -> bool {
+    if let Expression::Variable(identifier) = expression.unspanned() {
+        if let Some(slot) = lookup(scope, *identifier) {
+            slots[slot] = slots[slot].meet(&value);
+            return !slots[slot].is_bottom();
+        }
+    }
+    true
+}
+
+// what remains of `value` if it differs from `other`, which only excludes something if
+// `other` is a single value at one of the ends of `value`
+fn excluding<D: Domain>(value: &Abstract<D>, other: &Abstract<D>) -> Abstract<D> {
+    match (value, other) {
+        (Abstract::Numeral(numeral), Abstract::Numeral(other)) => {
+            match (numeral.bounds(), other.bounds()) {
+                (Some((low, high)), Some((c, d))) if c == d && c == low => {
+                    Abstract::Numeral(D::range(low + 1, high))
+                }
+                (Some((low, high)), Some((c, d))) if c == d && c == high => {
+                    Abstract::Numeral(D::range(low, high - 1))
+                }
+                _ => value.clone(),
+            }
+        }
+        (Abstract::Truth(_), Abstract::Truth(other)) if other.can_be_true != other.can_be_false => {
+            Abstract::Truth(other.not())
+        }
+        _ => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eval::Store, integer::I32, typecheck::INT_SORT};
+
+    fn warnings<D: Domain>(
+        source: &str,
+        arithmetic: impl Arithmetic,
+    ) -> Result<Vec<(Warning, usize)>, String> {
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        let analysis = annotated_program.analyze_in::<D, _>(arithmetic, Some(&Defaults::default()));
+        Ok(analysis
+            .warnings
+            .0
+            .into_iter()
+            .map(|warning| (warning.error, warning.span.map_or(0, |span| span.start)))
+            .collect())
+    }
+
+    #[test]
+    fn test_loop_widening_and_narrowing() -> Result<(), String> {
+        let source = "program p(n:Int, s:Int) {
+  s := 0;
+  {
+    var i:Int;
+    i := 0;
+    while i ≤ 9 do { s := s+i; i := i+1; }
+    if i = 10 then s := s/i; else s := 1/0;
+  }
+}";
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        let analysis = annotated_program.analyze::<Interval>();
+        assert_eq!(
+            analysis.exit.lookup("s".into()),
+            Some(&Abstract::Numeral(Interval::Between(0, MAX)))
+        );
+        let states: Vec<_> = analysis
+            .points
+            .iter()
+            .map(|point| point.state.to_string())
+            .collect();
+        assert_eq!(states[4], "{n ↦ [-∞, +∞], s ↦ [0, +∞], i ↦ [0, 9]}");
+        assert_eq!(states[6], "{n ↦ [-∞, +∞], s ↦ [0, +∞], i ↦ 10}");
+        // the else branch is never reached, so 1/0 isn't reported
+        assert_eq!(
+            warnings::<Interval>(source, Unbounded)?,
+            vec![(Warning::ConstantCondition(true), 119)]
+        );
+        assert_eq!(
+            warnings::<Interval>(source, I32(Overflow::Checked))?,
+            vec![
+                (Warning::Overflow { always: false }, 95),
+                (Warning::ConstantCondition(true), 119),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_division_by_zero() -> Result<(), String> {
+        let source = "program p(x:Int, y:Int) {
+  y := 10/x;
+  if x = 0 then x := 1; else x := x;
+  y := 10/x;
+  x := 0;
+  y := y/x;
+}";
+        assert_eq!(
+            warnings::<Interval>(source, Unbounded)?,
+            vec![
+                (Warning::DivisionByZero { always: false }, 33),
+                // intervals can't express x ≠ 0 after the else branch
+                (Warning::DivisionByZero { always: false }, 83),
+                (Warning::DivisionByZero { always: true }, 106),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_comparisons_and_remainder() -> Result<(), String> {
+        let source = "program p(x:Int, y:Int) {
+  if x > 0 ∧ x < 10 then y := x % 4; else y := -5 % x;
+  if x ≥ 10 ⇒ 100 % x = 0 then x := 1 % y; else x := y;
+}";
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        let analysis = annotated_program.analyze::<Interval>();
+        let states: Vec<_> = analysis
+            .points
+            .iter()
+            .map(|point| point.state.to_string())
+            .collect();
+        assert_eq!(states[1], "{x ↦ [1, 9], y ↦ [-∞, +∞]}");
+        // x % 4 is in [0, 3] and -5 % x in [-5, 0]
+        assert_eq!(states[3], "{x ↦ [-∞, +∞], y ↦ [-5, 3]}");
+        assert_eq!(states[5], "{x ↦ [10, +∞], y ↦ [-5, 3]}");
+        assert_eq!(
+            warnings::<Interval>(source, Unbounded)?,
+            vec![
+                (Warning::DivisionByZero { always: false }, 75),
+                // 100 % x is only evaluated if x ≥ 10
+                (Warning::DivisionByZero { always: false }, 123),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_overflow() -> Result<(), String> {
+        let source = "program p(x:Int, y:Int) {
+  x := 2147483647;
+  if y ≤ 0 then y := -y; else y := x+x;
+}";
+        assert_eq!(warnings::<Interval>(source, Unbounded)?, vec![]);
+        assert_eq!(
+            warnings::<Interval>(source, I32(Overflow::Checked))?,
+            vec![
+                (Warning::Overflow { always: false }, 68),
+                (Warning::Overflow { always: true }, 82),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_calls_are_inlined() -> Result<(), String> {
+        let source = "var g:Int;
+procedure inc(a:Int; ref b:Int) {
+  b := a+1;
+  g := g+b;
+}
+program p(x:Int, y:Int) {
+  x := 3;
+  call inc(x; y);
+  call inc(y; x);
+  if g = 9 then y := 0; else y := 1;
+}";
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        let analysis = annotated_program.analyze::<Interval>();
+        assert_eq!(analysis.exit.to_string(), "{g ↦ 9, x ↦ 5, y ↦ 0}");
+        // the state in the body is joined over both calls
+        assert_eq!(
+            analysis.points[2].state.to_string(),
+            "{g ↦ [0, 4], a ↦ [3, 4], b ↦ [-∞, +∞]}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_recursion_is_cut_off() -> Result<(), String> {
+        let program = Program::parse(include_str!("../programs/recursion.fsp"))?;
+        let annotated_program = program.typecheck()?;
+        let analysis = annotated_program
+            .analyze_in::<Sign, _>(I32(Overflow::Checked), Some(&Defaults::default()));
+        assert_eq!(analysis.exit.to_string(), "{n ↦ ⊤, f ↦ ⊤, e ↦ ⊤}");
+        let warnings: Vec<_> = analysis
+            .warnings
+            .0
+            .iter()
+            .map(|w| w.error.clone())
+            .collect();
+        assert_eq!(warnings, vec![Warning::Overflow { always: false }; 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_signs() -> Result<(), String> {
+        let source = "program p(x:Int, y:Int, b:Bool) {
+  if 0 ≤ x then {
+    y := x/2 - 1;
+    b := y = 0 ∨ 0 ≤ y;
+    if x ≤ -1 then y := 0;
+  }
+  else y := x*x;
+}";
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        let analysis = annotated_program.analyze::<Sign>();
+        assert_eq!(analysis.exit.to_string(), "{x ↦ ⊤, y ↦ ⊤, b ↦ ⊤}");
+        assert_eq!(
+            analysis.points[1].state.to_string(),
+            "{x ↦ ≥0, y ↦ ⊤, b ↦ ⊤}"
+        );
+        assert_eq!(
+            warnings::<Sign>(source, I32(Overflow::Checked))?,
+            vec![
+                (Warning::ConstantCondition(false), 107),
+                (Warning::Overflow { always: false }, 145),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_arithmetic() -> Result<(), String> {
+        let source = "program p(x:Int, y:Int) {
+  x := 2147483647;
+  x := x+1;
+  if x ≤ 2147483647 then y := 1; else y := 2;
+}";
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        // the exit state holds exactly the values the evaluation ends with
+        fn agree<A: Arithmetic + Copy>(program: &Program, arithmetic: A) -> Result<(), String> {
+            let analysis =
+                program.analyze_in::<Interval, _>(arithmetic, Some(&Defaults::default()));
+            let outputs = program
+                .eval_in(vec![0.into(), 0.into()], arithmetic, Store::default())
+                .map_err(|error| error.to_string())?;
+            let bindings = ["x", "y"].into_iter().zip(outputs);
+            let state = bindings.map(|(identifier, value)| format!("{identifier} ↦ {value}"));
+            assert_eq!(
+                analysis.exit.to_string(),
+                format!("{{{}}}", state.collect::<Vec<_>>().join(", "))
+            );
+            Ok(())
+        }
+        agree(&annotated_program, Unbounded)?;
+        agree(&annotated_program, I32(Overflow::Wrapping))?;
+        agree(&annotated_program, I32(Overflow::Saturating))?;
+
+        let checked = I32(Overflow::Checked);
+        let analysis =
+            annotated_program.analyze_in::<Interval, _>(checked, Some(&Defaults::default()));
+        assert_eq!(analysis.exit, State(None));
+        assert!(annotated_program
+            .eval_in(vec![0.into(), 0.into()], checked, Store::default())
+            .is_err());
+        assert_eq!(
+            warnings::<Interval>(source, checked)?,
+            vec![(Warning::Overflow { always: true }, 52)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_defaults() -> Result<(), String> {
+        let source = "var g:Int;
+program p(x:Int, b:Bool) {
+  var y:Int;
+  var c:Bool;
+  x := y+g;
+  if c then b := true; else b := false;
+}";
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        let defaults = Defaults::none().with(INT_SORT, 7.into());
+        let analysis = annotated_program.analyze_in::<Interval, _>(Unbounded, Some(&defaults));
+        assert_eq!(
+            analysis.points[2].state.to_string(),
+            "{g ↦ 7, x ↦ [-∞, +∞], b ↦ ⊤, y ↦ 7, c ↦ ⊥}"
+        );
+        // reading c fails
+        assert_eq!(analysis.exit, State(None));
+        let defaults = defaults.with(BOOL_SORT, Value::True);
+        let analysis = annotated_program.analyze_in::<Interval, _>(Unbounded, Some(&defaults));
+        assert_eq!(analysis.exit.to_string(), "{g ↦ 7, x ↦ 14, b ↦ true}");
+        let outputs = annotated_program.eval_in(
+            vec![0.into(), Value::False],
+            Unbounded,
+            Store::init(defaults),
+        );
+        assert_eq!(outputs, Ok(vec![14.into(), Value::True]));
+        let analysis = annotated_program.analyze_in::<Interval, _>(Unbounded, None);
+        assert_eq!(
+            analysis.points[2].state.to_string(),
+            "{g ↦ ⊥, x ↦ [-∞, +∞], b ↦ ⊤, y ↦ ⊥, c ↦ ⊥}"
+        );
+        assert_eq!(analysis.exit, State(None));
+        Ok(())
+    }
+}
@@ -0,0 +1,276 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{diagnostic::Diagnostics, typecheck::Signature, types::*};
+
+// a read of a variable that isn't assigned on every path leading to it, so that it may
+// see the default value of the store instead
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unassigned<'a>(pub Identifier<'a>);
+
+// the variables assigned on every path so far
+type Assigned<'a> = HashSet<Identifier<'a>>;
+
+impl<'a> Program<'a> {
+    // parameters count as assigned, and so do the globals inside procedures since they
+    // hold whatever the caller left there; the program has to have been typechecked,
+    // calls that weren't resolved only assign their ref arguments
+    pub fn unassigned_reads(&self) -> Diagnostics<Unassigned<'a>> {
+        let Program(declarations, _, parameters, body, _) = self;
+        let declarations = declarations.sequence();
+        let checker = Checker::new(&declarations);
+        let mut diagnostics = Diagnostics::new();
+        for declaration in declarations {
+            checker.declaration(declaration, &mut diagnostics);
+        }
+        let parameters: Assigned = parameters.identifiers().into_iter().collect();
+        checker.command(body, &parameters, parameters.clone(), &mut diagnostics);
+        diagnostics
+    }
+}
+
+struct Checker<'a> {
+    globals: Assigned<'a>,
+    // the globals each procedure assigns on every path through its body
+    assigns: HashMap<Signature<'a>, Assigned<'a>>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(declarations: &[&Declaration<'a>]) -> Checker<'a> {
+        let mut globals = Assigned::new();
+        let mut procedures = Vec::new();
+        for declaration in declarations {
+            match declaration.unspanned() {
+                Declaration::Variable(identifier, _) => {
+                    globals.insert(*identifier);
+                }
+                Declaration::Procedure(_, in_params, out_params, body, _) => {
+                    if let Some(signature) = declaration.signature() {
+                        procedures.push((signature, parameters(in_params, out_params), body));
+                    }
+                }
+                _ => {}
+            }
+        }
+        // the greatest fixpoint, starting from procedures that assign every global so
+        // that recursive calls don't take away what the rest of the body assigns
+        let mut checker = Checker {
+            assigns: procedures
+                .iter()
+                .map(|(signature, ..)| (signature.clone(), globals.clone()))
+                .collect(),
+            globals,
+        };
+        loop {
+            let assigns = procedures
+                .iter()
+                .map(|(signature, parameters, body)| {
+                    let assigned = checker.command(
+                        body,
+                        parameters,
+                        parameters.clone(),
+                        &mut Diagnostics::new(),
+                    );
+                    let globals = assigned
+                        .intersection(&checker.globals)
+                        .filter(|global| !parameters.contains(global))
+                        .copied()
+                        .collect();
+                    (signature.clone(), globals)
+                })
+                .collect();
+            if assigns == checker.assigns {
+                return checker;
+            }
+            checker.assigns = assigns;
+        }
+    }
+
+    fn declaration(
+        &self,
+        declaration: &Declaration<'a>,
+        diagnostics: &mut Diagnostics<Unassigned<'a>>,
+    ) {
+        match declaration {
+            Declaration::Variable(..) => {}
+            Declaration::Procedure(_, in_params, out_params, body, _) => {
+                let locals = parameters(in_params, out_params);
+                let assigned = locals.union(&self.globals).copied().collect();
+                self.command(body, &locals, assigned, diagnostics);
+            }
+            Declaration::Spanned(span, declaration) => diagnostics.within(*span, |diagnostics| {
+                self.declaration(declaration, diagnostics)
+            }),
+        }
+    }
+
+    // what is assigned after `command` if `assigned` is before it, `locals` are the
+    // names that don't refer to globals
+    fn command(
+        &self,
+        command: &Command<'a>,
+        locals: &Assigned<'a>,
+        mut assigned: Assigned<'a>,
+        diagnostics: &mut Diagnostics<Unassigned<'a>>,
+    ) -> Assigned<'a> {
+        match command {
+            Command::Assign(identifier, expression) => {
+                expression.unassigned_reads(&assigned, diagnostics);
+                assigned.insert(*identifier);
+                assigned
+            }
+            // the new variable starts out unassigned and the outer one is as it was
+            // once the block ends
+            Command::Var(identifier, _, command) => {
+                let outer = assigned.contains(identifier);
+                assigned.remove(identifier);
+                let mut locals = locals.clone();
+                locals.insert(*identifier);
+                let mut assigned = self.command(command, &locals, assigned, diagnostics);
+                assigned.remove(identifier);
+                if outer {
+                    assigned.insert(*identifier);
+                }
+                assigned
+            }
+            Command::Sequence(first, second) => {
+                let assigned = self.command(first, locals, assigned, diagnostics);
+                self.command(second, locals, assigned, diagnostics)
+            }
+            Command::IfElse(expression, if_branch, else_branch) => {
+                expression.unassigned_reads(&assigned, diagnostics);
+                let if_assigned = self.command(if_branch, locals, assigned.clone(), diagnostics);
+                let else_assigned = self.command(else_branch, locals, assigned, diagnostics);
+                if_assigned.intersection(&else_assigned).copied().collect()
+            }
+            // the body may not run at all, and only adds to what is assigned when it does
+            Command::If(expression, body) | Command::While(expression, body, _) => {
+                expression.unassigned_reads(&assigned, diagnostics);
+                self.command(body, locals, assigned.clone(), diagnostics);
+                assigned
+            }
+            Command::Call(_, expressions, variables, signature) => {
+                for expression in expressions.sequence() {
+                    expression.unassigned_reads(&assigned, diagnostics);
+                }
+                assigned.extend(variables.sequence());
+                if let Some(globals) = signature
+                    .as_ref()
+                    .and_then(|signature| self.assigns.get(signature))
+                {
+                    assigned.extend(globals.difference(locals));
+                }
+                assigned
+            }
+            Command::Spanned(span, command) => diagnostics.within(*span, |diagnostics| {
+                self.command(command, locals, assigned, diagnostics)
+            }),
+        }
+    }
+}
+
+fn parameters<'a>(in_params: &Parameters<'a>, out_params: &Parameters<'a>) -> Assigned<'a> {
+    in_params
+        .identifiers()
+        .into_iter()
+        .chain(out_params.identifiers())
+        .collect()
+}
+
+impl<'a> Expression<'a> {
+    fn unassigned_reads(
+        &self,
+        assigned: &Assigned<'a>,
+        diagnostics: &mut Diagnostics<Unassigned<'a>>,
+    ) {
+        match self {
+            Expression::Value(_) => {}
+            Expression::Variable(identifier) => {
+                if !assigned.contains(identifier) {
+                    diagnostics.push(Unassigned(*identifier));
+                }
+            }
+            Expression::Negative(expression) | Expression::Not(expression) => {
+                expression.unassigned_reads(assigned, diagnostics)
+            }
+            Expression::Sum(left, right)
+            | Expression::Difference(left, right)
+            | Expression::Product(left, right)
+            | Expression::Division(left, right)
+            | Expression::Remainder(left, right)
+            | Expression::Equal(left, right)
+            | Expression::NotEqual(left, right)
+            | Expression::LessThan(left, right)
+            | Expression::LessThanOrEqual(left, right)
+            | Expression::GreaterThan(left, right)
+            | Expression::GreaterThanOrEqual(left, right)
+            | Expression::And(left, right)
+            | Expression::Or(left, right)
+            | Expression::Implies(left, right)
+            | Expression::Equivalent(left, right) => {
+                left.unassigned_reads(assigned, diagnostics);
+                right.unassigned_reads(assigned, diagnostics);
+            }
+            Expression::Spanned(span, expression) => diagnostics.within(*span, |diagnostics| {
+                expression.unassigned_reads(assigned, diagnostics)
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unassigned_reads(source: &str) -> Result<Vec<(&str, usize)>, String> {
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        Ok(annotated_program
+            .unassigned_reads()
+            .0
+            .into_iter()
+            .map(|read| (read.error.0 .0, read.span.map_or(0, |span| span.start)))
+            .collect())
+    }
+
+    #[test]
+    fn test_gcd() -> Result<(), String> {
+        let reads = unassigned_reads(include_str!("../programs/gcd.fsp"))?;
+        assert_eq!(reads, vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_branches_and_blocks() -> Result<(), String> {
+        let source = "var g:Int;
+program p(a:Int) {
+  var x:Int;
+  var b:Bool;
+  if 0 ≤ a then x := 1; else b := true;
+  a := x;
+  x := 1;
+  while b do { var x:Int; a := x; }
+  a := x + g;
+}";
+        assert_eq!(
+            unassigned_reads(source)?,
+            vec![("x", 106), ("b", 127), ("x", 150), ("g", 166)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_calls() -> Result<(), String> {
+        let source = "var g:Int;
+procedure set(n:Int; ref r:Int) { g := n; r := g; }
+procedure maybe(n:Int; ref r:Int) { if n ≤ 0 then call set(n; r); }
+program p(a:Int) {
+  var x:Int;
+  var y:Int;
+  call maybe(y; x);
+  a := x + g;
+  call set(a; y);
+  a := y + g;
+}";
+        assert_eq!(unassigned_reads(source)?, vec![("y", 191), ("g", 209)]);
+        Ok(())
+    }
+}
@@ -0,0 +1,352 @@
+use std::{collections::HashMap, ops::RangeInclusive};
+
+use crate::{
+    diagnostic::{Diagnostic, Diagnostics},
+    eval::{
+        Environment, Limits, Outcome, ProcedureEnvironment, RuntimeError, Store,
+        VariableEnvironment,
+    },
+    hoare::free_variables,
+    trace::Trace,
+    typecheck::{TypeError, BOOL_SORT},
+    types::*,
+};
+
+// postconditions refer to the value a parameter `x` had when the program terminated as
+// `x` and to the value it was called with as `old_x`
+static OLD: &str = "old_";
+
+// Int parameters range over `range`, Bool parameters over both truth values
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bounds {
+    pub range: RangeInclusive<i32>,
+    pub limits: Limits,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict<'a> {
+    // the inputs the postconditions held for, and those whose evaluation ran out of fuel
+    // and so couldn't be checked
+    Verified { verified: usize, diverged: usize },
+    Counterexample(Counterexample<'a>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Counterexample<'a> {
+    pub parameters: Vec<Identifier<'a>>,
+    pub inputs: Vec<Value>,
+    pub failure: Failure<'a>,
+    pub trace: Trace<'a>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Failure<'a> {
+    Error(Diagnostic<RuntimeError<'a>>),
+    // a postcondition that evaluates to false or can't be evaluated
+    Violated {
+        postcondition: Expression<'a>,
+        outputs: Vec<Value>,
+    },
+}
+
+impl<'a> Program<'a> {
+    // runs the program on every input within the bounds, in lexicographic order, until a
+    // run fails or ends in a store that a postcondition doesn't hold in
+    pub fn check_bounded(
+        &self,
+        postconditions: &[Expression<'a>],
+        bounds: &Bounds,
+    ) -> Result<Verdict<'a>, Diagnostics<TypeError<'a>>> {
+        let parameters = self.2.identifiers();
+        let sorts = self.2.sorts();
+        let old = old(&parameters, postconditions);
+
+        let mut diagnostics = Diagnostics::new();
+        // `old_x` would otherwise silently refer to a variable of that name
+        let mut variables = parameters.clone();
+        for declaration in self.0.sequence() {
+            if let Declaration::Variable(identifier, _) = declaration.unspanned() {
+                variables.push(*identifier);
+            }
+        }
+        for (_, identifier) in &old {
+            if variables.contains(identifier) {
+                diagnostics.push(TypeError::AmbiguousOld(*identifier));
+            }
+        }
+        let mut variable_typings: HashMap<_, _> = old
+            .iter()
+            .map(|(index, identifier)| (*identifier, sorts[*index]))
+            .collect();
+        variable_typings.extend(parameters.iter().copied().zip(sorts.clone()));
+        for postcondition in postconditions {
+            postcondition.typecheck_condition(&variable_typings, &mut diagnostics);
+        }
+        if !diagnostics.is_empty() {
+            return Err(diagnostics);
+        }
+
+        let domains: Vec<Vec<Value>> = sorts
+            .iter()
+            .map(|sort| {
+                if *sort == BOOL_SORT {
+                    vec![Value::False, Value::True]
+                } else {
+                    bounds.range.clone().map(Value::from).collect()
+                }
+            })
+            .collect();
+
+        let (mut verified, mut diverged) = (0, 0);
+        for inputs in Inputs::new(&domains) {
+            let failure = match self.eval_with_limits(inputs.clone(), bounds.limits) {
+                Ok(Outcome::Terminated(outputs)) => {
+                    let mut store = Store::default();
+                    let mut variable_environment = VariableEnvironment::init();
+                    let old = old
+                        .iter()
+                        .map(|(index, identifier)| (identifier, &inputs[*index]));
+                    let bindings = old.chain(parameters.iter().zip(&outputs));
+                    for (address, (identifier, value)) in bindings.enumerate() {
+                        store = store.update(address, value.clone());
+                        variable_environment = variable_environment.update(identifier, &address);
+                    }
+                    let environment: Environment =
+                        (variable_environment, ProcedureEnvironment::init());
+                    let violated = postconditions.iter().find(|postcondition| {
+                        postcondition.eval(&store, &environment) != Ok(Value::True)
+                    });
+                    match violated {
+                        Some(postcondition) => Some(Failure::Violated {
+                            postcondition: postcondition.clone(),
+                            outputs,
+                        }),
+                        None => {
+                            verified += 1;
+                            None
+                        }
+                    }
+                }
+                Ok(Outcome::Diverged(..)) => {
+                    diverged += 1;
+                    None
+                }
+                Err(error) => Some(Failure::Error(error)),
+            };
+            if let Some(failure) = failure {
+                // runs are deterministic, so the traced one fails the same way
+                let (_, trace) = self.eval_traced(inputs.clone());
+                return Ok(Verdict::Counterexample(Counterexample {
+                    parameters,
+                    inputs,
+                    failure,
+                    trace,
+                }));
+            }
+        }
+        Ok(Verdict::Verified { verified, diverged })
+    }
+}
+
+// the index of every parameter whose input value the postconditions refer to, with the
+// identifier they use for it, so that the name doesn't have to outlive the call
+fn old<'a>(
+    parameters: &[Identifier<'a>],
+    postconditions: &[Expression<'a>],
+) -> Vec<(usize, Identifier<'a>)> {
+    let mut mentioned = Vec::new();
+    for postcondition in postconditions {
+        free_variables(postcondition, &mut mentioned);
+    }
+    let parameters = parameters.iter().enumerate();
+    parameters
+        .filter_map(|(index, parameter)| {
+            let name = format!("{OLD}{parameter}");
+            let identifier = mentioned
+                .iter()
+                .find(|identifier| identifier.to_string() == name)?;
+            Some((index, *identifier))
+        })
+        .collect()
+}
+
+// every sequence with one value of each domain, the last one changing fastest
+pub(crate) struct Inputs<'d> {
+    domains: &'d [Vec<Value>],
+    indices: Option<Vec<usize>>,
+}
+
+impl<'d> Inputs<'d> {
+    pub(crate) fn new(domains: &'d [Vec<Value>]) -> Inputs<'d> {
+        let indices = if domains.iter().any(Vec::is_empty) {
+            None
+        } else {
+            Some(vec![0; domains.len()])
+        };
+        Inputs { domains, indices }
+    }
+}
+
+impl<'d> Iterator for Inputs<'d> {
+    type Item = Vec<Value>;
+
+    fn next(&mut self) -> Option<Vec<Value>> {
+        let indices = self.indices.as_mut()?;
+        let inputs = indices
+            .iter()
+            .zip(self.domains)
+            .map(|(index, domain)| domain[*index].clone())
+            .collect();
+        let mut position = indices.len();
+        loop {
+            if position == 0 {
+                self.indices = None;
+                break;
+            }
+            position -= 1;
+            indices[position] += 1;
+            if indices[position] < self.domains[position].len() {
+                break;
+            }
+            indices[position] = 0;
+        }
+        Some(inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gcd::{a_b_gcd_parameter_sequence, generate_gcd};
+
+    use super::*;
+
+    fn bounds(range: RangeInclusive<i32>) -> Bounds {
+        Bounds {
+            range,
+            limits: Limits {
+                steps: 1000,
+                depth: 50,
+            },
+        }
+    }
+
+    fn check<'a>(
+        source: &'a str,
+        postconditions: &[&'a str],
+        range: RangeInclusive<i32>,
+    ) -> Result<Verdict<'a>, String> {
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        let postconditions = postconditions
+            .iter()
+            .map(|postcondition| Expression::parse(postcondition))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(annotated_program.check_bounded(&postconditions, &bounds(range))?)
+    }
+
+    #[test]
+    fn test_verified() -> Result<(), String> {
+        let annotated_program = generate_gcd(a_b_gcd_parameter_sequence()).typecheck()?;
+        let postconditions = [
+            Expression::parse("a = old_a ∧ b = old_b")?,
+            Expression::parse("¬(a ≤ 0) ∧ ¬(b ≤ 0) ∨ c = a*b + (a+b)")?,
+        ];
+        assert_eq!(
+            annotated_program.check_bounded(&postconditions, &bounds(0..=5))?,
+            Verdict::Verified {
+                verified: 1296,
+                diverged: 0
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_counterexample() -> Result<(), String> {
+        let source = "program square(x:Int, y:Int) {\n  y := x*x;\n}";
+        let Verdict::Counterexample(counterexample) = check(source, &["y ≤ 10"], -8..=8)? else {
+            panic!("y ≤ 10 doesn't hold for x = -8");
+        };
+        assert_eq!(counterexample.inputs, vec![(-8).into(), (-8).into()]);
+        assert_eq!(
+            counterexample.to_string(),
+            "counterexample x = -8, y = -8
+postcondition y≤10 doesn't hold for x = -8, y = 64
+y:=(x*x); | top 2 | {x ↦ 0, y ↦ 1} | 1: -8 → 64
+"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_runtime_error() -> Result<(), String> {
+        let source = "program p(b:Bool, x:Int) {\n  if b then x := 10/x;\n}";
+        let Verdict::Counterexample(counterexample) = check(source, &[], -2..=2)? else {
+            panic!("10/x fails for x = 0");
+        };
+        assert_eq!(counterexample.inputs, vec![Value::True, 0.into()]);
+        let Failure::Error(error) = counterexample.failure else {
+            panic!("the program fails before the postconditions are checked");
+        };
+        assert_eq!(error.error, RuntimeError::DivisionByZero);
+        assert_eq!(counterexample.trace.0.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_divergence() -> Result<(), String> {
+        let source = "program p(x:Int) {\n  while ¬(x = 0) do x := x-1;\n}";
+        assert_eq!(
+            check(source, &["x = 0"], -2..=2)?,
+            Verdict::Verified {
+                verified: 3,
+                diverged: 2
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_ambiguous_old() -> Result<(), String> {
+        let postconditions = [Expression::parse("x = old_x")?];
+        for source in [
+            "program p(x:Int, old_x:Int) {\n  x := 1;\n}",
+            "var old_x:Int;\nprogram p(x:Int) {\n  x := 1;\n}",
+        ] {
+            let annotated_program = Program::parse(source)?.typecheck()?;
+            let diagnostics = annotated_program
+                .check_bounded(&postconditions, &bounds(0..=1))
+                .unwrap_err();
+            assert_eq!(
+                diagnostics
+                    .0
+                    .iter()
+                    .map(|d| d.error.clone())
+                    .collect::<Vec<_>>(),
+                vec![TypeError::AmbiguousOld("old_x".into())]
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_postcondition_type_error() -> Result<(), String> {
+        let source = "program p(x:Int) {\n  x := 1;\n}";
+        let postconditions = [Expression::parse("x")?, Expression::parse("y = 1")?];
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        let diagnostics = annotated_program
+            .check_bounded(&postconditions, &bounds(0..=1))
+            .unwrap_err();
+        assert_eq!(
+            diagnostics
+                .0
+                .iter()
+                .map(|d| d.error.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                TypeError::NonBoolCondition(Sort("Int".into())),
+                TypeError::UndefinedIdentifier("y".into())
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,796 @@
+use std::collections::HashMap;
+
+use crate::{
+    diagnostic::Diagnostic,
+    eval::{Address, RuntimeError, Store},
+    integer::{Arithmetic, Integer, Unbounded},
+    typecheck::Signature,
+    types::*,
+};
+
+// where a variable is kept, relative to the frame of the procedure it is used in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Location {
+    Global(Address),
+    // from the base of the frame, the in parameters first and then the locals
+    Local(usize),
+    // the address the caller passed for the nth ref parameter
+    Ref(usize),
+}
+
+// the instructions of a stack machine, the operands are taken from the top of the stack
+// with the right one on top and the result is pushed in their place
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction<'a> {
+    Push(Value),
+    // the identifier is only for the error if the variable wasn't assigned
+    Load(Location, Identifier<'a>),
+    Store(Location),
+    // a fresh variable of the sort, as `Store::allocate` makes it
+    Fresh(Location, Sort<'a>),
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Negate,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Not,
+    Jump(usize),
+    JumpIfFalse(usize),
+    // pops the in arguments into a new frame that starts `top` slots above the base of
+    // the current one and binds the ref parameters to the addresses of `refs`
+    Call {
+        procedure: usize,
+        top: usize,
+        refs: Vec<Location>,
+    },
+    Return,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry<'a> {
+    pub name: Identifier<'a>,
+    pub address: usize,
+    pub arguments: usize,
+}
+
+// a compiled program, the code of the program body comes first and ends with the return
+// that stops the machine, followed by that of the procedures
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bytecode<'a> {
+    pub code: Vec<Instruction<'a>>,
+    // of the innermost command or expression each instruction was compiled from
+    pub spans: Vec<Option<Span>>,
+    pub procedures: Vec<Entry<'a>>,
+    pub globals: usize,
+    pub parameters: usize,
+}
+
+impl<'a> Program<'a> {
+    // the program has to have been typechecked, so that calls are annotated
+    pub fn compile(&self) -> Result<Bytecode<'a>, Diagnostic<RuntimeError<'a>>> {
+        let Program(declarations, _, parameters, body, _) = self;
+        let mut compiler = Compiler::default();
+        let mut scope = Scope::default();
+        // the procedures are compiled after the program body, in the scope they were
+        // declared in
+        let mut definitions = Vec::new();
+        let declarations = declarations.sequence();
+        let mut index = 0;
+        while index < declarations.len() {
+            let group: Vec<_> = declarations[index..]
+                .iter()
+                .map_while(|declaration| match declaration.unspanned() {
+                    Declaration::Procedure(name, in_params, out_params, body, _) => {
+                        Some((*declaration, name, in_params, out_params, body))
+                    }
+                    _ => None,
+                })
+                .collect();
+            if group.is_empty() {
+                compiler.declaration(declarations[index], &mut scope)?;
+                index += 1;
+                continue;
+            }
+            index += group.len();
+            // a group of consecutive procedures can call each other
+            for (declaration, name, in_params, ..) in &group {
+                if let Some(signature) = declaration.signature() {
+                    scope
+                        .procedures
+                        .insert(signature, compiler.procedures.len());
+                }
+                compiler.procedures.push(Entry {
+                    name: **name,
+                    address: 0,
+                    arguments: in_params.len(),
+                });
+            }
+            for (_, _, in_params, out_params, body) in group {
+                definitions.push((scope.clone(), in_params, out_params, body));
+            }
+        }
+        let globals = compiler.globals;
+
+        let mut program = scope.clone();
+        for (slot, identifier) in parameters.identifiers().into_iter().enumerate() {
+            program.variables.insert(identifier, Location::Local(slot));
+        }
+        compiler.command(body, &program, parameters.len())?;
+        compiler.emit(Instruction::Return);
+
+        for (procedure, definition) in definitions.into_iter().enumerate() {
+            let (mut scope, in_params, out_params, body) = definition;
+            compiler.procedures[procedure].address = compiler.code.len();
+            for (slot, identifier) in in_params.identifiers().into_iter().enumerate() {
+                scope.variables.insert(identifier, Location::Local(slot));
+            }
+            for (index, identifier) in out_params.identifiers().into_iter().enumerate() {
+                scope.variables.insert(identifier, Location::Ref(index));
+            }
+            compiler.command(body, &scope, in_params.len())?;
+            compiler.emit(Instruction::Return);
+        }
+
+        Ok(Bytecode {
+            code: compiler.code,
+            spans: compiler.spans,
+            procedures: compiler.procedures,
+            globals,
+            parameters: parameters.len(),
+        })
+    }
+}
+
+// what the names in a command refer to
+#[derive(Debug, Clone, Default)]
+struct Scope<'a> {
+    variables: HashMap<Identifier<'a>, Location>,
+    procedures: HashMap<Signature<'a>, usize>,
+}
+
+#[derive(Default)]
+struct Compiler<'a> {
+    code: Vec<Instruction<'a>>,
+    spans: Vec<Option<Span>>,
+    procedures: Vec<Entry<'a>>,
+    globals: usize,
+    span: Option<Span>,
+}
+
+impl<'a> Compiler<'a> {
+    fn emit(&mut self, instruction: Instruction<'a>) -> usize {
+        self.code.push(instruction);
+        self.spans.push(self.span);
+        self.code.len() - 1
+    }
+
+    // the address of the next instruction
+    fn here(&self) -> usize {
+        self.code.len()
+    }
+
+    // points the jump at `from` to the next instruction
+    fn patch(&mut self, from: usize) {
+        let here = self.here();
+        match &mut self.code[from] {
+            Instruction::Jump(to) | Instruction::JumpIfFalse(to) => *to = here,
+            instruction => unreachable!("{instruction:?} isn't a jump"),
+        }
+    }
+
+    fn within(
+        &mut self,
+        span: Span,
+        compile: impl FnOnce(&mut Self) -> Result<(), Diagnostic<RuntimeError<'a>>>,
+    ) -> Result<(), Diagnostic<RuntimeError<'a>>> {
+        let outer = self.span.replace(span);
+        let result = compile(self);
+        self.span = outer;
+        result.map_err(|error| error.or_span(span))
+    }
+
+    // globals are allocated by the code before the program body
+    fn declaration(
+        &mut self,
+        declaration: &Declaration<'a>,
+        scope: &mut Scope<'a>,
+    ) -> Result<(), Diagnostic<RuntimeError<'a>>> {
+        match declaration {
+            Declaration::Variable(identifier, sort) => {
+                let location = Location::Global(self.globals);
+                scope.variables.insert(*identifier, location);
+                self.emit(Instruction::Fresh(location, *sort));
+                self.globals += 1;
+                Ok(())
+            }
+            Declaration::Procedure(..) => Ok(()),
+            Declaration::Spanned(span, declaration) => {
+                self.within(*span, |compiler| compiler.declaration(declaration, scope))
+            }
+        }
+    }
+
+    // `top` is the number of slots of the frame in use, where a var or the frame of a
+    // call starts
+    fn command(
+        &mut self,
+        command: &Command<'a>,
+        scope: &Scope<'a>,
+        top: usize,
+    ) -> Result<(), Diagnostic<RuntimeError<'a>>> {
+        match command {
+            Command::Assign(identifier, expression) => {
+                let location = lookup(scope, *identifier)?;
+                self.expression(expression, scope)?;
+                self.emit(Instruction::Store(location));
+            }
+            Command::Var(identifier, sort, command) => {
+                let location = Location::Local(top);
+                self.emit(Instruction::Fresh(location, *sort));
+                let mut scope = scope.clone();
+                scope.variables.insert(*identifier, location);
+                self.command(command, &scope, top + 1)?;
+            }
+            Command::Sequence(first, second) => {
+                self.command(first, scope, top)?;
+                self.command(second, scope, top)?;
+            }
+            Command::IfElse(condition, if_branch, else_branch) => {
+                self.expression(condition, scope)?;
+                let to_else = self.emit(Instruction::JumpIfFalse(0));
+                self.command(if_branch, scope, top)?;
+                let to_end = self.emit(Instruction::Jump(0));
+                self.patch(to_else);
+                self.command(else_branch, scope, top)?;
+                self.patch(to_end);
+            }
+            Command::If(condition, if_branch) => {
+                self.expression(condition, scope)?;
+                let to_end = self.emit(Instruction::JumpIfFalse(0));
+                self.command(if_branch, scope, top)?;
+                self.patch(to_end);
+            }
+            Command::While(condition, body, _) => {
+                let start = self.here();
+                self.expression(condition, scope)?;
+                let to_end = self.emit(Instruction::JumpIfFalse(0));
+                self.command(body, scope, top)?;
+                self.emit(Instruction::Jump(start));
+                self.patch(to_end);
+            }
+            Command::Call(identifier, expressions, variables, signature) => {
+                let signature = signature
+                    .as_ref()
+                    .ok_or(RuntimeError::UnannotatedCall(*identifier))?;
+                let procedure = *scope
+                    .procedures
+                    .get(signature)
+                    .ok_or_else(|| RuntimeError::UnknownSignature(signature.clone()))?;
+                for expression in expressions.sequence() {
+                    self.expression(&expression, scope)?;
+                }
+                let refs = variables
+                    .sequence()
+                    .into_iter()
+                    .map(|identifier| lookup(scope, identifier))
+                    .collect::<Result<_, _>>()?;
+                self.emit(Instruction::Call {
+                    procedure,
+                    top,
+                    refs,
+                });
+            }
+            Command::Spanned(span, command) => {
+                self.within(*span, |compiler| compiler.command(command, scope, top))?;
+            }
+        }
+        Ok(())
+    }
+
+    // the operands are evaluated from left to right like `Expression::eval` does, and
+    // the right operand of ∧, ∨ and ⇒ only if the left one doesn't decide
+    fn expression(
+        &mut self,
+        expression: &Expression<'a>,
+        scope: &Scope<'a>,
+    ) -> Result<(), Diagnostic<RuntimeError<'a>>> {
+        let operator = match expression {
+            Expression::Value(value) => {
+                self.emit(Instruction::Push(value.clone()));
+                return Ok(());
+            }
+            Expression::Variable(identifier) => {
+                let location = lookup(scope, *identifier)?;
+                self.emit(Instruction::Load(location, *identifier));
+                return Ok(());
+            }
+            Expression::Negative(operand) | Expression::Not(operand) => {
+                self.expression(operand, scope)?;
+                match expression {
+                    Expression::Negative(_) => self.emit(Instruction::Negate),
+                    _ => self.emit(Instruction::Not),
+                };
+                return Ok(());
+            }
+            Expression::And(left, right) => {
+                self.expression(left, scope)?;
+                let to_false = self.emit(Instruction::JumpIfFalse(0));
+                self.expression(right, scope)?;
+                let to_end = self.emit(Instruction::Jump(0));
+                self.patch(to_false);
+                self.emit(Instruction::Push(Value::False));
+                self.patch(to_end);
+                return Ok(());
+            }
+            Expression::Or(left, right) | Expression::Implies(left, right) => {
+                self.expression(left, scope)?;
+                if let Expression::Implies(..) = expression {
+                    self.emit(Instruction::Not);
+                }
+                let to_right = self.emit(Instruction::JumpIfFalse(0));
+                self.emit(Instruction::Push(Value::True));
+                let to_end = self.emit(Instruction::Jump(0));
+                self.patch(to_right);
+                self.expression(right, scope)?;
+                self.patch(to_end);
+                return Ok(());
+            }
+            Expression::Spanned(span, expression) => {
+                return self.within(*span, |compiler| compiler.expression(expression, scope));
+            }
+            Expression::Sum(..) => Instruction::Add,
+            Expression::Difference(..) => Instruction::Subtract,
+            Expression::Product(..) => Instruction::Multiply,
+            Expression::Division(..) => Instruction::Divide,
+            Expression::Remainder(..) => Instruction::Remainder,
+            Expression::Equal(..) | Expression::Equivalent(..) => Instruction::Equal,
+            Expression::NotEqual(..) => Instruction::NotEqual,
+            Expression::LessThan(..) => Instruction::Less,
+            Expression::LessThanOrEqual(..) => Instruction::LessOrEqual,
+            Expression::GreaterThan(..) => Instruction::Greater,
+            Expression::GreaterThanOrEqual(..) => Instruction::GreaterOrEqual,
+        };
+        let (left, right) = match expression {
+            Expression::Sum(left, right)
+            | Expression::Difference(left, right)
+            | Expression::Product(left, right)
+            | Expression::Division(left, right)
+            | Expression::Remainder(left, right)
+            | Expression::Equal(left, right)
+            | Expression::Equivalent(left, right)
+            | Expression::NotEqual(left, right)
+            | Expression::LessThan(left, right)
+            | Expression::LessThanOrEqual(left, right)
+            | Expression::GreaterThan(left, right)
+            | Expression::GreaterThanOrEqual(left, right) => (left, right),
+            _ => unreachable!("the other expressions returned above"),
+        };
+        self.expression(left, scope)?;
+        self.expression(right, scope)?;
+        self.emit(operator);
+        Ok(())
+    }
+}
+
+fn lookup<'a>(
+    scope: &Scope<'a>,
+    identifier: Identifier<'a>,
+) -> Result<Location, Diagnostic<RuntimeError<'a>>> {
+    scope
+        .variables
+        .get(&identifier)
+        .copied()
+        .ok_or_else(|| RuntimeError::UnknownIdentifier(identifier).into())
+}
+
+// a procedure being executed
+struct Frame {
+    base: Address,
+    refs: Vec<Address>,
+    // where to continue once it returns
+    return_to: usize,
+}
+
+impl Frame {
+    fn address(&self, location: &Location) -> Address {
+        match location {
+            Location::Global(address) => *address,
+            Location::Local(slot) => self.base + slot,
+            Location::Ref(index) => self.refs[*index],
+        }
+    }
+}
+
+impl<'a> Bytecode<'a> {
+    pub fn run(
+        &self,
+        value_sequence: Vec<Value>,
+    ) -> Result<Vec<Value>, Diagnostic<RuntimeError<'a>>> {
+        self.run_in(value_sequence, Unbounded, Store::default())
+    }
+
+    // `Program::eval_in` on the machine, the variables have the addresses the
+    // evaluation gives them
+    pub fn run_in<A: Arithmetic>(
+        &self,
+        value_sequence: Vec<Value>,
+        arithmetic: A,
+        store: Store,
+    ) -> Result<Vec<Value>, Diagnostic<RuntimeError<'a>>> {
+        if value_sequence.len() != self.parameters {
+            return Err(RuntimeError::ArityMismatch {
+                expected: self.parameters,
+                found: value_sequence.len(),
+            }
+            .into());
+        }
+        let mut machine = Machine {
+            memory: Vec::new(),
+            stack: Vec::new(),
+            store,
+            arithmetic,
+        };
+        let initial = machine.store.map.clone();
+        for (address, value) in initial.iter() {
+            machine.write(*address, Some(value.clone()));
+        }
+        let arguments: Vec<_> = (self.globals..self.globals + self.parameters).collect();
+        for (address, value) in arguments.iter().zip(value_sequence) {
+            let value = machine.represent(value)?;
+            machine.write(*address, Some(value));
+        }
+        let mut frames = vec![Frame {
+            base: self.globals,
+            refs: Vec::new(),
+            return_to: self.code.len(),
+        }];
+        let mut counter = 0;
+        while counter < self.code.len() {
+            counter = machine
+                .execute(counter, self, &mut frames)
+                .map_err(|error| match self.spans[counter] {
+                    Some(span) => error.or_span(span),
+                    None => error,
+                })?;
+        }
+        Ok(arguments
+            .iter()
+            .map(|address| {
+                machine.memory[*address]
+                    .clone()
+                    .expect("inputs are assigned")
+            })
+            .collect())
+    }
+}
+
+struct Machine<A> {
+    // by address, none where nothing was written
+    memory: Vec<Option<Value>>,
+    stack: Vec<Value>,
+    // only for the defaults of fresh variables
+    store: Store,
+    arithmetic: A,
+}
+
+impl<A: Arithmetic> Machine<A> {
+    fn write(&mut self, address: Address, value: Option<Value>) {
+        if address >= self.memory.len() {
+            self.memory.resize(address + 1, None);
+        }
+        self.memory[address] = value;
+    }
+
+    fn represent<'a>(&self, value: Value) -> Result<Value, Diagnostic<RuntimeError<'a>>> {
+        match value {
+            Value::Numeral(numeral) => Ok(Value::Numeral(self.arithmetic.represent(numeral)?)),
+            value => Ok(value),
+        }
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("the operands were pushed")
+    }
+
+    fn pop_numeral<'a>(&mut self) -> Result<Integer, Diagnostic<RuntimeError<'a>>> {
+        match self.pop() {
+            Value::Numeral(numeral) => Ok(numeral),
+            value => Err(RuntimeError::NotANumeral(value).into()),
+        }
+    }
+
+    fn push_truth(&mut self, truth: bool) {
+        self.stack
+            .push(if truth { Value::True } else { Value::False });
+    }
+
+    // returns the address of the next instruction
+    fn execute<'a>(
+        &mut self,
+        counter: usize,
+        bytecode: &Bytecode<'a>,
+        frames: &mut Vec<Frame>,
+    ) -> Result<usize, Diagnostic<RuntimeError<'a>>> {
+        let instruction = &bytecode.code[counter];
+        let frame = frames.last().expect("the program's frame is left last");
+        match instruction {
+            Instruction::Push(value) => {
+                let value = self.represent(value.clone())?;
+                self.stack.push(value);
+            }
+            Instruction::Load(location, identifier) => {
+                let value = self
+                    .memory
+                    .get(frame.address(location))
+                    .cloned()
+                    .flatten()
+                    .ok_or(RuntimeError::Uninitialized(*identifier))?;
+                self.stack.push(value);
+            }
+            Instruction::Store(location) => {
+                let value = self.pop();
+                self.write(frame.address(location), Some(value));
+            }
+            Instruction::Fresh(location, sort) => {
+                let default = self
+                    .store
+                    .defaults
+                    .as_ref()
+                    .and_then(|defaults| defaults.of(*sort))
+                    .cloned();
+                self.write(frame.address(location), default);
+            }
+            Instruction::Add
+            | Instruction::Subtract
+            | Instruction::Multiply
+            | Instruction::Divide
+            | Instruction::Remainder => {
+                let right = self.pop_numeral()?;
+                let left = self.pop_numeral()?;
+                let arithmetic = &self.arithmetic;
+                let result = match instruction {
+                    Instruction::Add => arithmetic.sum(left, right),
+                    Instruction::Subtract => arithmetic.difference(left, right),
+                    Instruction::Multiply => arithmetic.product(left, right),
+                    Instruction::Divide => arithmetic.quotient(left, right),
+                    _ => arithmetic.remainder(left, right),
+                }?;
+                self.stack.push(Value::Numeral(result));
+            }
+            Instruction::Negate => {
+                let operand = self.pop_numeral()?;
+                let result = self.arithmetic.negative(operand)?;
+                self.stack.push(Value::Numeral(result));
+            }
+            Instruction::Equal | Instruction::NotEqual => {
+                let (right, left) = (self.pop(), self.pop());
+                self.push_truth((left == right) == (*instruction == Instruction::Equal));
+            }
+            Instruction::Less
+            | Instruction::LessOrEqual
+            | Instruction::Greater
+            | Instruction::GreaterOrEqual => {
+                let right = self.pop_numeral()?;
+                let left = self.pop_numeral()?;
+                self.push_truth(match instruction {
+                    Instruction::Less => left < right,
+                    Instruction::LessOrEqual => left <= right,
+                    Instruction::Greater => left > right,
+                    _ => left >= right,
+                });
+            }
+            Instruction::Not => {
+                let operand = self.pop();
+                self.push_truth(operand != Value::True);
+            }
+            Instruction::Jump(to) => return Ok(*to),
+            Instruction::JumpIfFalse(to) => {
+                if self.pop() != Value::True {
+                    return Ok(*to);
+                }
+            }
+            Instruction::Call {
+                procedure,
+                top,
+                refs,
+            } => {
+                let entry = &bytecode.procedures[*procedure];
+                let base = frame.base + top;
+                let arguments = self.stack.split_off(self.stack.len() - entry.arguments);
+                let refs = refs
+                    .iter()
+                    .map(|location| frame.address(location))
+                    .collect();
+                for (index, value) in arguments.into_iter().enumerate() {
+                    self.write(base + index, Some(value));
+                }
+                frames.push(Frame {
+                    base,
+                    refs,
+                    return_to: counter + 1,
+                });
+                return Ok(entry.address);
+            }
+            Instruction::Return => {
+                let frame = frames.pop().expect("every return has its frame");
+                return Ok(frame.return_to);
+            }
+        }
+        Ok(counter + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        eval::Defaults,
+        integer::{Overflow, I32},
+        typecheck::INT_SORT,
+    };
+
+    use super::*;
+
+    // the machine gives the same outputs, or the same error at the same span, as the
+    // evaluator
+    fn agree(source: &str, inputs: Vec<Vec<Value>>) -> Result<(), String> {
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        let bytecode = annotated_program.compile()?;
+        for input_sequence in inputs {
+            assert_eq!(
+                bytecode.run(input_sequence.clone()),
+                annotated_program.eval(input_sequence.clone()),
+                "on {input_sequence:?}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_examples() -> Result<(), String> {
+        let mut inputs = Vec::new();
+        for a in -3..12 {
+            for b in -3..12 {
+                inputs.push(vec![a.into(), b.into(), 0.into(), 0.into()]);
+            }
+        }
+        agree(include_str!("../programs/gcd.fsp"), inputs.clone())?;
+        // div doesn't terminate unless the divisor is positive
+        inputs.retain(|input_sequence| matches!(&input_sequence[1], Value::Numeral(b) if !b.is_negative() && !b.is_zero()));
+        agree(include_str!("../programs/division.fsp"), inputs)?;
+        let inputs = (0..10)
+            .map(|n| vec![n.into(), 0.into(), Value::False])
+            .collect();
+        agree(include_str!("../programs/recursion.fsp"), inputs)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_scopes() -> Result<(), String> {
+        // a procedure sees the globals declared before it, shadowing and nested frames
+        let source = "var g:Int;
+procedure inc(n:Int; ref r:Int) { r := n + g; g := g + 1; }
+var h:Int;
+procedure twice(n:Int; ref r:Int) {
+  var g:Int;
+  g := 10;
+  call inc(n; r);
+  { var x:Int; call inc(r; x); r := x * g + h; }
+}
+program p(a:Int, b:Int, c:Bool) {
+  h := 100;
+  call twice(a; b);
+  { var a:Int; a := 7; b := b + a; }
+  c := a ≠ b ∨ 1/0 = 0;
+  c := c ∧ (b < a ⇒ 1/0 = 0) ⇔ ¬(a % 2 = 0 ∧ a > -1);
+}";
+        let inputs = (-3..4)
+            .map(|a| vec![a.into(), 0.into(), Value::True])
+            .collect();
+        agree(source, inputs)?;
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        let bytecode = annotated_program.compile()?;
+        assert_eq!(
+            bytecode.run(vec![2.into(), 0.into(), Value::True])?,
+            vec![2.into(), 137.into(), Value::False]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() -> Result<(), String> {
+        let source = "program p(a:Int, b:Int) {\n  a := 1 + a / b;\n}";
+        agree(source, vec![vec![1.into(), 0.into()], vec![1.into()]])?;
+        let bytecode = Program::parse(source)?.typecheck()?.compile()?;
+        let error = bytecode.run(vec![1.into(), 0.into()]).unwrap_err();
+        assert_eq!(error.error, RuntimeError::DivisionByZero);
+        assert_eq!(error.span, Some(Span { start: 37, end: 42 }));
+
+        // the same defaults, strictness and overflow
+        let source = "var g:Bool;
+program p(a:Int, b:Bool, c:Bool) {
+  { var y:Int; a := y * 65536 * 65536; }
+  var f:Bool;
+  b := f = false;
+  c := g;
+}";
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        let bytecode = annotated_program.compile()?;
+        let input_sequence = vec![1.into(), Value::False, Value::True];
+        for store in [
+            Store::default(),
+            Store::strict(),
+            Store::init(Defaults::default().with(INT_SORT, 1.into())),
+            Store::init(Defaults::none().with(INT_SORT, 7.into())),
+        ] {
+            for overflow in [Overflow::Checked, Overflow::Wrapping, Overflow::Saturating] {
+                assert_eq!(
+                    bytecode.run_in(input_sequence.clone(), I32(overflow), store.clone()),
+                    annotated_program.eval_in(input_sequence.clone(), I32(overflow), store.clone())
+                );
+            }
+            assert_eq!(
+                bytecode.run_in(input_sequence.clone(), Unbounded, store.clone()),
+                annotated_program.eval_in(input_sequence.clone(), Unbounded, store)
+            );
+        }
+
+        // calls have to have been annotated by the typechecker
+        let source = "procedure f(; ref r:Int) { r := 1; }\nprogram p(a:Int) { call f(; a); }";
+        let error = Program::parse(source)?.compile().unwrap_err();
+        assert_eq!(error.error, RuntimeError::UnannotatedCall("f".into()));
+        assert_eq!(error.span, Some(Span { start: 56, end: 68 }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_long_loop() -> Result<(), String> {
+        let source = "program p(a:Int) {\n  while ¬(a = 0) do a := a-1;\n}";
+        let bytecode = Program::parse(source)?.typecheck()?.compile()?;
+        assert_eq!(bytecode.run(vec![100000.into()])?, vec![0.into()]);
+        // and deep recursion, which only takes memory of the machine
+        let source =
+            "procedure down(n:Int; ref r:Int) { if 0 < n then call down(n-1; r); r := r+1; }
+program p(a:Int) { call down(a; a); }";
+        let bytecode = Program::parse(source)?.typecheck()?.compile()?;
+        assert_eq!(bytecode.run(vec![100000.into()])?, vec![200001.into()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_disassembly() -> Result<(), String> {
+        let source = "procedure f(n:Int; ref r:Int) { r := -n; }
+program p(a:Int, b:Bool) {
+  if b ∧ 0 < a then call f(a; a);
+}";
+        let bytecode = Program::parse(source)?.typecheck()?.compile()?;
+        assert_eq!(
+            bytecode.to_string(),
+            "program:
+   0  load l1 (b)
+   1  jumpf 6
+   2  push 0
+   3  load l0 (a)
+   4  lt
+   5  jump 7
+   6  push false
+   7  jumpf 10
+   8  load l0 (a)
+   9  call #0 top 2 ref [l0]
+  10  ret
+#0 f:
+  11  load l0 (n)
+  12  neg
+  13  store r0
+  14  ret
+"
+        );
+        Ok(())
+    }
+}
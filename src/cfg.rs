@@ -0,0 +1,695 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::{hoare::Substitution, pretty::Style, typecheck::Signature, types::*};
+
+pub type BlockId = usize;
+
+// every graph starts with an empty entry block and ends with an empty exit block
+pub const ENTRY: BlockId = 0;
+pub const EXIT: BlockId = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement<'a> {
+    Assign(Identifier<'a>, Expression<'a>),
+    // a fresh variable, in scope until the end of the command the `var` was declared for
+    Var(Identifier<'a>, Sort<'a>),
+    Call(
+        Identifier<'a>,
+        Vec<Expression<'a>>,
+        Vec<Identifier<'a>>,
+        Option<Signature<'a>>,
+    ),
+}
+
+// statements executed one after the other, a call is a block of its own
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Block<'a> {
+    // with the span of the command each was lowered from
+    pub statements: Vec<(Statement<'a>, Option<Span>)>,
+}
+
+impl<'a> Block<'a> {
+    pub fn is_call(&self) -> bool {
+        matches!(self.statements[..], [(Statement::Call(..), _)])
+    }
+}
+
+// taken if the condition evaluates to the truth value, or always if there is none
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edge<'a> {
+    pub from: BlockId,
+    pub to: BlockId,
+    pub condition: Option<(Expression<'a>, bool)>,
+}
+
+// a place in a graph, before the statement at `index` of the block or at its end
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Point {
+    pub block: BlockId,
+    pub index: usize,
+}
+
+// the control-flow graph of a procedure or program body, a `var` that hides a variable
+// in scope declares one with a fresh name like `x'1` instead, so that every name stands
+// for one variable
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cfg<'a> {
+    pub name: Identifier<'a>,
+    // none for the program
+    pub signature: Option<Signature<'a>>,
+    pub in_params: Vec<Identifier<'a>>,
+    // the parameters of a program are both
+    pub out_params: Vec<Identifier<'a>>,
+    // the global variables declared before the procedure, all of them for the program,
+    // except those a parameter hides
+    pub globals: Vec<Identifier<'a>>,
+    pub blocks: Vec<Block<'a>>,
+    pub edges: Vec<Edge<'a>>,
+    // where control is before each command of the body other than a sequence, in the
+    // order they were written and at the start of the header of a loop and the block of
+    // a call, followed by the exit
+    pub points: Vec<Point>,
+    fresh: usize,
+}
+
+// the names in scope and the fresh ones given to the variables that hide others
+#[derive(Clone, Default)]
+struct Scope<'a> {
+    names: HashSet<Identifier<'a>>,
+    renaming: Substitution<'a>,
+}
+
+impl<'a> Scope<'a> {
+    fn identifier(&self, identifier: Identifier<'a>) -> Identifier<'a> {
+        match self.renaming.get(&identifier) {
+            Some(Expression::Variable(fresh)) => *fresh,
+            _ => identifier,
+        }
+    }
+
+    fn expression(&self, expression: &Expression<'a>) -> Expression<'a> {
+        if self.renaming.is_empty() {
+            expression.clone()
+        } else {
+            expression.substitute(&self.renaming)
+        }
+    }
+}
+
+impl<'a> Program<'a> {
+    // the graphs of the procedures in the order they were declared, then the program's
+    pub fn cfgs(&self) -> Vec<Cfg<'a>> {
+        let Program(declarations, name, parameters, body, _) = self;
+        let mut globals = Vec::new();
+        let mut cfgs = Vec::new();
+        for declaration in declarations.sequence() {
+            match declaration.unspanned() {
+                Declaration::Variable(identifier, _) => globals.push(*identifier),
+                Declaration::Procedure(name, in_params, out_params, body, _) => {
+                    let signature = declaration.signature();
+                    let cfg = Cfg::new(*name, signature, in_params, out_params, &globals);
+                    cfgs.push(cfg.lower(body));
+                }
+                Declaration::Spanned(..) => unreachable!("unspanned"),
+            }
+        }
+        let cfg = Cfg::new(*name, None, parameters, parameters, &globals);
+        cfgs.push(cfg.lower(body));
+        cfgs
+    }
+}
+
+impl<'a> Cfg<'a> {
+    fn new(
+        name: Identifier<'a>,
+        signature: Option<Signature<'a>>,
+        in_params: &Parameters<'a>,
+        out_params: &Parameters<'a>,
+        globals: &[Identifier<'a>],
+    ) -> Cfg<'a> {
+        let (in_params, out_params) = (in_params.identifiers(), out_params.identifiers());
+        let globals = globals
+            .iter()
+            .filter(|global| !in_params.contains(global) && !out_params.contains(global))
+            .copied()
+            .collect();
+        Cfg {
+            name,
+            signature,
+            in_params,
+            out_params,
+            globals,
+            blocks: vec![Block::default(), Block::default()],
+            edges: Vec::new(),
+            points: Vec::new(),
+            fresh: 0,
+        }
+    }
+
+    fn lower(mut self, body: &Command<'a>) -> Cfg<'a> {
+        let variables = [&self.in_params, &self.out_params, &self.globals];
+        let scope = Scope {
+            names: variables.into_iter().flatten().copied().collect(),
+            renaming: Substitution::new(),
+        };
+        let start = self.block();
+        self.edge(ENTRY, start, None);
+        let end = self.command(body, start, None, &scope);
+        self.edge(end, EXIT, None);
+        self.points.push(Point {
+            block: EXIT,
+            index: 0,
+        });
+        self
+    }
+
+    fn block(&mut self) -> BlockId {
+        self.blocks.push(Block::default());
+        self.blocks.len() - 1
+    }
+
+    fn edge(&mut self, from: BlockId, to: BlockId, condition: Option<(Expression<'a>, bool)>) {
+        self.edges.push(Edge {
+            from,
+            to,
+            condition,
+        });
+    }
+
+    fn push(&mut self, block: BlockId, statement: Statement<'a>, span: Option<Span>) {
+        self.blocks[block].statements.push((statement, span));
+    }
+
+    fn point(&mut self, block: BlockId) {
+        let index = self.blocks[block].statements.len();
+        self.points.push(Point { block, index });
+    }
+
+    fn fresh(&mut self, identifier: Identifier<'a>) -> Identifier<'a> {
+        self.fresh += 1;
+        identifier.fresh(self.fresh)
+    }
+
+    // appends `command` to `block`, returns the block control continues in
+    fn command(
+        &mut self,
+        command: &Command<'a>,
+        block: BlockId,
+        span: Option<Span>,
+        scope: &Scope<'a>,
+    ) -> BlockId {
+        if !matches!(command, Command::Sequence(..) | Command::Spanned(..)) {
+            match command {
+                // the header and the call are the next block
+                Command::While(..) | Command::Call(..) => self.points.push(Point {
+                    block: self.blocks.len(),
+                    index: 0,
+                }),
+                _ => self.point(block),
+            }
+        }
+        match command {
+            Command::Assign(identifier, expression) => {
+                let identifier = scope.identifier(*identifier);
+                let statement = Statement::Assign(identifier, scope.expression(expression));
+                self.push(block, statement, span);
+                block
+            }
+            Command::Var(identifier, sort, command) => {
+                let mut scope = scope.clone();
+                let mut variable = *identifier;
+                if !scope.names.insert(variable) {
+                    variable = self.fresh(variable);
+                    scope
+                        .renaming
+                        .insert(*identifier, Expression::Variable(variable));
+                } else {
+                    scope.renaming.remove(identifier);
+                }
+                self.push(block, Statement::Var(variable, *sort), span);
+                self.command(command, block, span, &scope)
+            }
+            Command::Sequence(first, second) => {
+                let block = self.command(first, block, span, scope);
+                self.command(second, block, span, scope)
+            }
+            Command::IfElse(condition, if_branch, else_branch) => {
+                let condition = scope.expression(condition);
+                let (if_block, else_block, join) = (self.block(), self.block(), self.block());
+                self.edge(block, if_block, Some((condition.clone(), true)));
+                self.edge(block, else_block, Some((condition, false)));
+                let if_end = self.command(if_branch, if_block, span, scope);
+                let else_end = self.command(else_branch, else_block, span, scope);
+                self.edge(if_end, join, None);
+                self.edge(else_end, join, None);
+                join
+            }
+            Command::If(condition, if_branch) => {
+                let condition = scope.expression(condition);
+                let (if_block, join) = (self.block(), self.block());
+                self.edge(block, if_block, Some((condition.clone(), true)));
+                self.edge(block, join, Some((condition, false)));
+                let if_end = self.command(if_branch, if_block, span, scope);
+                self.edge(if_end, join, None);
+                join
+            }
+            // the condition is tested in a block of its own that the body returns to
+            Command::While(condition, body, _) => {
+                let condition = scope.expression(condition);
+                let (header, body_block, after) = (self.block(), self.block(), self.block());
+                self.edge(block, header, None);
+                self.edge(header, body_block, Some((condition.clone(), true)));
+                self.edge(header, after, Some((condition, false)));
+                let body_end = self.command(body, body_block, span, scope);
+                self.edge(body_end, header, None);
+                after
+            }
+            Command::Call(identifier, expressions, variables, signature) => {
+                let (call, after) = (self.block(), self.block());
+                let (expressions, variables) = (expressions.sequence(), variables.sequence());
+                let statement = Statement::Call(
+                    *identifier,
+                    expressions
+                        .iter()
+                        .map(|expression| scope.expression(expression))
+                        .collect(),
+                    variables
+                        .into_iter()
+                        .map(|variable| scope.identifier(variable))
+                        .collect(),
+                    signature.clone(),
+                );
+                self.push(call, statement, span);
+                self.edge(block, call, None);
+                self.edge(call, after, None);
+                after
+            }
+            Command::Spanned(span, command) => self.command(command, block, Some(*span), scope),
+        }
+    }
+
+    pub fn successors(&self, block: BlockId) -> impl Iterator<Item = &Edge<'a>> {
+        self.edges.iter().filter(move |edge| edge.from == block)
+    }
+
+    pub fn predecessors(&self, block: BlockId) -> impl Iterator<Item = &Edge<'a>> {
+        self.edges.iter().filter(move |edge| edge.to == block)
+    }
+}
+
+// the graphs as one Graphviz digraph with a cluster per procedure and program, the
+// expressions printed with as few parentheses as possible
+pub fn dot(cfgs: &[Cfg]) -> String {
+    let mut lines = vec![
+        "digraph cfg {".to_string(),
+        "  node [shape=box, fontname=\"monospace\"];".to_string(),
+    ];
+    for (graph, cfg) in cfgs.iter().enumerate() {
+        let kind = match cfg.signature {
+            Some(_) => "procedure",
+            None => "program",
+        };
+        lines.push(format!("  subgraph cluster_{graph} {{"));
+        lines.push(format!("    label=\"{kind} {}\";", cfg.name));
+        for (id, block) in cfg.blocks.iter().enumerate() {
+            let attributes = match id {
+                ENTRY => "label=\"entry\", shape=oval".to_string(),
+                EXIT => "label=\"exit\", shape=oval".to_string(),
+                _ if block.is_call() => {
+                    let label = escape(&label(&block.statements[0].0));
+                    format!("label=\"{label}\", style=rounded")
+                }
+                _ => {
+                    let statements = block.statements.iter();
+                    let label: String = statements
+                        .map(|(statement, _)| format!("{}\\l", escape(&label(statement))))
+                        .collect();
+                    format!("label=\"{label}\"")
+                }
+            };
+            lines.push(format!("    n{graph}_{id} [{attributes}];"));
+        }
+        for Edge {
+            from,
+            to,
+            condition,
+        } in &cfg.edges
+        {
+            let label = match condition {
+                None => String::new(),
+                Some((condition, true)) => format!(" [label=\"{}\"]", escape(&pretty(condition))),
+                Some((condition, false)) => {
+                    let negation = match condition.unspanned() {
+                        Expression::Not(operand) => pretty(operand),
+                        _ => pretty(&Expression::Not(condition.clone().into())),
+                    };
+                    format!(" [label=\"{}\"]", escape(&negation))
+                }
+            };
+            lines.push(format!("    n{graph}_{from} -> n{graph}_{to}{label};"));
+        }
+        lines.push("  }".to_string());
+    }
+    lines.push("}".to_string());
+    lines.join("\n") + "\n"
+}
+
+// on a single line
+pub(crate) fn pretty(expression: &Expression) -> String {
+    let style = Style {
+        width: usize::MAX,
+        ..Style::default()
+    };
+    expression.pretty(&style)
+}
+
+fn label(statement: &Statement) -> String {
+    match statement {
+        Statement::Assign(name, expression) => format!("{name} := {}", pretty(expression)),
+        Statement::Var(name, sort) => format!("var {name}:{sort}"),
+        Statement::Call(name, expressions, variables, _) => {
+            let expressions: Vec<_> = expressions.iter().map(pretty).collect();
+            let variables: Vec<_> = variables.iter().map(ToString::to_string).collect();
+            format!(
+                "call {name}({}; {})",
+                expressions.join(", "),
+                variables.join(", ")
+            )
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Forward,
+    Backward,
+}
+
+// a dataflow problem over a lattice of facts, `join` has to be monotone and the lattice
+// of finite height for `solve` to terminate
+pub trait Dataflow<'a> {
+    type Fact: Clone + PartialEq;
+
+    fn direction(&self) -> Direction;
+    // what holds at the entry of a forward problem or the exit of a backward one
+    fn boundary(&self, cfg: &Cfg<'a>) -> Self::Fact;
+    // what the other blocks start from, the neutral element of `join`
+    fn initial(&self, cfg: &Cfg<'a>) -> Self::Fact;
+    fn join(&self, left: &Self::Fact, right: &Self::Fact) -> Self::Fact;
+    // the fact after the block from the one before it, the other way around for a
+    // backward problem
+    fn transfer(&self, id: BlockId, block: &Block<'a>, fact: &Self::Fact) -> Self::Fact;
+    // what taking the edge tells, e.g. that its condition holds
+    fn edge(&self, _edge: &Edge<'a>, fact: &Self::Fact) -> Self::Fact {
+        fact.clone()
+    }
+}
+
+// the facts at the start and the end of every block
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution<F> {
+    pub before: Vec<F>,
+    pub after: Vec<F>,
+}
+
+// the least fixpoint of the dataflow equations, found by revisiting the blocks whose
+// inputs changed until none do
+pub fn solve<'a, D: Dataflow<'a>>(cfg: &Cfg<'a>, dataflow: &D) -> Solution<D::Fact> {
+    let n = cfg.blocks.len();
+    let forward = dataflow.direction() == Direction::Forward;
+    let (boundary, initial) = (dataflow.boundary(cfg), dataflow.initial(cfg));
+    // the facts flowing into a block and out of it, in the direction of the problem
+    let mut inputs = vec![initial.clone(); n];
+    let mut outputs = vec![initial.clone(); n];
+    let mut worklist: VecDeque<BlockId> = (0..n).collect();
+    if !forward {
+        worklist = worklist.into_iter().rev().collect();
+    }
+    let mut queued = vec![true; n];
+    while let Some(block) = worklist.pop_front() {
+        queued[block] = false;
+        let incoming: Vec<_> = if forward {
+            cfg.predecessors(block)
+                .map(|edge| dataflow.edge(edge, &outputs[edge.from]))
+                .collect()
+        } else {
+            cfg.successors(block)
+                .map(|edge| dataflow.edge(edge, &outputs[edge.to]))
+                .collect()
+        };
+        let start = if block == if forward { ENTRY } else { EXIT } {
+            boundary.clone()
+        } else {
+            initial.clone()
+        };
+        inputs[block] = incoming
+            .iter()
+            .fold(start, |fact, other| dataflow.join(&fact, other));
+        let output = dataflow.transfer(block, &cfg.blocks[block], &inputs[block]);
+        if output == outputs[block] {
+            continue;
+        }
+        outputs[block] = output;
+        let dependents: Vec<_> = if forward {
+            cfg.successors(block).map(|edge| edge.to).collect()
+        } else {
+            cfg.predecessors(block).map(|edge| edge.from).collect()
+        };
+        for dependent in dependents {
+            if !queued[dependent] {
+                queued[dependent] = true;
+                worklist.push_back(dependent);
+            }
+        }
+    }
+    if forward {
+        Solution {
+            before: inputs,
+            after: outputs,
+        }
+    } else {
+        Solution {
+            before: outputs,
+            after: inputs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    static SOURCE: &str = "var g:Int;
+procedure f(n:Int; ref r:Int) {
+  r := 0;
+  while 0 < n do { r := r + n; n := n - 1; }
+  if r = 0 then g := 1;
+}
+program p(a:Int) { var x:Int; x := a; call f(x; a); }";
+
+    fn edges(cfg: &Cfg) -> Vec<(BlockId, BlockId, Option<bool>)> {
+        let edges = cfg.edges.iter();
+        edges
+            .map(|edge| (edge.from, edge.to, edge.condition.as_ref().map(|c| c.1)))
+            .collect()
+    }
+
+    #[test]
+    fn test_lowering() -> Result<(), String> {
+        let cfgs = Program::parse(SOURCE)?.typecheck()?.cfgs();
+        let [f, p] = &cfgs[..] else {
+            panic!("{} graphs", cfgs.len());
+        };
+        assert_eq!(
+            (f.name, f.signature.is_some(), p.signature.is_some()),
+            ("f".into(), true, false)
+        );
+        assert_eq!(
+            (&f.in_params[..], &f.out_params[..]),
+            (&["n".into()][..], &["r".into()][..])
+        );
+        assert_eq!(
+            (&f.globals[..], &p.out_params[..]),
+            (&["g".into()][..], &["a".into()][..])
+        );
+        let statements = |block: &Block| -> Vec<String> {
+            let statements = block.statements.iter();
+            statements
+                .map(|(statement, _)| statement.to_string())
+                .collect()
+        };
+        assert_eq!(statements(&f.blocks[2]), vec!["r:=0"]);
+        assert_eq!(statements(&f.blocks[3]), Vec::<String>::new());
+        assert_eq!(statements(&f.blocks[4]), vec!["r:=(r+n)", "n:=(n-1)"]);
+        assert_eq!(statements(&f.blocks[6]), vec!["g:=1"]);
+        assert_eq!(
+            edges(f),
+            vec![
+                (ENTRY, 2, None),
+                (2, 3, None),
+                (3, 4, Some(true)),
+                (3, 5, Some(false)),
+                (4, 3, None),
+                (5, 6, Some(true)),
+                (5, 7, Some(false)),
+                (6, 7, None),
+                (7, EXIT, None),
+            ]
+        );
+        let points = f.points.iter().map(|point| (point.block, point.index));
+        assert_eq!(
+            points.collect::<Vec<_>>(),
+            vec![(2, 0), (3, 0), (4, 0), (4, 1), (5, 0), (6, 0), (EXIT, 0)]
+        );
+        assert_eq!(statements(&p.blocks[2]), vec!["var x:Int", "x:=a"]);
+        assert!(p.blocks[3].is_call() && !p.blocks[2].is_call());
+        assert_eq!(statements(&p.blocks[3]), vec!["call f(x;a)"]);
+        assert_eq!(
+            edges(p),
+            vec![
+                (ENTRY, 2, None),
+                (2, 3, None),
+                (3, 4, None),
+                (4, EXIT, None)
+            ]
+        );
+        // the statements keep the span of their command
+        let (_, span) = p.blocks[2].statements[1];
+        assert_eq!(
+            span.map(|span| &SOURCE[span.start..span.end]),
+            Some("x := a;")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_renaming() -> Result<(), String> {
+        let source = "var x:Int; var y:Int;
+procedure f(x:Int; ref r:Int) { r := x + y; }
+program p(a:Int) {
+  var a:Bool; a := true;
+  if a then { var a:Int; a := x; call f(a; a); }
+  x := 0;
+}";
+        let cfgs = Program::parse(source)?.typecheck()?.cfgs();
+        // the parameter hides the global
+        assert_eq!(cfgs[0].globals, vec!["y".into()]);
+        // in the order of the blocks, the call's comes after the join's
+        let statements = cfgs[1].blocks.iter().flat_map(|block| &block.statements);
+        let statements: Vec<_> = statements.map(|(s, _)| s.to_string()).collect();
+        assert_eq!(
+            statements,
+            vec![
+                "var a'1:Bool",
+                "a'1:=true",
+                "var a'2:Int",
+                "a'2:=x",
+                "x:=0",
+                "call f(a'2;a'2)"
+            ]
+        );
+        let condition = cfgs[1].edges.iter().find_map(|edge| edge.condition.clone());
+        assert_eq!(condition.map(|(c, _)| c.to_string()), Some("a'1".into()));
+        Ok(())
+    }
+
+    // the variables assigned on every path, the parameters are on entry
+    struct Assigned;
+
+    impl<'a> Dataflow<'a> for Assigned {
+        type Fact = HashSet<Identifier<'a>>;
+
+        fn direction(&self) -> Direction {
+            Direction::Forward
+        }
+        fn boundary(&self, cfg: &Cfg<'a>) -> Self::Fact {
+            cfg.in_params.iter().copied().collect()
+        }
+        fn initial(&self, cfg: &Cfg<'a>) -> Self::Fact {
+            let variables = [&cfg.in_params, &cfg.out_params, &cfg.globals];
+            variables.into_iter().flatten().copied().collect()
+        }
+        fn join(&self, left: &Self::Fact, right: &Self::Fact) -> Self::Fact {
+            left.intersection(right).copied().collect()
+        }
+        fn transfer(&self, _: BlockId, block: &Block<'a>, fact: &Self::Fact) -> Self::Fact {
+            let mut fact = fact.clone();
+            for (statement, _) in &block.statements {
+                match statement {
+                    Statement::Assign(identifier, _) => fact.insert(*identifier),
+                    Statement::Var(..) => false,
+                    Statement::Call(_, _, variables, _) => {
+                        fact.extend(variables);
+                        false
+                    }
+                };
+            }
+            fact
+        }
+    }
+
+    // whether a call may follow
+    struct Calls;
+
+    impl<'a> Dataflow<'a> for Calls {
+        type Fact = bool;
+
+        fn direction(&self) -> Direction {
+            Direction::Backward
+        }
+        fn boundary(&self, _: &Cfg<'a>) -> bool {
+            false
+        }
+        fn initial(&self, _: &Cfg<'a>) -> bool {
+            false
+        }
+        fn join(&self, left: &bool, right: &bool) -> bool {
+            *left || *right
+        }
+        fn transfer(&self, _: BlockId, block: &Block<'a>, fact: &bool) -> bool {
+            *fact || block.is_call()
+        }
+    }
+
+    #[test]
+    fn test_solve() -> Result<(), String> {
+        let cfgs = Program::parse(SOURCE)?.typecheck()?.cfgs();
+        let solution = solve(&cfgs[0], &Assigned);
+        let assigned = |fact: &HashSet<Identifier>| {
+            let mut names: Vec<_> = fact
+                .iter()
+                .map(|identifier| identifier.to_string())
+                .collect();
+            names.sort();
+            names
+        };
+        assert_eq!(assigned(&solution.before[2]), vec!["n"]);
+        // around the loop and after the if without else
+        assert_eq!(assigned(&solution.before[3]), vec!["n", "r"]);
+        assert_eq!(assigned(&solution.after[6]), vec!["g", "n", "r"]);
+        assert_eq!(assigned(&solution.before[EXIT]), vec!["n", "r"]);
+
+        let solution = solve(&cfgs[1], &Calls);
+        assert_eq!(solution.before, vec![true, false, true, true, false]);
+        assert_eq!(solution.after, vec![true, false, true, false, false]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dot() -> Result<(), String> {
+        let dot = dot(&Program::parse(SOURCE)?.cfgs());
+        assert!(dot.starts_with("digraph cfg {\n"));
+        assert!(dot.contains("  subgraph cluster_0 {\n    label=\"procedure f\";\n"));
+        assert!(dot.contains("    n0_4 [label=\"r := r + n\\ln := n - 1\\l\"];\n"));
+        assert!(dot.contains("    n0_3 -> n0_5 [label=\"¬(0 < n)\"];\n"));
+        assert!(dot.contains("    n1_3 [label=\"call f(x; a)\", style=rounded];\n"));
+        assert!(dot.ends_with("  }\n}\n"));
+        Ok(())
+    }
+}
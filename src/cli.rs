@@ -0,0 +1,846 @@
+use std::{fs, io, ops::RangeInclusive, process::ExitCode};
+
+use crate::{
+    absint::{Domain, Interval, Sign},
+    bounded::{Bounds, Verdict},
+    cfg,
+    dataflow::{self, Annotated, AvailableExpressions, Liveness, ReachingDefinitions},
+    eval::{Defaults, Limits, Store},
+    hoare::VerificationCondition,
+    integer::{Overflow, Unbounded, I32},
+    optimize::Passes,
+    pretty::Style,
+    repl, smt,
+    typecheck::{Level, Lints},
+    types::{Expression, Program, Value},
+};
+
+static USAGE: &str = "usage:
+  check FILE [--allow|--note|--warn|--deny CODE]..
+                             typecheck FILE and warn about reads of unassigned variables
+                             and about calls passing a variable as a ref argument twice
+                             (duplicate-output) or also reading it in an in argument
+                             (output-in-input), at the level given for CODE
+  run FILE [--args V1,V2,..] [--i32 checked|wrapping|saturating] [--strict] [--vm]
+                             typecheck and run FILE on the given input values, with
+                             unbounded integers or 32 bit ones that fail, wrap around or
+                             saturate on overflow, unassigned variables read as 0 or
+                             fail with --strict, compiled to bytecode with --vm
+  trace FILE [--args V1,..] [--i32 MODE] [--strict]
+                             like run, printing every command and call executed first
+  compile FILE               print the bytecode FILE compiles to
+  cfg FILE                   print the control-flow graphs of FILE in Graphviz's DOT format
+  dataflow FILE live|reaching|available
+                             print FILE with the live variables, reaching definitions or
+                             available expressions before each command as comments
+  fmt FILE [--width N] [--indent N] [--ascii]
+                             print FILE in canonical form, with lines of at most N
+                             columns (80), N spaces per level (2) and ASCII operators
+  optimize FILE [--no-fold] [--no-propagate] [--no-branches] [--no-dead-code]
+                             print FILE with constants folded and propagated, ifs with a
+                             constant condition replaced by their branch and dead
+                             assignments and unused vars removed, except for the passes
+                             turned off
+  vc FILE                    print the verification conditions of FILE
+  smt FILE                   print the verification conditions of FILE as an SMT-LIB 2 script
+  analyze FILE [--signs] [--i32 MODE] [--strict]
+                             print the intervals, or signs, the variables of FILE may have
+                             before each command, with integers and unassigned variables
+                             as run represents them, and warn about divisions by 0,
+                             checked overflows and constant conditions
+  verify FILE --range LO..HI [--ensures EXPR]..
+                             run FILE on every input in the range and check that it
+                             doesn't fail and ends in a state where each EXPR holds,
+                             `old_x` is the input value of parameter x
+  repl                       start an interactive session
+
+exit codes: 1 usage or I/O error, 2 syntax error, 3 type error, 4 runtime error,
+  5 counterexample";
+
+// each run of `verify` is cut off after this many steps or this deep in recursion
+static LIMITS: Limits = Limits {
+    steps: 10_000,
+    depth: 100,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum Failure {
+    Usage(String),
+    Syntax(String),
+    Type(String),
+    Runtime(String),
+    Counterexample(String),
+}
+
+impl Failure {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Failure::Usage(_) => 1,
+            Failure::Syntax(_) => 2,
+            Failure::Type(_) => 3,
+            Failure::Runtime(_) => 4,
+            Failure::Counterexample(_) => 5,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Failure::Usage(message)
+            | Failure::Syntax(message)
+            | Failure::Type(message)
+            | Failure::Runtime(message)
+            | Failure::Counterexample(message) => message,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Subcommand<'a> {
+    Check(&'a str, Lints),
+    Run(&'a str, RunOptions),
+    Trace(&'a str, RunOptions),
+    Compile(&'a str),
+    Cfg(&'a str),
+    Dataflow(&'a str, Problem),
+    Fmt(&'a str, Style),
+    Optimize(&'a str, Passes),
+    Vc(&'a str),
+    Smt(&'a str),
+    Analyze(&'a str, AnalyzeOptions),
+    Verify(&'a str, RangeInclusive<i32>, Vec<&'a str>),
+    Repl,
+}
+
+// the analysis `dataflow` annotates a program with
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Problem {
+    Live,
+    Reaching,
+    Available,
+}
+
+// how `analyze` abstracts the program
+#[derive(Debug, PartialEq, Default)]
+pub struct AnalyzeOptions {
+    // signs instead of intervals
+    pub signs: bool,
+    // 32 bit integers instead of unbounded ones
+    pub overflow: Option<Overflow>,
+    // variables are unassigned until written instead of 0 or false
+    pub strict: bool,
+}
+
+// how `run` and `trace` evaluate the program
+#[derive(Debug, PartialEq, Default)]
+pub struct RunOptions {
+    pub values: Vec<Value>,
+    // 32 bit integers instead of unbounded ones
+    pub overflow: Option<Overflow>,
+    // reads of unassigned variables fail instead of giving 0
+    pub strict: bool,
+    // on the bytecode machine instead of the evaluator, only for `run`
+    pub vm: bool,
+}
+
+pub fn main(args: &[String]) -> ExitCode {
+    let result = Subcommand::parse(args).and_then(|subcommand| {
+        if subcommand == Subcommand::Repl {
+            return repl::run(io::stdin().lock(), io::stdout())
+                .map(|()| String::new())
+                .map_err(|error| Failure::Usage(error.to_string()));
+        }
+        let path = subcommand.path();
+        let source = fs::read_to_string(path)
+            .map_err(|error| Failure::Usage(format!("can't read {path}: {error}")))?;
+        subcommand.execute(&source)
+    });
+    match result {
+        Ok(output) => {
+            print!("{output}");
+            ExitCode::SUCCESS
+        }
+        Err(failure) => {
+            eprintln!("{}", failure.message());
+            ExitCode::from(failure.exit_code())
+        }
+    }
+}
+
+impl<'a> Subcommand<'a> {
+    pub fn parse(args: &'a [String]) -> Result<Subcommand<'a>, Failure> {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        match args[..] {
+            ["check", path, ref options @ ..] => Ok(Subcommand::Check(path, lints(options)?)),
+            ["run", path, ref options @ ..] => {
+                Ok(Subcommand::Run(path, RunOptions::parse(options)?))
+            }
+            ["trace", path, ref options @ ..] => match RunOptions::parse(options)? {
+                RunOptions { vm: true, .. } => Err(Failure::Usage(USAGE.into())),
+                options => Ok(Subcommand::Trace(path, options)),
+            },
+            ["compile", path] => Ok(Subcommand::Compile(path)),
+            ["cfg", path] => Ok(Subcommand::Cfg(path)),
+            ["dataflow", path, "live"] => Ok(Subcommand::Dataflow(path, Problem::Live)),
+            ["dataflow", path, "reaching"] => Ok(Subcommand::Dataflow(path, Problem::Reaching)),
+            ["dataflow", path, "available"] => Ok(Subcommand::Dataflow(path, Problem::Available)),
+            ["fmt", path, ref options @ ..] => Ok(Subcommand::Fmt(path, style(options)?)),
+            ["optimize", path, ref options @ ..] => {
+                Ok(Subcommand::Optimize(path, passes(options)?))
+            }
+            ["vc", path] => Ok(Subcommand::Vc(path)),
+            ["smt", path] => Ok(Subcommand::Smt(path)),
+            ["analyze", path, ref options @ ..] => {
+                Ok(Subcommand::Analyze(path, AnalyzeOptions::parse(options)?))
+            }
+            ["verify", path, "--range", range, ref ensures @ ..] => {
+                let invalid = || Failure::Usage(format!("invalid range {range:?}"));
+                let (low, high) = range.split_once("..").ok_or_else(invalid)?;
+                let low = low.trim().parse().map_err(|_| invalid())?;
+                let high = high.trim().parse().map_err(|_| invalid())?;
+                let postconditions = ensures
+                    .chunks(2)
+                    .map(|option| match option {
+                        ["--ensures", postcondition] => Ok(*postcondition),
+                        _ => Err(Failure::Usage(USAGE.into())),
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(Subcommand::Verify(path, low..=high, postconditions))
+            }
+            ["repl"] => Ok(Subcommand::Repl),
+            _ => Err(Failure::Usage(USAGE.into())),
+        }
+    }
+
+    fn path(&self) -> &'a str {
+        match self {
+            Subcommand::Check(path, _)
+            | Subcommand::Run(path, ..)
+            | Subcommand::Trace(path, ..)
+            | Subcommand::Compile(path)
+            | Subcommand::Cfg(path)
+            | Subcommand::Dataflow(path, _)
+            | Subcommand::Fmt(path, _)
+            | Subcommand::Optimize(path, _)
+            | Subcommand::Vc(path)
+            | Subcommand::Smt(path)
+            | Subcommand::Analyze(path, _)
+            | Subcommand::Verify(path, ..) => path,
+            Subcommand::Repl => "",
+        }
+    }
+
+    // the text printed on success
+    pub fn execute(&self, source: &str) -> Result<String, Failure> {
+        let program =
+            Program::parse(source).map_err(|error| Failure::Syntax(error.render(source)))?;
+        match self {
+            // only findings at level error make the command fail
+            Subcommand::Check(_, lints) => {
+                let (annotated_program, findings) = program
+                    .typecheck_with(lints)
+                    .map_err(|diagnostics| Failure::Type(diagnostics.render(source)))?;
+                let warnings = annotated_program.unassigned_reads();
+                let mut output: Vec<_> = findings
+                    .iter()
+                    .map(|(level, finding)| finding.render_as(&level.to_string(), source))
+                    .collect();
+                if !warnings.is_empty() {
+                    output.push(warnings.render_as("warning", source));
+                }
+                if output.is_empty() {
+                    Ok(String::new())
+                } else {
+                    Ok(format!("{}\n", output.join("\n\n")))
+                }
+            }
+            Subcommand::Run(_, options) => {
+                let annotated_program = typecheck(&program, source)?;
+                let (values, store) = (options.values.clone(), options.store());
+                let result = if options.vm {
+                    annotated_program
+                        .compile()
+                        .and_then(|bytecode| match options.overflow {
+                            None => bytecode.run_in(values, Unbounded, store),
+                            Some(overflow) => bytecode.run_in(values, I32(overflow), store),
+                        })
+                } else {
+                    match options.overflow {
+                        None => annotated_program.eval_in(values, Unbounded, store),
+                        Some(overflow) => annotated_program.eval_in(values, I32(overflow), store),
+                    }
+                };
+                let result = result.map_err(|error| Failure::Runtime(error.render(source)))?;
+                let result: Vec<_> = result.iter().map(Value::to_string).collect();
+                Ok(format!("{}\n", result.join(",")))
+            }
+            Subcommand::Trace(_, options) => {
+                let annotated_program = typecheck(&program, source)?;
+                let (values, store) = (options.values.clone(), options.store());
+                let (result, trace) = match options.overflow {
+                    None => annotated_program.eval_traced_in(values, Unbounded, store),
+                    Some(overflow) => {
+                        annotated_program.eval_traced_in(values, I32(overflow), store)
+                    }
+                };
+                let result = result.map_err(|error| {
+                    Failure::Runtime(format!("{trace}{}", error.render(source)))
+                })?;
+                let result: Vec<_> = result.iter().map(Value::to_string).collect();
+                Ok(format!("{trace}{}\n", result.join(",")))
+            }
+            Subcommand::Compile(_) => {
+                let annotated_program = typecheck(&program, source)?;
+                let bytecode = annotated_program
+                    .compile()
+                    .map_err(|error| Failure::Runtime(error.render(source)))?;
+                Ok(bytecode.to_string())
+            }
+            Subcommand::Cfg(_) => Ok(cfg::dot(&program.cfgs())),
+            // calls have to be resolved to know which globals they read and write
+            Subcommand::Dataflow(_, problem) => {
+                let annotated_program = typecheck(&program, source)?;
+                let cfgs = annotated_program.cfgs();
+                let summaries = dataflow::summaries(&cfgs);
+                let notes = match problem {
+                    Problem::Live => {
+                        dataflow::annotate(&cfgs, |cfg| Liveness::new(cfg, &summaries))
+                    }
+                    Problem::Reaching => {
+                        dataflow::annotate(&cfgs, |cfg| ReachingDefinitions::new(cfg, &summaries))
+                    }
+                    Problem::Available => {
+                        dataflow::annotate(&cfgs, |cfg| AvailableExpressions::new(cfg, &summaries))
+                    }
+                };
+                let annotated = Annotated {
+                    program: &annotated_program,
+                    notes,
+                };
+                Ok(format!("{annotated}\n"))
+            }
+            Subcommand::Fmt(_, style) => Ok(format!("{}\n", program.pretty(style))),
+            Subcommand::Optimize(_, passes) => {
+                let annotated_program = typecheck(&program, source)?;
+                let optimized = annotated_program.optimize(passes);
+                Ok(format!("{}\n", optimized.pretty(&Style::default())))
+            }
+            Subcommand::Vc(_) => {
+                let conditions = verification_conditions(&program, source)?;
+                Ok(conditions
+                    .iter()
+                    .map(|condition| format!("{condition}\n"))
+                    .collect())
+            }
+            Subcommand::Smt(_) => Ok(smt::script(&verification_conditions(&program, source)?)),
+            Subcommand::Analyze(_, options) if options.signs => {
+                analyze::<Sign>(&program, source, options)
+            }
+            Subcommand::Analyze(_, options) => analyze::<Interval>(&program, source, options),
+            Subcommand::Verify(_, range, postconditions) => {
+                let annotated_program = typecheck(&program, source)?;
+                let postconditions = postconditions
+                    .iter()
+                    .map(|postcondition| {
+                        Expression::parse(postcondition)
+                            .map_err(|error| Failure::Syntax(error.render(postcondition)))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let bounds = Bounds {
+                    range: range.clone(),
+                    limits: LIMITS,
+                };
+                match annotated_program.check_bounded(&postconditions, &bounds) {
+                    Ok(Verdict::Counterexample(counterexample)) => {
+                        Err(Failure::Counterexample(counterexample.to_string()))
+                    }
+                    Ok(verdict) => Ok(verdict.to_string()),
+                    // the positions are those in the postconditions, not in the source
+                    Err(diagnostics) => Err(Failure::Type(diagnostics.to_string())),
+                }
+            }
+            Subcommand::Repl => Ok(String::new()),
+        }
+    }
+}
+
+fn typecheck<'a>(program: &Program<'a>, source: &str) -> Result<Program<'a>, Failure> {
+    program
+        .typecheck()
+        .map_err(|diagnostics| Failure::Type(diagnostics.render(source)))
+}
+
+// warnings follow the states, they don't make the command fail
+fn analyze<D: Domain>(
+    program: &Program,
+    source: &str,
+    options: &AnalyzeOptions,
+) -> Result<String, Failure> {
+    let annotated_program = typecheck(program, source)?;
+    let defaults = Defaults::default();
+    let defaults = (!options.strict).then_some(&defaults);
+    let analysis = match options.overflow {
+        Some(overflow) => annotated_program.analyze_in::<D, _>(I32(overflow), defaults),
+        None => annotated_program.analyze_in::<D, _>(Unbounded, defaults),
+    };
+    let mut output = analysis.to_string();
+    if !analysis.warnings.is_empty() {
+        output.push_str(&format!(
+            "\n{}\n",
+            analysis.warnings.render_as("warning", source)
+        ));
+    }
+    Ok(output)
+}
+
+fn verification_conditions<'a>(
+    program: &Program<'a>,
+    source: &str,
+) -> Result<Vec<VerificationCondition<'a>>, Failure> {
+    program
+        .verification_conditions()
+        .map_err(|diagnostics| Failure::Type(diagnostics.render(source)))
+}
+
+// the passes that `--no-fold`, `--no-propagate`, `--no-branches` and `--no-dead-code`
+// don't turn off, each flag at most once
+fn passes(options: &[&str]) -> Result<Passes, Failure> {
+    let mut passes = Passes::default();
+    for option in options {
+        let pass = match *option {
+            "--no-fold" => &mut passes.fold,
+            "--no-propagate" => &mut passes.propagate,
+            "--no-branches" => &mut passes.branches,
+            "--no-dead-code" => &mut passes.dead_code,
+            _ => return Err(Failure::Usage(USAGE.into())),
+        };
+        if !*pass {
+            return Err(Failure::Usage(USAGE.into()));
+        }
+        *pass = false;
+    }
+    Ok(passes)
+}
+
+// `--width`, `--indent` and `--ascii` in any order, each at most once
+fn style(mut options: &[&str]) -> Result<Style, Failure> {
+    let (mut width, mut indent, mut ascii) = (None, None, false);
+    let number = |option: &str, value: &str| {
+        value
+            .parse()
+            .map_err(|_| Failure::Usage(format!("invalid {option} {value:?}")))
+    };
+    loop {
+        options = match options {
+            [] => break,
+            ["--width", value, rest @ ..] if width.is_none() => {
+                width = Some(number("width", value)?);
+                rest
+            }
+            ["--indent", value, rest @ ..] if indent.is_none() => {
+                indent = Some(number("indent", value)?);
+                rest
+            }
+            ["--ascii", rest @ ..] if !ascii => {
+                ascii = true;
+                rest
+            }
+            _ => return Err(Failure::Usage(USAGE.into())),
+        }
+    }
+    let default = Style::default();
+    Ok(Style {
+        width: width.unwrap_or(default.width),
+        indent: indent.unwrap_or(default.indent),
+        ascii,
+    })
+}
+
+// the level of each aliasing check, later options override earlier ones
+fn lints(mut options: &[&str]) -> Result<Lints, Failure> {
+    let mut lints = Lints::default();
+    while let [option, code, rest @ ..] = options {
+        let level = match *option {
+            "--allow" => Level::Allow,
+            "--note" => Level::Note,
+            "--warn" => Level::Warning,
+            "--deny" => Level::Error,
+            _ => return Err(Failure::Usage(USAGE.into())),
+        };
+        if !lints.set(code, level) {
+            return Err(Failure::Usage(format!("unknown check {code:?}")));
+        }
+        options = rest;
+    }
+    match options {
+        [] => Ok(lints),
+        _ => Err(Failure::Usage(USAGE.into())),
+    }
+}
+
+impl RunOptions {
+    // `--args`, `--i32`, `--strict` and `--vm` in any order, each at most once
+    fn parse(mut options: &[&str]) -> Result<RunOptions, Failure> {
+        let mut values = None;
+        let mut run_options = RunOptions::default();
+        loop {
+            options = match options {
+                [] => break,
+                ["--args", arguments, rest @ ..] if values.is_none() => {
+                    values = Some(parse_values(arguments)?);
+                    rest
+                }
+                ["--i32", mode, rest @ ..] if run_options.overflow.is_none() => {
+                    run_options.overflow = Some(overflow(mode)?);
+                    rest
+                }
+                ["--strict", rest @ ..] if !run_options.strict => {
+                    run_options.strict = true;
+                    rest
+                }
+                ["--vm", rest @ ..] if !run_options.vm => {
+                    run_options.vm = true;
+                    rest
+                }
+                _ => return Err(Failure::Usage(USAGE.into())),
+            }
+        }
+        run_options.values = values.unwrap_or_default();
+        Ok(run_options)
+    }
+
+    fn store(&self) -> Store {
+        if self.strict {
+            Store::strict()
+        } else {
+            Store::default()
+        }
+    }
+}
+
+impl AnalyzeOptions {
+    // `--signs`, `--i32` and `--strict` in any order, each at most once
+    fn parse(mut options: &[&str]) -> Result<AnalyzeOptions, Failure> {
+        let mut analyze_options = AnalyzeOptions::default();
+        loop {
+            options = match options {
+                [] => break,
+                ["--signs", rest @ ..] if !analyze_options.signs => {
+                    analyze_options.signs = true;
+                    rest
+                }
+                ["--i32", mode, rest @ ..] if analyze_options.overflow.is_none() => {
+                    analyze_options.overflow = Some(overflow(mode)?);
+                    rest
+                }
+                ["--strict", rest @ ..] if !analyze_options.strict => {
+                    analyze_options.strict = true;
+                    rest
+                }
+                _ => return Err(Failure::Usage(USAGE.into())),
+            }
+        }
+        Ok(analyze_options)
+    }
+}
+
+fn overflow(mode: &str) -> Result<Overflow, Failure> {
+    match mode {
+        "checked" => Ok(Overflow::Checked),
+        "wrapping" => Ok(Overflow::Wrapping),
+        "saturating" => Ok(Overflow::Saturating),
+        mode => Err(Failure::Usage(format!("invalid overflow mode {mode:?}"))),
+    }
+}
+
+// comma separated numerals and truth values, e.g. `60,24,0,0`
+fn parse_values(values: &str) -> Result<Vec<Value>, Failure> {
+    if values.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    values
+        .split(',')
+        .map(|value| match value.trim() {
+            "true" => Ok(Value::True),
+            "false" => Ok(Value::False),
+            numeral => numeral
+                .parse()
+                .map(Value::Numeral)
+                .map_err(|_| Failure::Usage(format!("invalid input value {numeral:?}"))),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static GCD: &str = include_str!("../programs/gcd.fsp");
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn inputs(values: Vec<Value>) -> RunOptions {
+        RunOptions {
+            values,
+            ..RunOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_subcommand() {
+        let run = args(&["run", "gcd.fsp", "--args", "60, 24,true,-1"]);
+        assert_eq!(
+            Subcommand::parse(&run),
+            Ok(Subcommand::Run(
+                "gcd.fsp",
+                inputs(vec![60.into(), 24.into(), Value::True, (-1).into()])
+            ))
+        );
+        let check = args(&["check", "gcd.fsp"]);
+        assert_eq!(
+            Subcommand::parse(&check),
+            Ok(Subcommand::Check("gcd.fsp", Lints::default()))
+        );
+        let check = args(&["check", "gcd.fsp", "--deny", "duplicate-output"]);
+        let lints = Lints {
+            duplicate_output: Level::Error,
+            ..Lints::default()
+        };
+        assert_eq!(
+            Subcommand::parse(&check),
+            Ok(Subcommand::Check("gcd.fsp", lints))
+        );
+        let check = args(&["check", "gcd.fsp", "--deny", "aliasing"]);
+        assert_eq!(Subcommand::parse(&check).unwrap_err().exit_code(), 1);
+        assert_eq!(Subcommand::parse(&args(&["repl"])), Ok(Subcommand::Repl));
+        let run = args(&["run", "gcd.fsp", "--args", "1,x"]);
+        assert_eq!(Subcommand::parse(&run).unwrap_err().exit_code(), 1);
+        let run = args(&[
+            "run", "gcd.fsp", "--i32", "wrapping", "--strict", "--args", "1",
+        ]);
+        assert_eq!(
+            Subcommand::parse(&run),
+            Ok(Subcommand::Run(
+                "gcd.fsp",
+                RunOptions {
+                    values: vec![1.into()],
+                    overflow: Some(Overflow::Wrapping),
+                    strict: true,
+                    vm: false,
+                }
+            ))
+        );
+        let run = args(&["run", "gcd.fsp", "--i32", "modular"]);
+        assert_eq!(Subcommand::parse(&run).unwrap_err().exit_code(), 1);
+        let trace = args(&["trace", "gcd.fsp", "--vm"]);
+        assert_eq!(Subcommand::parse(&trace).unwrap_err().exit_code(), 1);
+        let run = args(&["run", "gcd.fsp", "--strict", "--strict"]);
+        assert_eq!(Subcommand::parse(&run).unwrap_err().exit_code(), 1);
+        assert_eq!(
+            Subcommand::parse(&args(&["lint"])).unwrap_err().exit_code(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_run_gcd() {
+        let run = Subcommand::Run(
+            "gcd.fsp",
+            inputs(vec![60.into(), 24.into(), 0.into(), 0.into()]),
+        );
+        assert_eq!(run.execute(GCD), Ok("60,24,12,2\n".to_string()));
+        let vm = RunOptions {
+            vm: true,
+            ..inputs(vec![60.into(), 24.into(), 0.into(), 0.into()])
+        };
+        let run = Subcommand::Run("gcd.fsp", vm);
+        assert_eq!(run.execute(GCD), Ok("60,24,12,2\n".to_string()));
+        let bytecode = Subcommand::Compile("gcd.fsp").execute(GCD).unwrap();
+        assert!(bytecode.starts_with("program:\n   0  fresh g0 Int\n"));
+        assert!(bytecode.contains("\n#1 gcd:\n  31  push 0\n"));
+        let dot = Subcommand::Cfg("gcd.fsp").execute(GCD).unwrap();
+        assert_eq!(dot.matches("subgraph cluster_").count(), 3);
+        let live = Subcommand::Dataflow("gcd.fsp", Problem::Live).execute(GCD);
+        assert_eq!(live.unwrap().matches("// exit: live {").count(), 3);
+        let notes = Subcommand::Check("gcd.fsp", Lints::default()).execute(GCD);
+        assert_eq!(notes.unwrap().matches("[output-in-input]").count(), 2);
+        let mut lints = Lints::default();
+        lints.set("output-in-input", Level::Allow);
+        let check = Subcommand::Check("gcd.fsp", lints);
+        assert_eq!(check.execute(GCD), Ok(String::new()));
+        let trace = Subcommand::Trace(
+            "gcd.fsp",
+            inputs(vec![6.into(), 4.into(), 0.into(), 0.into()]),
+        );
+        let output = trace.execute(GCD).unwrap();
+        assert!(output.starts_with("call gcd((a*b), (a+b);c, d); | top 5 |"));
+        assert!(output.ends_with("\n  return gcd in (5,6) out (3,4)\n6,4,2,3\n"));
+    }
+
+    #[test]
+    fn test_fmt_round_trip() {
+        let fmt = Subcommand::Fmt("gcd.fsp", Style::default());
+        let formatted = fmt.execute(GCD).unwrap();
+        assert!(
+            formatted.starts_with("var c:Int;\n\nprocedure div(a:Int, b:Int; ref q:Int, r:Int) {")
+        );
+        assert_eq!(fmt.execute(&formatted), Ok(formatted.clone()));
+        let fmt = args(&["fmt", "gcd.fsp", "--ascii", "--width", "30"]);
+        let fmt = Subcommand::parse(&fmt).unwrap();
+        let formatted = fmt.execute(GCD).unwrap();
+        assert!(formatted.contains("  while !(a <= 0)\n      && !(b <= 0) do {"));
+        assert!(formatted.lines().all(|line| line.chars().count() <= 30));
+        assert_eq!(fmt.execute(&formatted), Ok(formatted.clone()));
+    }
+
+    #[test]
+    fn test_optimize() {
+        let optimize = args(&["optimize", "p.fsp", "--no-fold", "--no-branches"]);
+        let optimize = Subcommand::parse(&optimize).unwrap();
+        let passes = Passes {
+            propagate: true,
+            dead_code: true,
+            ..Passes::NONE
+        };
+        assert_eq!(optimize, Subcommand::Optimize("p.fsp", passes));
+        let twice = args(&["optimize", "p.fsp", "--no-fold", "--no-fold"]);
+        assert!(Subcommand::parse(&twice).is_err());
+        let source = "program p(x:Int) { var y:Int; y := 1; x := x + y; }";
+        let output = Subcommand::Optimize("p.fsp", Passes::default()).execute(source);
+        assert_eq!(
+            output,
+            Ok("program p(x:Int) {\n  x := x + 1;\n}\n".to_string())
+        );
+        assert_eq!(optimize.execute(source), output);
+    }
+
+    #[test]
+    fn test_verification_conditions() {
+        let source = "program p(x:Int) requires 0 ≤ x ensures 1 ≤ x { x := x+1; }";
+        assert_eq!(
+            Subcommand::Vc("p.fsp").execute(source),
+            Ok("program p meets its specification: (¬(0≤x)∨1≤(x+1))\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_analyze() {
+        let analyze = args(&["analyze", "p.fsp", "--signs"]);
+        let analyze = Subcommand::parse(&analyze).unwrap();
+        let options = AnalyzeOptions {
+            signs: true,
+            ..AnalyzeOptions::default()
+        };
+        assert_eq!(analyze, Subcommand::Analyze("p.fsp", options));
+        let source = "program p(x:Int) {\n  x := 0;\n  x := 1/x;\n}";
+        let output = Subcommand::Analyze("p.fsp", AnalyzeOptions::default())
+            .execute(source)
+            .unwrap();
+        assert!(output.starts_with("x:=0; | {x ↦ [-∞, +∞]}\nx:=(1/x); | {x ↦ 0}\nexit | ⊥\n"));
+        assert!(output.contains("warning: division by 0\n --> 3:8\n"));
+        let output = analyze.execute(source).unwrap();
+        assert!(output.starts_with("x:=0; | {x ↦ ⊤}\nx:=(1/x); | {x ↦ 0}\n"));
+        let checked = args(&["analyze", "p.fsp", "--i32", "checked"]);
+        let checked = Subcommand::parse(&checked).unwrap();
+        let source = "program p(x:Int) {\n  x := x + 1;\n}";
+        let output = checked.execute(source).unwrap();
+        assert!(output.contains("warning: possible i32 overflow\n --> 2:8\n"));
+        let output = Subcommand::Analyze("p.fsp", AnalyzeOptions::default()).execute(source);
+        assert!(!output.unwrap().contains("warning"));
+        let strict = args(&["analyze", "p.fsp", "--strict"]);
+        let strict = Subcommand::parse(&strict).unwrap();
+        let source = "program p(x:Int) {\n  var y:Int;\n  x := y;\n}";
+        let output = strict.execute(source).unwrap();
+        assert!(output.ends_with("x:=y; | {x ↦ [-∞, +∞], y ↦ ⊥}\nexit | ⊥\n"));
+    }
+
+    #[test]
+    fn test_verify() {
+        let verify = args(&[
+            "verify",
+            "gcd.fsp",
+            "--range",
+            "0..3",
+            "--ensures",
+            "c ≤ a*b + (a+b)",
+        ]);
+        let verify = Subcommand::parse(&verify).unwrap();
+        assert_eq!(
+            verify,
+            Subcommand::Verify("gcd.fsp", 0..=3, vec!["c ≤ a*b + (a+b)"])
+        );
+        assert_eq!(
+            verify.execute(GCD),
+            Ok("256 verified, 0 ran out of fuel\n".to_string())
+        );
+        let verify = Subcommand::Verify("gcd.fsp", 0..=3, vec!["d ≤ old_d"]);
+        let failure = verify.execute(GCD).unwrap_err();
+        assert_eq!(failure.exit_code(), 5);
+        assert!(failure
+            .message()
+            .starts_with("counterexample a = 1, b = 1, c = 0, d = 0\n"));
+        let verify = args(&["verify", "gcd.fsp", "--range", "0..x"]);
+        assert_eq!(Subcommand::parse(&verify).unwrap_err().exit_code(), 1);
+    }
+
+    #[test]
+    fn test_failure_exit_codes() {
+        let check = Subcommand::Check("p.fsp", Lints::default());
+        assert_eq!(check.execute("program p() {").unwrap_err().exit_code(), 2);
+        let source = "program p(a:Int) { a := true; a := b; }";
+        let failure = check.execute(source).unwrap_err();
+        assert_eq!(failure.exit_code(), 3);
+        assert_eq!(failure.message().matches("error:").count(), 2);
+        let run = Subcommand::Run("p.fsp", inputs(vec![1.into()]));
+        let source = "program p(a:Int) { a := a / 0; }";
+        assert_eq!(run.execute(source).unwrap_err().exit_code(), 4);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let source = "program p(a:Int) { a := a * a * a; }";
+        let i32 = |overflow| {
+            let options = RunOptions {
+                overflow: Some(overflow),
+                ..inputs(vec![100_000.into()])
+            };
+            Subcommand::Run("p.fsp", options)
+        };
+        let run = Subcommand::Run("p.fsp", inputs(vec![100_000.into()]));
+        assert_eq!(run.execute(source), Ok("1000000000000000\n".to_string()));
+        let run = i32(Overflow::Checked);
+        let failure = run.execute(source).unwrap_err();
+        assert_eq!(failure.exit_code(), 4);
+        assert!(failure
+            .message()
+            .starts_with("error: 10000000000 is out of range"));
+        let run = i32(Overflow::Wrapping);
+        let wrapped = 100_000i32.wrapping_mul(100_000).wrapping_mul(100_000);
+        assert_eq!(run.execute(source), Ok(format!("{wrapped}\n")));
+        let run = i32(Overflow::Saturating);
+        assert_eq!(run.execute(source), Ok(format!("{}\n", i32::MAX)));
+    }
+
+    #[test]
+    fn test_unassigned() {
+        let source = "program p(a:Int) {\n  var x:Int;\n  a := a + x;\n}";
+        let output = Subcommand::Check("p.fsp", Lints::default())
+            .execute(source)
+            .unwrap();
+        assert!(output.starts_with("warning: x may be read before it is assigned\n --> 3:12\n"));
+        let run = Subcommand::Run("p.fsp", inputs(vec![1.into()]));
+        assert_eq!(run.execute(source), Ok("1\n".to_string()));
+        let strict = RunOptions {
+            strict: true,
+            ..inputs(vec![1.into()])
+        };
+        let failure = Subcommand::Run("p.fsp", strict)
+            .execute(source)
+            .unwrap_err();
+        assert_eq!(failure.exit_code(), 4);
+        assert!(failure
+            .message()
+            .starts_with("error: x is read before it is assigned\n --> 3:12\n"));
+    }
+}
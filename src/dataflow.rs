@@ -0,0 +1,629 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    ops::Range,
+};
+
+use crate::{
+    cfg::{
+        pretty, solve, Block, BlockId, Cfg, Dataflow, Direction, Edge, Point, Solution, Statement,
+    },
+    hoare::free_variables,
+    typecheck::Signature,
+    types::*,
+};
+
+// the globals a procedure may read and write, itself or in the procedures it calls
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Summary<'a> {
+    pub reads: BTreeSet<Identifier<'a>>,
+    pub writes: BTreeSet<Identifier<'a>>,
+}
+
+pub type Summaries<'a> = HashMap<Signature<'a>, Summary<'a>>;
+
+// the summaries of every procedure, found by recomputing them from those of the
+// procedures they call until none changes
+pub fn summaries<'a>(cfgs: &[Cfg<'a>]) -> Summaries<'a> {
+    let mut summaries = Summaries::new();
+    loop {
+        let mut changed = Vec::new();
+        for cfg in cfgs {
+            let Some(signature) = &cfg.signature else {
+                continue;
+            };
+            let context = Context::new(cfg, &summaries);
+            let mut summary = Summary::default();
+            for block in &cfg.blocks {
+                for (statement, _) in &block.statements {
+                    summary.reads.extend(context.reads(statement));
+                    summary.writes.extend(context.writes(statement));
+                }
+            }
+            for edge in &cfg.edges {
+                if let Some((condition, _)) = &edge.condition {
+                    summary.reads.extend(variables(condition));
+                }
+            }
+            let is_global = |identifier: &Identifier| cfg.globals.contains(identifier);
+            summary.reads.retain(is_global);
+            summary.writes.retain(is_global);
+            if summaries.get(signature) != Some(&summary) {
+                changed.push((signature.clone(), summary));
+            }
+        }
+        if changed.is_empty() {
+            return summaries;
+        }
+        summaries.extend(changed);
+    }
+}
+
+// what the statements of one graph read and may write, a call through its arguments and
+// the globals of the caller its callee reads or writes
+struct Context<'s, 'a> {
+    globals: &'s [Identifier<'a>],
+    summaries: &'s Summaries<'a>,
+}
+
+impl<'s, 'a> Context<'s, 'a> {
+    fn new(cfg: &'s Cfg<'a>, summaries: &'s Summaries<'a>) -> Context<'s, 'a> {
+        Context {
+            globals: &cfg.globals,
+            summaries,
+        }
+    }
+
+    // a procedure not summarized yet doesn't touch any global, an unresolved call may
+    // touch all of them
+    fn summary(&self, signature: &Option<Signature<'a>>) -> Summary<'a> {
+        let globals: BTreeSet<_> = self.globals.iter().copied().collect();
+        let Some(signature) = signature else {
+            return Summary {
+                reads: globals.clone(),
+                writes: globals,
+            };
+        };
+        let summary = self.summaries.get(signature).cloned().unwrap_or_default();
+        Summary {
+            reads: summary.reads.intersection(&globals).copied().collect(),
+            writes: summary.writes.intersection(&globals).copied().collect(),
+        }
+    }
+
+    fn reads(&self, statement: &Statement<'a>) -> BTreeSet<Identifier<'a>> {
+        match statement {
+            Statement::Assign(_, expression) => variables(expression),
+            Statement::Var(..) => BTreeSet::new(),
+            // the callee may read its ref parameters before it writes them
+            Statement::Call(_, expressions, arguments, signature) => {
+                let mut reads = self.summary(signature).reads;
+                reads.extend(expressions.iter().flat_map(variables));
+                reads.extend(arguments);
+                reads
+            }
+        }
+    }
+
+    fn writes(&self, statement: &Statement<'a>) -> BTreeSet<Identifier<'a>> {
+        match statement {
+            Statement::Assign(identifier, _) | Statement::Var(identifier, _) => {
+                BTreeSet::from([*identifier])
+            }
+            Statement::Call(_, _, arguments, signature) => {
+                let mut writes = self.summary(signature).writes;
+                writes.extend(arguments);
+                writes
+            }
+        }
+    }
+
+    // the variables a statement writes on every execution, a callee may leave a ref
+    // parameter or global as it was
+    fn kills(&self, statement: &Statement<'a>) -> BTreeSet<Identifier<'a>> {
+        match statement {
+            Statement::Call(..) => BTreeSet::new(),
+            _ => self.writes(statement),
+        }
+    }
+}
+
+fn variables<'a>(expression: &Expression<'a>) -> BTreeSet<Identifier<'a>> {
+    let mut identifiers = Vec::new();
+    free_variables(expression, &mut identifiers);
+    identifiers.into_iter().collect()
+}
+
+// a dataflow problem whose blocks transfer facts statement by statement, so that the
+// fact at any point within a block can be recovered from the solution
+pub trait Statements<'a>: Dataflow<'a> {
+    // the fact after the statement at `at` from the one before it, the other way around
+    // for a backward problem
+    fn statement(&self, statement: &Statement<'a>, at: Point, fact: &Self::Fact) -> Self::Fact;
+    // definitions are referred to by the number of the command they were made by
+    fn describe(&self, cfg: &Cfg<'a>, fact: &Self::Fact) -> String;
+}
+
+// the fact after the statements in `range` of the block from the one before them, in the
+// direction of the problem
+fn replay<'a, D: Statements<'a>>(
+    dataflow: &D,
+    id: BlockId,
+    block: &Block<'a>,
+    range: Range<usize>,
+    fact: D::Fact,
+) -> D::Fact {
+    let statements = range.map(|index| (&block.statements[index].0, Point { block: id, index }));
+    let step = |fact: D::Fact, (statement, at)| dataflow.statement(statement, at, &fact);
+    match dataflow.direction() {
+        Direction::Forward => statements.fold(fact, step),
+        Direction::Backward => statements.rev().fold(fact, step),
+    }
+}
+
+fn transfer<'a, D: Statements<'a>>(
+    dataflow: &D,
+    id: BlockId,
+    block: &Block<'a>,
+    fact: &D::Fact,
+) -> D::Fact {
+    replay(dataflow, id, block, 0..block.statements.len(), fact.clone())
+}
+
+// the solution of a problem for one graph
+pub struct Results<'c, 'a, D: Dataflow<'a>> {
+    pub cfg: &'c Cfg<'a>,
+    pub dataflow: D,
+    pub solution: Solution<D::Fact>,
+}
+
+impl<'c, 'a, D: Statements<'a>> Results<'c, 'a, D> {
+    pub fn new(cfg: &'c Cfg<'a>, dataflow: D) -> Results<'c, 'a, D> {
+        let solution = solve(cfg, &dataflow);
+        Results {
+            cfg,
+            dataflow,
+            solution,
+        }
+    }
+
+    // the fact at the point, which holds before the statement there
+    pub fn at(&self, point: Point) -> D::Fact {
+        let Point { block: id, index } = point;
+        let block = &self.cfg.blocks[id];
+        match self.dataflow.direction() {
+            Direction::Forward => {
+                let fact = self.solution.before[id].clone();
+                replay(&self.dataflow, id, block, 0..index, fact)
+            }
+            Direction::Backward => {
+                let fact = self.solution.after[id].clone();
+                let range = index..block.statements.len();
+                replay(&self.dataflow, id, block, range, fact)
+            }
+        }
+    }
+
+    // a line for every point of the graph, before each command and at the exit, numbered
+    // in the order the commands were written
+    pub fn notes(&self) -> Vec<String> {
+        let points = self.cfg.points.iter().enumerate();
+        points
+            .map(|(number, point)| {
+                let fact = self.dataflow.describe(self.cfg, &self.at(*point));
+                if number + 1 == self.cfg.points.len() {
+                    format!("exit: {fact}")
+                } else {
+                    format!("{}: {fact}", number + 1)
+                }
+            })
+            .collect()
+    }
+}
+
+fn list(items: impl IntoIterator<Item = String>) -> String {
+    format!("{{{}}}", items.into_iter().collect::<Vec<_>>().join(", "))
+}
+
+// the variables whose current value may be read later, backward over sets of variables:
+// the out parameters and globals are at the exit of a procedure, the parameters at the
+// exit of the program
+pub struct Liveness<'s, 'a> {
+    context: Context<'s, 'a>,
+}
+
+impl<'s, 'a> Liveness<'s, 'a> {
+    pub fn new(cfg: &'s Cfg<'a>, summaries: &'s Summaries<'a>) -> Liveness<'s, 'a> {
+        Liveness {
+            context: Context::new(cfg, summaries),
+        }
+    }
+}
+
+impl<'s, 'a> Dataflow<'a> for Liveness<'s, 'a> {
+    type Fact = BTreeSet<Identifier<'a>>;
+
+    fn direction(&self) -> Direction {
+        Direction::Backward
+    }
+    fn boundary(&self, cfg: &Cfg<'a>) -> Self::Fact {
+        let mut live: Self::Fact = cfg.out_params.iter().copied().collect();
+        if cfg.signature.is_some() {
+            live.extend(&cfg.globals);
+        }
+        live
+    }
+    fn initial(&self, _: &Cfg<'a>) -> Self::Fact {
+        BTreeSet::new()
+    }
+    fn join(&self, left: &Self::Fact, right: &Self::Fact) -> Self::Fact {
+        left.union(right).copied().collect()
+    }
+    fn transfer(&self, id: BlockId, block: &Block<'a>, fact: &Self::Fact) -> Self::Fact {
+        transfer(self, id, block, fact)
+    }
+    fn edge(&self, edge: &Edge<'a>, fact: &Self::Fact) -> Self::Fact {
+        let mut fact = fact.clone();
+        if let Some((condition, _)) = &edge.condition {
+            fact.extend(variables(condition));
+        }
+        fact
+    }
+}
+
+impl<'s, 'a> Statements<'a> for Liveness<'s, 'a> {
+    fn statement(&self, statement: &Statement<'a>, _: Point, fact: &Self::Fact) -> Self::Fact {
+        let mut fact = fact - &self.context.kills(statement);
+        fact.extend(self.context.reads(statement));
+        fact
+    }
+    fn describe(&self, _: &Cfg<'a>, fact: &Self::Fact) -> String {
+        format!("live {}", list(fact.iter().map(ToString::to_string)))
+    }
+}
+
+// where a variable may have been given its current value, none for the value it had when
+// the procedure or program was entered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Definition<'a> {
+    pub variable: Identifier<'a>,
+    pub at: Option<Point>,
+}
+
+// the definitions that may reach a point, forward over sets of definitions: assignments
+// and declarations replace the definitions of their variable, calls only add theirs
+pub struct ReachingDefinitions<'s, 'a> {
+    context: Context<'s, 'a>,
+}
+
+impl<'s, 'a> ReachingDefinitions<'s, 'a> {
+    pub fn new(cfg: &'s Cfg<'a>, summaries: &'s Summaries<'a>) -> ReachingDefinitions<'s, 'a> {
+        ReachingDefinitions {
+            context: Context::new(cfg, summaries),
+        }
+    }
+}
+
+impl<'s, 'a> Dataflow<'a> for ReachingDefinitions<'s, 'a> {
+    type Fact = BTreeSet<Definition<'a>>;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+    fn boundary(&self, cfg: &Cfg<'a>) -> Self::Fact {
+        let variables = [&cfg.in_params, &cfg.out_params, &cfg.globals];
+        let variables = variables.into_iter().flatten();
+        variables
+            .map(|variable| Definition {
+                variable: *variable,
+                at: None,
+            })
+            .collect()
+    }
+    fn initial(&self, _: &Cfg<'a>) -> Self::Fact {
+        BTreeSet::new()
+    }
+    fn join(&self, left: &Self::Fact, right: &Self::Fact) -> Self::Fact {
+        left.union(right).copied().collect()
+    }
+    fn transfer(&self, id: BlockId, block: &Block<'a>, fact: &Self::Fact) -> Self::Fact {
+        transfer(self, id, block, fact)
+    }
+}
+
+impl<'s, 'a> Statements<'a> for ReachingDefinitions<'s, 'a> {
+    fn statement(&self, statement: &Statement<'a>, at: Point, fact: &Self::Fact) -> Self::Fact {
+        let kills = self.context.kills(statement);
+        let mut fact = fact.clone();
+        fact.retain(|definition| !kills.contains(&definition.variable));
+        fact.extend(self.context.writes(statement).into_iter().map(|variable| {
+            let at = Some(at);
+            Definition { variable, at }
+        }));
+        fact
+    }
+    fn describe(&self, cfg: &Cfg<'a>, fact: &Self::Fact) -> String {
+        let definitions = fact.iter().map(|Definition { variable, at }| {
+            match at.and_then(|at| cfg.points.iter().position(|point| *point == at)) {
+                Some(number) => format!("{variable}@{}", number + 1),
+                None => format!("{variable}@entry"),
+            }
+        });
+        format!("reaching {}", list(definitions))
+    }
+}
+
+// the expressions whose value was computed on every path and hasn't changed since,
+// forward over sets of indices into the non-trivial subexpressions of the graph: only
+// the left operand of ∧, ∨ and ⇒ is always evaluated, and writing a variable makes the
+// expressions that mention it unavailable
+pub struct AvailableExpressions<'s, 'a> {
+    context: Context<'s, 'a>,
+    pub expressions: Vec<Expression<'a>>,
+    // of each expression
+    variables: Vec<BTreeSet<Identifier<'a>>>,
+}
+
+impl<'s, 'a> AvailableExpressions<'s, 'a> {
+    pub fn new(cfg: &'s Cfg<'a>, summaries: &'s Summaries<'a>) -> AvailableExpressions<'s, 'a> {
+        let mut expressions = Vec::new();
+        for block in &cfg.blocks {
+            for (statement, _) in &block.statements {
+                for expression in statement_expressions(statement) {
+                    evaluated(expression, &mut expressions);
+                }
+            }
+        }
+        for edge in &cfg.edges {
+            if let Some((condition, _)) = &edge.condition {
+                evaluated(condition, &mut expressions);
+            }
+        }
+        AvailableExpressions {
+            context: Context::new(cfg, summaries),
+            variables: expressions.iter().map(variables).collect(),
+            expressions,
+        }
+    }
+
+    fn generate(&self, expression: &Expression<'a>, fact: &mut BTreeSet<usize>) {
+        let mut found = Vec::new();
+        evaluated(expression, &mut found);
+        fact.extend(found.iter().filter_map(|expression| {
+            let mut expressions = self.expressions.iter();
+            expressions.position(|other| other == expression)
+        }));
+    }
+}
+
+fn statement_expressions<'s, 'a>(statement: &'s Statement<'a>) -> &'s [Expression<'a>] {
+    match statement {
+        Statement::Assign(_, expression) => std::slice::from_ref(expression),
+        Statement::Var(..) => &[],
+        Statement::Call(_, expressions, ..) => expressions,
+    }
+}
+
+// adds the operations evaluating the expression always performs, operands first
+fn evaluated<'a>(expression: &Expression<'a>, found: &mut Vec<Expression<'a>>) {
+    let expression = expression.unspanned();
+    match expression {
+        Expression::Value(_) | Expression::Variable(_) => return,
+        Expression::Negative(operand) | Expression::Not(operand) => evaluated(operand, found),
+        Expression::And(left, _) | Expression::Or(left, _) | Expression::Implies(left, _) => {
+            evaluated(left, found)
+        }
+        Expression::Sum(left, right)
+        | Expression::Difference(left, right)
+        | Expression::Product(left, right)
+        | Expression::Division(left, right)
+        | Expression::Remainder(left, right)
+        | Expression::Equal(left, right)
+        | Expression::NotEqual(left, right)
+        | Expression::LessThan(left, right)
+        | Expression::LessThanOrEqual(left, right)
+        | Expression::GreaterThan(left, right)
+        | Expression::GreaterThanOrEqual(left, right)
+        | Expression::Equivalent(left, right) => {
+            evaluated(left, found);
+            evaluated(right, found);
+        }
+        Expression::Spanned(..) => unreachable!("unspanned"),
+    }
+    if !found.contains(expression) {
+        found.push(expression.clone());
+    }
+}
+
+impl<'s, 'a> Dataflow<'a> for AvailableExpressions<'s, 'a> {
+    type Fact = BTreeSet<usize>;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+    fn boundary(&self, _: &Cfg<'a>) -> Self::Fact {
+        BTreeSet::new()
+    }
+    fn initial(&self, _: &Cfg<'a>) -> Self::Fact {
+        (0..self.expressions.len()).collect()
+    }
+    fn join(&self, left: &Self::Fact, right: &Self::Fact) -> Self::Fact {
+        left.intersection(right).copied().collect()
+    }
+    fn transfer(&self, id: BlockId, block: &Block<'a>, fact: &Self::Fact) -> Self::Fact {
+        transfer(self, id, block, fact)
+    }
+    fn edge(&self, edge: &Edge<'a>, fact: &Self::Fact) -> Self::Fact {
+        let mut fact = fact.clone();
+        if let Some((condition, _)) = &edge.condition {
+            self.generate(condition, &mut fact);
+        }
+        fact
+    }
+}
+
+impl<'s, 'a> Statements<'a> for AvailableExpressions<'s, 'a> {
+    fn statement(&self, statement: &Statement<'a>, _: Point, fact: &Self::Fact) -> Self::Fact {
+        let mut fact = fact.clone();
+        for expression in statement_expressions(statement) {
+            self.generate(expression, &mut fact);
+        }
+        let writes = self.context.writes(statement);
+        fact.retain(|index| self.variables[*index].is_disjoint(&writes));
+        fact
+    }
+    fn describe(&self, _: &Cfg<'a>, fact: &Self::Fact) -> String {
+        let expressions = fact.iter().map(|index| pretty(&self.expressions[*index]));
+        format!("available {}", list(expressions))
+    }
+}
+
+// the notes of the analysis for each graph, in the order of `Program::cfgs`
+pub fn annotate<'c, 'a, D: Statements<'a>>(
+    cfgs: &'c [Cfg<'a>],
+    analysis: impl Fn(&'c Cfg<'a>) -> D,
+) -> Vec<Vec<String>> {
+    let results = cfgs.iter().map(|cfg| Results::new(cfg, analysis(cfg)));
+    results.map(|results| results.notes()).collect()
+}
+
+// the program printed like its `Display` with a comment before every command and before
+// the closing brace of each body, from the notes of its graphs
+pub struct Annotated<'p, 'a> {
+    pub program: &'p Program<'a>,
+    pub notes: Vec<Vec<String>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "var g:Int; var h:Int;
+procedure inc(;ref r:Int) { r := r + g; h := 1; }
+program p(a:Int, b:Int) {
+  var x:Int;
+  x := a + b;
+  if 0 < x then { call inc(;x); }
+  a := a + b;
+  b := x;
+}";
+
+    fn names<T: ToString>(items: impl IntoIterator<Item = T>) -> Vec<String> {
+        items.into_iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn test_summaries() -> Result<(), String> {
+        let source = "var g:Int; var h:Int;
+procedure f(n:Int; ref r:Int) { if 0 < n then call f(n - 1; h); r := g; }
+procedure k(;ref g:Int) { call f(0; g); }
+program p(a:Int) { call k(;a); }";
+        let cfgs = Program::parse(source)?.typecheck()?.cfgs();
+        let summaries = summaries(&cfgs);
+        let summary = |cfg: &Cfg| summaries[cfg.signature.as_ref().unwrap()].clone();
+        let f = summary(&cfgs[0]);
+        // the recursive call reads its ref argument
+        assert_eq!(
+            (names(&f.reads), names(&f.writes)),
+            (names(["g", "h"]), names(["h"]))
+        );
+        // g is the parameter of k, so what f does to the global isn't visible to it
+        let k = summary(&cfgs[1]);
+        assert_eq!(
+            (names(&k.reads), names(&k.writes)),
+            (names(["h"]), names(["h"]))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_liveness() -> Result<(), String> {
+        let cfgs = Program::parse(SOURCE)?.typecheck()?.cfgs();
+        let summaries = summaries(&cfgs);
+        let [inc, p] = &cfgs[..] else {
+            panic!("{} graphs", cfgs.len());
+        };
+        let results = Results::new(inc, Liveness::new(inc, &summaries));
+        let live = |point| names(results.at(point));
+        assert_eq!(live(inc.points[0]), names(["g", "r"]));
+        // h is written before the exit, the global g is live there for the callers
+        assert_eq!(live(inc.points[1]), names(["g", "r"]));
+        let results = Results::new(p, Liveness::new(p, &summaries));
+        let live = |point| names(results.at(point));
+        // before `var x`, `x := a + b`, the if, the call, `a := a + b`, `b := x` and the exit
+        assert_eq!(live(p.points[0]), names(["a", "b", "g"]));
+        assert_eq!(live(p.points[2]), names(["a", "b", "g", "x"]));
+        // the call reads its ref argument and the global the callee reads
+        assert_eq!(live(p.points[3]), names(["a", "b", "g", "x"]));
+        assert_eq!(live(p.points[4]), names(["a", "b", "x"]));
+        assert_eq!(live(p.points[6]), names(["a", "b"]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_reaching_definitions() -> Result<(), String> {
+        let cfgs = Program::parse(SOURCE)?.typecheck()?.cfgs();
+        let summaries = summaries(&cfgs);
+        let p = &cfgs[1];
+        let results = Results::new(p, ReachingDefinitions::new(p, &summaries));
+        let describe = |point| results.dataflow.describe(p, &results.at(point));
+        assert_eq!(
+            describe(p.points[1]),
+            "reaching {a@entry, b@entry, g@entry, h@entry, x@1}"
+        );
+        // the call may or may not have changed x, and it wrote h
+        assert_eq!(
+            describe(p.points[4]),
+            "reaching {a@entry, b@entry, g@entry, h@entry, h@4, x@2, x@4}"
+        );
+        assert_eq!(
+            describe(p.points[6]),
+            "reaching {a@5, b@6, g@entry, h@entry, h@4, x@2, x@4}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_available_expressions() -> Result<(), String> {
+        let source = "program p(a:Int, b:Int, c:Bool) {
+  var x:Int;
+  x := a + b;
+  if c ∧ 0 < a * b then { b := a + b; } else { x := -(a + b); }
+  while a + b < x ∨ 0 < a do { a := a - 1; }
+  b := a;
+}";
+        let cfgs = Program::parse(source)?.typecheck()?.cfgs();
+        let summaries = summaries(&cfgs);
+        let p = &cfgs[0];
+        let results = Results::new(p, AvailableExpressions::new(p, &summaries));
+        let describe = |point| results.dataflow.describe(p, &results.at(point));
+        assert_eq!(describe(p.points[2]), "available {a + b}");
+        // the right operand of ∧ may not have been evaluated, b changed in one branch
+        assert_eq!(describe(p.points[4]), "available {a + b, c ∧ 0 < a * b}");
+        assert_eq!(describe(p.points[5]), "available {}");
+        let condition = "available {a + b, a + b < x, a + b < x ∨ 0 < a}";
+        assert_eq!(describe(p.points[6]), condition);
+        assert_eq!(describe(p.points[7]), condition);
+        assert_eq!(describe(p.points[8]), "available {}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_annotated() -> Result<(), String> {
+        let program = Program::parse(SOURCE)?.typecheck()?;
+        let cfgs = program.cfgs();
+        let summaries = summaries(&cfgs);
+        let notes = annotate(&cfgs, |cfg| Liveness::new(cfg, &summaries));
+        let annotated = Annotated {
+            program: &program,
+            notes,
+        }
+        .to_string();
+        assert!(annotated.starts_with("var g:Int;\nvar h:Int;\nprocedure inc (; ref r:Int) {\n"));
+        assert!(annotated.contains("  // 2: live {g, r}\n  h:=1;\n  // exit: live {g, h, r}\n}\n"));
+        assert!(annotated
+            .contains("  if (0<x) then {\n    // 4: live {a, b, g, x}\n    call inc(;x);\n  }\n"));
+        // the notes are comments
+        assert_eq!(Program::parse(&annotated)?.typecheck()?, program);
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use std::{error::Error, fmt::Display};
+
+use crate::types::Span;
+
+// an error together with the source span it was raised for, if the program was parsed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic<E> {
+    pub error: E,
+    pub span: Option<Span>,
+}
+
+impl<E> Diagnostic<E> {
+    pub fn new(error: E, span: Span) -> Diagnostic<E> {
+        Diagnostic {
+            error,
+            span: Some(span),
+        }
+    }
+
+    // errors keep the span of the innermost node that has one
+    pub fn or_span(mut self, span: Span) -> Diagnostic<E> {
+        self.span.get_or_insert(span);
+        self
+    }
+}
+
+impl<E: Display> Diagnostic<E> {
+    // the error followed by the offending source line with the span underlined
+    pub fn render(&self, source: &str) -> String {
+        self.render_as("error", source)
+    }
+
+    // `level` says how serious it is, e.g. `warning`
+    pub fn render_as(&self, level: &str, source: &str) -> String {
+        let Some(span) = self.span else {
+            return format!("{level}: {}", self.error);
+        };
+        let start = span.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+        let line_number = source[..start].matches('\n').count() + 1;
+        let column = source[line_start..start].chars().count();
+        let width = source[start..span.end.clamp(start, line_end)]
+            .chars()
+            .count()
+            .max(1);
+        let gutter = " ".repeat(line_number.to_string().len());
+        format!(
+            "{level}: {}\n{gutter}--> {line_number}:{}\n{gutter} |\n{line_number} | {}\n{gutter} | {}{}",
+            self.error,
+            column + 1,
+            &source[line_start..line_end],
+            " ".repeat(column),
+            "^".repeat(width),
+        )
+    }
+}
+
+impl<E: Display> Display for Diagnostic<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<E: Error> Error for Diagnostic<E> {}
+
+impl<E> From<E> for Diagnostic<E> {
+    fn from(error: E) -> Self {
+        Diagnostic { error, span: None }
+    }
+}
+
+impl From<&str> for Diagnostic<String> {
+    fn from(error: &str) -> Self {
+        error.to_string().into()
+    }
+}
+
+impl<E: Display> From<Diagnostic<E>> for String {
+    fn from(diagnostic: Diagnostic<E>) -> Self {
+        diagnostic.to_string()
+    }
+}
+
+// every diagnostic of a phase that carries on after the first error
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Diagnostics<E>(pub Vec<Diagnostic<E>>);
+
+impl<E> Diagnostics<E> {
+    pub fn new() -> Diagnostics<E> {
+        Diagnostics(Vec::new())
+    }
+
+    pub fn push(&mut self, diagnostic: impl Into<Diagnostic<E>>) {
+        self.0.push(diagnostic.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    // diagnostics reported by `check` without a span of their own get `span`
+    pub fn within<T>(&mut self, span: Span, check: impl FnOnce(&mut Self) -> T) -> T {
+        let start = self.0.len();
+        let result = check(self);
+        for diagnostic in &mut self.0[start..] {
+            diagnostic.span.get_or_insert(span);
+        }
+        result
+    }
+}
+
+impl<E: Display> Diagnostics<E> {
+    pub fn render(&self, source: &str) -> String {
+        self.render_as("error", source)
+    }
+
+    pub fn render_as(&self, level: &str, source: &str) -> String {
+        self.0
+            .iter()
+            .map(|diagnostic| diagnostic.render_as(level, source))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+impl<E: Display> Display for Diagnostics<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let errors: Vec<_> = self
+            .0
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect();
+        write!(f, "{}", errors.join("\n"))
+    }
+}
+
+impl<E: Error> Error for Diagnostics<E> {}
+
+impl<E: Display> From<Diagnostics<E>> for String {
+    fn from(diagnostics: Diagnostics<E>) -> Self {
+        diagnostics.to_string()
+    }
+}
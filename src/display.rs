@@ -1,4 +1,18 @@
-use crate::types::*;
+use crate::{
+    absint::{Abstract, Analysis, Domain, Interval, Sign, State, Truth, Warning, MAX, MIN},
+    assignment::Unassigned,
+    bounded::{Counterexample, Failure, Verdict},
+    bytecode::{Bytecode, Instruction, Location},
+    cfg::Statement,
+    dataflow::Annotated,
+    eval::{Divergence, ProcedureEnvironment, RuntimeError, Store, VariableEnvironment},
+    hoare::{Obligation, VerificationCondition},
+    integer::Integer,
+    smallstep::{Configuration, Term},
+    trace::{Change, Event, Trace},
+    typecheck::{Aliasing, Level, TypeError},
+    types::*,
+};
 use std::fmt::Display;
 
 impl<'a> Display for Expression<'a> {
@@ -10,12 +24,32 @@ impl<'a> Display for Expression<'a> {
             Expression::Difference(left, right) => write!(f, "({left}-{right})"),
             Expression::Product(left, right) => write!(f, "({left}*{right})"),
             Expression::Division(left, right) => write!(f, "({left}/{right})"),
+            Expression::Remainder(left, right) => write!(f, "({left}%{right})"),
             Expression::Negative(expression) => write!(f, "-({expression})"),
-            Expression::Equal(left, right) => write!(f, "{left}={right}"),
-            Expression::LessThanOrEqual(left, right) => write!(f, "{left}≤{right}"),
-            Expression::And(left, right) => write!(f, "({left}∧{right}"),
+            Expression::Equal(left, right) => {
+                write!(f, "{}={}", comparand(left), comparand(right))
+            }
+            Expression::NotEqual(left, right) => {
+                write!(f, "{}≠{}", comparand(left), comparand(right))
+            }
+            Expression::LessThan(left, right) => {
+                write!(f, "{}<{}", comparand(left), comparand(right))
+            }
+            Expression::LessThanOrEqual(left, right) => {
+                write!(f, "{}≤{}", comparand(left), comparand(right))
+            }
+            Expression::GreaterThan(left, right) => {
+                write!(f, "{}>{}", comparand(left), comparand(right))
+            }
+            Expression::GreaterThanOrEqual(left, right) => {
+                write!(f, "{}≥{}", comparand(left), comparand(right))
+            }
+            Expression::And(left, right) => write!(f, "({left}∧{right})"),
             Expression::Or(left, right) => write!(f, "({left}∨{right})"),
+            Expression::Implies(left, right) => write!(f, "({left}⇒{right})"),
+            Expression::Equivalent(left, right) => write!(f, "({left}⇔{right})"),
             Expression::Not(expression) => write!(f, "¬({expression})"),
+            Expression::Spanned(_, expression) => expression.fmt(f),
         }
     }
 }
@@ -28,7 +62,19 @@ impl Display for Sort<'_> {
 
 impl Display for Identifier<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        match self.1 {
+            Some(n) => write!(f, "{}'{n}", self.0),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+impl Display for Integer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_negative() {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", self.decimal())
     }
 }
 
@@ -52,14 +98,21 @@ impl<'a> Display for Declaration<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Declaration::Variable(identifier, sort) => write!(f, "var {identifier}:{sort};"),
-            Declaration::Procedure(name, input_parameters, output_parameters, body) => {
+            Declaration::Procedure(
+                name,
+                input_parameters,
+                output_parameters,
+                body,
+                specification,
+            ) => {
                 let body = indent(format!("{body}"));
                 write!(f, "procedure {name} ({input_parameters}")?;
-                if let Parameters::Sequence(..) = output_parameters {
+                if !output_parameters.is_empty() {
                     write!(f, "; ref {output_parameters}")?;
                 }
-                write!(f, ") {{\n{body}\n}}")
+                write!(f, "){specification} {{\n{body}\n}}")
             }
+            Declaration::Spanned(_, declaration) => declaration.fmt(f),
         }
     }
 }
@@ -70,20 +123,36 @@ impl<'a> Display for Parameters<'a> {
             Parameters::Empty => write!(f, ""),
             Parameters::Sequence(rest, variable, sort) => {
                 write!(f, "{rest}")?;
-                if let Parameters::Sequence(..) = **rest {
+                if !rest.is_empty() {
                     write!(f, ",")?;
                 }
                 write!(f, "{variable}:{sort}")
             }
+            Parameters::Spanned(_, parameters) => parameters.fmt(f),
         }
     }
 }
 
 impl<'a> Display for Program<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let Program(declarations, name, input, body) = self;
+        let Program(declarations, name, input, body, specification) = self;
         let body = indent(format!("{body}"));
-        write!(f, "{declarations}\nprogram {name} ({input}) {{\n{body}\n}}")
+        write!(
+            f,
+            "{declarations}program {name} ({input}){specification} {{\n{body}\n}}"
+        )
+    }
+}
+
+impl<'a> Display for Specification<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(requires) = &self.requires {
+            write!(f, " requires {requires}")?;
+        }
+        if let Some(ensures) = &self.ensures {
+            write!(f, " ensures {ensures}")?;
+        }
+        Ok(())
     }
 }
 
@@ -91,7 +160,7 @@ impl<'a> Display for Declarations<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Declarations::Empty => write!(f, ""),
-            Declarations::Sequence(declaration, rest) => write!(f, "{declaration}\n{rest}"),
+            Declarations::Sequence(rest, declaration) => writeln!(f, "{rest}{declaration}"),
         }
     }
 }
@@ -131,28 +200,595 @@ impl<'a> Display for Command<'a> {
         match self {
             Command::Assign(name, expression) => write!(f, "{name}:={expression};"),
             Command::Var(name, sort, rest) => write!(f, "var {name}:{sort};\n{rest}"),
-            Command::Sequence(first, rest) => write!(f, "{first}\n{rest}"),
+            Command::Sequence(first, rest) => match first.unspanned() {
+                // the scope of a var ends before the rest of the sequence
+                Command::Var(..) => {
+                    let first = indent(format!("{first}"));
+                    write!(f, "{{\n{first}\n}}\n{rest}")
+                }
+                _ => write!(f, "{first}\n{rest}"),
+            },
             Command::IfElse(condition, if_branch, else_branch) => {
                 let if_branch = indent(format!("{if_branch}"));
                 let else_branch = indent(format!("{else_branch}"));
-                write!(f, "if ({condition}) then {{\n{if_branch}\n}} else {{\n{else_branch}\n}}")
+                write!(
+                    f,
+                    "if ({condition}) then {{\n{if_branch}\n}} else {{\n{else_branch}\n}}"
+                )
             }
             Command::If(condition, if_branch) => {
                 let if_branch = indent(format!("{if_branch}"));
-                write!(f, "if ({condition}) then\n{if_branch}")
+                write!(f, "if ({condition}) then {{\n{if_branch}\n}}")
             }
-            Command::While(cond, body) => {
+            Command::While(cond, body, invariant) => {
                 let body = indent(format!("{body}"));
-                write!(f, "while {cond} do {{\n{body}\n}}")
+                write!(f, "while {cond} ")?;
+                if let Some(invariant) = invariant {
+                    write!(f, "invariant {invariant} ")?;
+                }
+                write!(f, "do {{\n{body}\n}}")
             }
             Command::Call(function, input, output, _) => {
                 write!(f, "call {function}({input};{output});")
             }
+            Command::Spanned(_, command) => command.fmt(f),
+        }
+    }
+}
+
+impl<'a> Display for Statement<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Statement::Assign(name, expression) => write!(f, "{name}:={expression}"),
+            Statement::Var(name, sort) => write!(f, "var {name}:{sort}"),
+            Statement::Call(function, input, output, _) => {
+                let input: Vec<_> = input.iter().map(ToString::to_string).collect();
+                let output: Vec<_> = output.iter().map(ToString::to_string).collect();
+                write!(
+                    f,
+                    "call {function}({};{})",
+                    input.join(", "),
+                    output.join(", ")
+                )
+            }
+        }
+    }
+}
+
+// the notes as comments, each before the command it belongs to, the last before the
+// closing brace of the body
+impl<'p, 'a> Display for Annotated<'p, 'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Program(declarations, name, input, body, specification) = self.program;
+        let mut notes = self.notes.iter().map(|notes| &notes[..]);
+        for declaration in declarations.sequence() {
+            match declaration.unspanned() {
+                Declaration::Procedure(name, input, output, body, specification) => {
+                    let body = annotated_body(body, notes.next().unwrap_or_default());
+                    write!(f, "procedure {name} ({input}")?;
+                    if !output.is_empty() {
+                        write!(f, "; ref {output}")?;
+                    }
+                    writeln!(f, "){specification} {{\n{body}\n}}")?;
+                }
+                _ => writeln!(f, "{declaration}")?,
+            }
+        }
+        let body = annotated_body(body, notes.next().unwrap_or_default());
+        write!(f, "program {name} ({input}){specification} {{\n{body}\n}}")
+    }
+}
+
+fn annotated_body(body: &Command, notes: &[String]) -> String {
+    let mut notes = notes.iter();
+    let mut body = annotated(body, &mut notes);
+    for note in notes {
+        body.push_str(&format!("\n// {note}"));
+    }
+    indent(body)
+}
+
+// like the `Display` of the command, with the next note before each command that isn't
+// a sequence
+fn annotated<'n>(command: &Command, notes: &mut impl Iterator<Item = &'n String>) -> String {
+    let note = match command {
+        Command::Sequence(..) | Command::Spanned(..) => None,
+        _ => notes.next(),
+    };
+    let command = match command {
+        Command::Assign(..) | Command::Call(..) => command.to_string(),
+        Command::Var(name, sort, rest) => format!("var {name}:{sort};\n{}", annotated(rest, notes)),
+        Command::Sequence(first, rest) => {
+            let (first_command, first) = (first.unspanned(), annotated(first, notes));
+            let rest = annotated(rest, notes);
+            match first_command {
+                Command::Var(..) => format!("{{\n{}\n}}\n{rest}", indent(first)),
+                _ => format!("{first}\n{rest}"),
+            }
+        }
+        Command::IfElse(condition, if_branch, else_branch) => {
+            let if_branch = indent(annotated(if_branch, notes));
+            let else_branch = indent(annotated(else_branch, notes));
+            format!("if ({condition}) then {{\n{if_branch}\n}} else {{\n{else_branch}\n}}")
+        }
+        Command::If(condition, if_branch) => {
+            let if_branch = indent(annotated(if_branch, notes));
+            format!("if ({condition}) then {{\n{if_branch}\n}}")
+        }
+        Command::While(condition, body, invariant) => {
+            let invariant = match invariant {
+                Some(invariant) => format!("invariant {invariant} "),
+                None => String::new(),
+            };
+            let body = indent(annotated(body, notes));
+            format!("while {condition} {invariant}do {{\n{body}\n}}")
+        }
+        Command::Spanned(_, command) => return annotated(command, notes),
+    };
+    match note {
+        Some(note) => format!("// {note}\n{command}"),
+        None => command,
+    }
+}
+
+impl<'a> Display for TypeError<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeError::UndefinedIdentifier(identifier) => {
+                write!(f, "identifier {identifier} is not defined")
+            }
+            TypeError::DuplicateParameter(identifier) => {
+                write!(f, "parameter {identifier} declared twice")
+            }
+            TypeError::SortMismatch { expected, found } => {
+                write!(
+                    f,
+                    "expected expression of sort {expected} but found {found}"
+                )
+            }
+            TypeError::NoMatchingSignature {
+                name,
+                in_sorts,
+                out_sorts,
+            } => {
+                let in_sorts = join(in_sorts);
+                let out_sorts = join(out_sorts);
+                write!(
+                    f,
+                    "no procedure matches signature {name}({in_sorts};{out_sorts})"
+                )
+            }
+            TypeError::NonBoolCondition(sort) => {
+                write!(f, "condition must be of sort Bool but is {sort}")
+            }
+            TypeError::AmbiguousOld(identifier) => {
+                write!(f, "{identifier} names both a variable and an input value")
+            }
+            TypeError::Aliasing(aliasing) => write!(f, "{aliasing}"),
+        }
+    }
+}
+
+impl<'a> Display for Aliasing<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Aliasing::DuplicateOutput(identifier) => {
+                write!(f, "{identifier} is passed as more than one ref argument")?
+            }
+            Aliasing::OutputInInput(identifier) => write!(
+                f,
+                "{identifier} is passed as a ref argument and read by an in argument"
+            )?,
+        }
+        write!(f, " [{}]", self.code())
+    }
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Level::Allow => write!(f, "allow"),
+            Level::Note => write!(f, "note"),
+            Level::Warning => write!(f, "warning"),
+            Level::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl<'a> Display for RuntimeError<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeError::DivisionByZero => write!(f, "division by 0"),
+            RuntimeError::UnknownIdentifier(identifier) => {
+                write!(f, "unknown identifier {identifier}")
+            }
+            RuntimeError::UnknownSignature((name, (in_sorts, out_sorts))) => {
+                let in_sorts = join(in_sorts);
+                let out_sorts = join(out_sorts);
+                write!(f, "unknown signature {name}({in_sorts};{out_sorts})")
+            }
+            RuntimeError::UnannotatedCall(name) => {
+                write!(f, "call of {name} hasn't been annotated")
+            }
+            RuntimeError::ArityMismatch { expected, found } => {
+                write!(f, "expected {expected} values but got {found}")
+            }
+            RuntimeError::NotANumeral(value) => {
+                write!(f, "expected a numeral but found {value}")
+            }
+            RuntimeError::Overflow(integer) => write!(f, "{integer} is out of range"),
+            RuntimeError::Uninitialized(identifier) => {
+                write!(f, "{identifier} is read before it is assigned")
+            }
+        }
+    }
+}
+
+impl<'a> Display for Obligation<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Obligation::Procedure(name) => write!(f, "procedure {name} meets its specification"),
+            Obligation::Program(name) => write!(f, "program {name} meets its specification"),
+            Obligation::Preserved => write!(f, "the loop preserves its invariant"),
+            Obligation::Exit => write!(f, "the invariant holds what follows the loop"),
+        }
+    }
+}
+
+impl<'a> Display for VerificationCondition<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.obligation, self.formula)
+    }
+}
+
+// MIN and MAX are the infinite bounds
+impl Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bound = |bound: i64| match bound {
+            MIN => "-∞".to_string(),
+            MAX => "+∞".to_string(),
+            bound => bound.to_string(),
+        };
+        match self {
+            Interval::Empty => write!(f, "⊥"),
+            Interval::Between(low, high) if low == high => write!(f, "{low}"),
+            Interval::Between(low, high) => write!(f, "[{}, {}]", bound(*low), bound(*high)),
+        }
+    }
+}
+
+impl Display for Sign {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = match (self.negative, self.zero, self.positive) {
+            (false, false, false) => "⊥",
+            (true, false, false) => "-",
+            (false, true, false) => "0",
+            (false, false, true) => "+",
+            (true, true, false) => "≤0",
+            (true, false, true) => "≠0",
+            (false, true, true) => "≥0",
+            (true, true, true) => "⊤",
+        };
+        write!(f, "{sign}")
+    }
+}
+
+impl Display for Truth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.can_be_true, self.can_be_false) {
+            (false, false) => write!(f, "⊥"),
+            (true, false) => write!(f, "true"),
+            (false, true) => write!(f, "false"),
+            (true, true) => write!(f, "⊤"),
+        }
+    }
+}
+
+impl<D: Display> Display for Abstract<D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Abstract::Numeral(numeral) => numeral.fmt(f),
+            Abstract::Truth(truth) => truth.fmt(f),
+        }
+    }
+}
+
+impl<'a, D: Display> Display for State<'a, D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some(bindings) = &self.0 else {
+            return write!(f, "⊥");
+        };
+        let bindings: Vec<_> = (bindings.iter())
+            .map(|(identifier, value)| format!("{identifier} ↦ {value}"))
+            .collect();
+        write!(f, "{{{}}}", bindings.join(", "))
+    }
+}
+
+// the state before every command in the order they were reached, then the final state
+impl<'p, 'a, D: Domain> Display for Analysis<'p, 'a, D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for point in &self.points {
+            let command = point.command.to_string();
+            let command = command.lines().next().unwrap_or_default();
+            writeln!(f, "{command} | {}", point.state)?;
+        }
+        writeln!(f, "exit | {}", self.exit)
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::DivisionByZero { always: true } => write!(f, "division by 0"),
+            Warning::DivisionByZero { always: false } => write!(f, "possible division by 0"),
+            Warning::Overflow { always: true } => write!(f, "i32 overflow"),
+            Warning::Overflow { always: false } => write!(f, "possible i32 overflow"),
+            Warning::ConstantCondition(value) => write!(f, "condition is always {value}"),
+        }
+    }
+}
+
+impl<'a> Display for Verdict<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verdict::Verified { verified, diverged } => {
+                writeln!(f, "{verified} verified, {diverged} ran out of fuel")
+            }
+            Verdict::Counterexample(counterexample) => counterexample.fmt(f),
+        }
+    }
+}
+
+// the failure, then the trace of the run that led to it
+impl<'a> Display for Counterexample<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bindings = |values: &[Value]| {
+            let bindings: Vec<_> = (self.parameters.iter().zip(values))
+                .map(|(parameter, value)| format!("{parameter} = {value}"))
+                .collect();
+            bindings.join(", ")
+        };
+        writeln!(f, "counterexample {}", bindings(&self.inputs))?;
+        match &self.failure {
+            Failure::Error(error) => writeln!(f, "error: {error}")?,
+            Failure::Violated {
+                postcondition,
+                outputs,
+            } => writeln!(
+                f,
+                "postcondition {postcondition} doesn't hold for {}",
+                bindings(outputs)
+            )?,
+        }
+        self.trace.fmt(f)
+    }
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Divergence::OutOfFuel(steps) => write!(f, "gave up after {steps} steps"),
+            Divergence::TooDeep(depth) => {
+                write!(f, "gave up at more than {depth} nested calls")
+            }
+        }
+    }
+}
+
+impl Display for Store {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut entries: Vec<_> = self.map.iter().collect();
+        entries.sort_by_key(|(address, _)| **address);
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|(address, value)| format!("{address} ↦ {value}"))
+            .collect();
+        write!(f, "{{{}}}", entries.join(", "))
+    }
+}
+
+impl<'a> Display for VariableEnvironment<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut entries: Vec<_> = self.identifiers_to_addresses.iter().collect();
+        entries.sort_by_key(|(identifier, address)| (**address, **identifier));
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|(identifier, address)| format!("{identifier} ↦ {address}"))
+            .collect();
+        write!(f, "{{{}}}", entries.join(", "))
+    }
+}
+
+impl<'a> Display for ProcedureEnvironment<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut signatures: Vec<_> = self
+            .procedures
+            .keys()
+            .map(|(name, (in_sorts, out_sorts))| {
+                format!("{name}({};{})", join(in_sorts), join(out_sorts))
+            })
+            .collect();
+        signatures.sort();
+        write!(f, "{{{}}}", signatures.join(", "))
+    }
+}
+
+impl<'a> Display for Term<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Term::Command(command, ..) => command.fmt(f),
+            Term::Sequence(first, second) => write!(f, "{first}\n{second}"),
+        }
+    }
+}
+
+impl<'a> Display for Configuration<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.term {
+            Some(term) => write!(f, "⟨{term}, {}⟩", self.store),
+            None => self.store.fmt(f),
+        }
+    }
+}
+
+// one line per event, nested events indented below the command or call causing them
+impl<'a> Display for Trace<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for event in &self.0 {
+            writeln!(f, "{}{event}", "  ".repeat(event.depth()))?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Display for Event<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::Command {
+                command,
+                top,
+                environment,
+                changes,
+                ..
+            } => {
+                // nested commands follow as events of their own
+                let command = command.lines().next().unwrap_or_default();
+                write!(f, "{command} | top {top} | {environment}")?;
+                if !changes.is_empty() {
+                    let changes: Vec<_> = changes.iter().map(Change::to_string).collect();
+                    write!(f, " | {}", changes.join(", "))?;
+                }
+                Ok(())
+            }
+            Event::Call {
+                name,
+                in_addresses,
+                out_addresses,
+                ..
+            } => write!(
+                f,
+                "call {name} in ({}) out ({})",
+                join(in_addresses),
+                join(out_addresses)
+            ),
+            Event::Return {
+                name,
+                in_addresses,
+                out_addresses,
+                ..
+            } => write!(
+                f,
+                "return {name} in ({}) out ({})",
+                join(in_addresses),
+                join(out_addresses)
+            ),
+        }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = |value: &Option<Value>| match value {
+            Some(value) => value.to_string(),
+            None => "?".to_string(),
+        };
+        write!(
+            f,
+            "{}: {} → {}",
+            self.address,
+            value(&self.before),
+            value(&self.after)
+        )
+    }
+}
+
+impl<'a> Display for Unassigned<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} may be read before it is assigned", self.0)
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Location::Global(address) => write!(f, "g{address}"),
+            Location::Local(slot) => write!(f, "l{slot}"),
+            Location::Ref(index) => write!(f, "r{index}"),
+        }
+    }
+}
+
+impl<'a> Display for Instruction<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instruction::Push(value) => write!(f, "push {value}"),
+            Instruction::Load(location, identifier) => write!(f, "load {location} ({identifier})"),
+            Instruction::Store(location) => write!(f, "store {location}"),
+            Instruction::Fresh(location, sort) => write!(f, "fresh {location} {sort}"),
+            Instruction::Add => write!(f, "add"),
+            Instruction::Subtract => write!(f, "sub"),
+            Instruction::Multiply => write!(f, "mul"),
+            Instruction::Divide => write!(f, "div"),
+            Instruction::Remainder => write!(f, "rem"),
+            Instruction::Negate => write!(f, "neg"),
+            Instruction::Equal => write!(f, "eq"),
+            Instruction::NotEqual => write!(f, "ne"),
+            Instruction::Less => write!(f, "lt"),
+            Instruction::LessOrEqual => write!(f, "le"),
+            Instruction::Greater => write!(f, "gt"),
+            Instruction::GreaterOrEqual => write!(f, "ge"),
+            Instruction::Not => write!(f, "not"),
+            Instruction::Jump(to) => write!(f, "jump {to}"),
+            Instruction::JumpIfFalse(to) => write!(f, "jumpf {to}"),
+            Instruction::Call {
+                procedure,
+                top,
+                refs,
+            } => write!(f, "call #{procedure} top {top} ref [{}]", join(refs)),
+            Instruction::Return => write!(f, "ret"),
+        }
+    }
+}
+
+// a listing with the address of every instruction and a label where a procedure starts
+impl<'a> Display for Bytecode<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = self.code.len().to_string().len();
+        writeln!(f, "program:")?;
+        for (address, instruction) in self.code.iter().enumerate() {
+            for (index, entry) in self.procedures.iter().enumerate() {
+                if entry.address == address {
+                    writeln!(f, "#{index} {}:", entry.name)?;
+                }
+            }
+            writeln!(f, "  {address:>width$}  {instruction}")?;
         }
+        Ok(())
     }
 }
 
+fn join<T: Display>(items: &[T]) -> String {
+    items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
 fn indent(s: String) -> String {
     let s: String = s.lines().map(|s| format!("  {s}\n")).collect();
     s.trim_end().into()
 }
+
+fn comparand(expression: &Expression) -> String {
+    match expression.unspanned() {
+        Expression::Equal(..)
+        | Expression::NotEqual(..)
+        | Expression::LessThan(..)
+        | Expression::LessThanOrEqual(..)
+        | Expression::GreaterThan(..)
+        | Expression::GreaterThanOrEqual(..) => format!("({expression})"),
+        _ => format!("{expression}"),
+    }
+}
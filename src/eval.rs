@@ -1,50 +1,300 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, rc::Rc};
 
-use crate::{typecheck::Signature, types::*};
+#[cfg(test)]
+use crate::persistent::baseline::Map;
+#[cfg(not(test))]
+use crate::persistent::Map;
+use crate::{
+    diagnostic::Diagnostic,
+    integer::{Arithmetic, Integer, Unbounded},
+    trace::{Change, Event, Trace},
+    typecheck::{Signature, BOOL_SORT, INT_SORT},
+    types::*,
+};
 
-impl<'a> Program<'a> {
-    pub fn eval(&self, value_sequence: Vec<Value>) -> Result<Vec<Value>, String> {
-        let Program(declarations, _, parameters, body) = self;
-        let (environment, top) = declarations.eval()?;
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError<'a> {
+    DivisionByZero,
+    UnknownIdentifier(Identifier<'a>),
+    UnknownSignature(Signature<'a>),
+    UnannotatedCall(Identifier<'a>),
+    ArityMismatch { expected: usize, found: usize },
+    NotANumeral(Value),
+    // a result the integers the evaluation uses can't represent
+    Overflow(Integer),
+    // a read of a variable that wasn't assigned yet, in a strict store
+    Uninitialized(Identifier<'a>),
+}
 
-        let n = value_sequence.len();
+impl std::error::Error for RuntimeError<'_> {}
 
-        let argument_sequence: Vec<usize> = (top..(top + n)).collect();
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    // commands executed, including every test of a loop condition
+    pub steps: usize,
+    // procedure calls active at the same time
+    pub depth: usize,
+}
 
-        let var = parameters.eval(&argument_sequence, &environment.0)?;
-        let environment = (var, environment.1.clone());
+// why an evaluation was given up, standing in for ⊥ of the denotational semantics
+#[derive(Debug, Clone, PartialEq)]
+pub enum Divergence {
+    OutOfFuel(usize),
+    TooDeep(usize),
+}
 
-        let mut store = Store::init(Value::Numeral(0));
-        for i in 0..n {
-            store = store.update(argument_sequence[i], value_sequence[i].clone())
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Terminated(Vec<Value>),
+    // with the store the evaluation was in when it gave up
+    Diverged(Divergence, Store),
+}
+
+// why a command stopped before it finished
+enum Stop<'a> {
+    Error(Diagnostic<RuntimeError<'a>>),
+    Diverged(Divergence, Store),
+}
+
+impl<'a> From<Diagnostic<RuntimeError<'a>>> for Stop<'a> {
+    fn from(error: Diagnostic<RuntimeError<'a>>) -> Self {
+        Stop::Error(error)
+    }
+}
+
+impl<'a> From<RuntimeError<'a>> for Stop<'a> {
+    fn from(error: RuntimeError<'a>) -> Self {
+        Stop::Error(error.into())
+    }
+}
+
+// what an evaluation keeps track of besides the store: the integers it computes with,
+// the steps taken and calls active so far, checked against the limits if there are any,
+// and the trace if one is recorded
+struct Context<'a, A> {
+    arithmetic: A,
+    limits: Option<Limits>,
+    steps: usize,
+    depth: usize,
+    trace: Option<Trace<'a>>,
+    level: usize,
+}
+
+impl<'a, A: Arithmetic> Context<'a, A> {
+    fn new(arithmetic: A, limits: Option<Limits>, trace: bool) -> Context<'a, A> {
+        Context {
+            arithmetic,
+            limits,
+            steps: 0,
+            depth: 0,
+            trace: trace.then(Trace::default),
+            level: 0,
         }
+    }
 
-        let store = body.eval(&store, &environment, top + n)?;
+    fn step(&mut self, store: &Store) -> Result<(), Stop<'a>> {
+        self.steps += 1;
+        match self.limits {
+            Some(limits) if self.steps > limits.steps => Err(Stop::Diverged(
+                Divergence::OutOfFuel(limits.steps),
+                store.clone(),
+            )),
+            _ => Ok(()),
+        }
+    }
 
-        let mut value_sequence = vec![];
+    fn call(
+        &mut self,
+        store: &Store,
+        name: Identifier<'a>,
+        in_addresses: &[Address],
+        out_addresses: &[Address],
+    ) -> Result<(), Stop<'a>> {
+        self.depth += 1;
+        if let Some(limits) = self.limits {
+            if self.depth > limits.depth {
+                let divergence = Divergence::TooDeep(limits.depth);
+                return Err(Stop::Diverged(divergence, store.clone()));
+            }
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.0.push(Event::Call {
+                name,
+                in_addresses: in_addresses.to_vec(),
+                out_addresses: out_addresses.to_vec(),
+                depth: self.level,
+            });
+            self.level += 1;
+        }
+        Ok(())
+    }
 
-        for i in 0..n {
-            value_sequence.push(store.lookup(&argument_sequence[i]).clone());
+    fn ret(&mut self, name: Identifier<'a>, in_addresses: &[Address], out_addresses: &[Address]) {
+        self.depth -= 1;
+        if let Some(trace) = &mut self.trace {
+            self.level -= 1;
+            trace.0.push(Event::Return {
+                name,
+                in_addresses: in_addresses.to_vec(),
+                out_addresses: out_addresses.to_vec(),
+                depth: self.level,
+            });
         }
+    }
 
-        Ok(value_sequence)
+    // records that `command` starts, returns where its changes go once it has finished
+    fn begin(
+        &mut self,
+        command: &Command<'a>,
+        top: Address,
+        environment: &VariableEnvironment<'a>,
+    ) -> Option<usize> {
+        let trace = self.trace.as_mut()?;
+        trace.0.push(Event::Command {
+            command: command.to_string(),
+            top,
+            environment: environment.clone(),
+            changes: Vec::new(),
+            depth: self.level,
+        });
+        self.level += 1;
+        Some(trace.0.len() - 1)
+    }
+
+    fn end(&mut self, entry: Option<usize>, before: &Store, after: &Store) {
+        let (Some(index), Some(trace)) = (entry, &mut self.trace) else {
+            return;
+        };
+        self.level -= 1;
+        if let Event::Command { changes, .. } = &mut trace.0[index] {
+            *changes = Change::between(before, after);
+        }
     }
 }
 
-impl<'a> Declarations<'a> {
-    fn eval(&self) -> Result<(Environment<'a>, Address), String> {
-        match self {
-            Declarations::Empty => Ok((
-                (VariableEnvironment::init(), ProcedureEnvironment::init()),
-                0,
+impl<'a> Program<'a> {
+    pub fn eval(
+        &self,
+        value_sequence: Vec<Value>,
+    ) -> Result<Vec<Value>, Diagnostic<RuntimeError<'a>>> {
+        self.eval_in(value_sequence, Unbounded, Store::default())
+    }
+
+    // `Program::eval` with the integers of `arithmetic` instead of unbounded ones, the
+    // inputs are represented with them too, starting from `store`, e.g. `Store::strict()`
+    // or one with other defaults
+    pub fn eval_in<A: Arithmetic>(
+        &self,
+        value_sequence: Vec<Value>,
+        arithmetic: A,
+        store: Store,
+    ) -> Result<Vec<Value>, Diagnostic<RuntimeError<'a>>> {
+        let value_sequence = represent(value_sequence, &arithmetic)?;
+        let (store, environment, top, argument_sequence) = self.enter(value_sequence, store)?;
+
+        let mut context = Context::new(arithmetic, None, false);
+        match self.3.exec(&store, &environment, top, &mut context) {
+            Ok(store) => Ok(store.lookup_sequence(&argument_sequence)),
+            Err(Stop::Error(error)) => Err(error),
+            Err(Stop::Diverged(..)) => unreachable!("evaluation without limits doesn't give up"),
+        }
+    }
+
+    // `Program::eval` that records every command and call, the trace is returned even if
+    // the evaluation fails
+    pub fn eval_traced(
+        &self,
+        value_sequence: Vec<Value>,
+    ) -> (Result<Vec<Value>, Diagnostic<RuntimeError<'a>>>, Trace<'a>) {
+        self.eval_traced_in(value_sequence, Unbounded, Store::default())
+    }
+
+    pub fn eval_traced_in<A: Arithmetic>(
+        &self,
+        value_sequence: Vec<Value>,
+        arithmetic: A,
+        store: Store,
+    ) -> (Result<Vec<Value>, Diagnostic<RuntimeError<'a>>>, Trace<'a>) {
+        let entered = represent(value_sequence, &arithmetic)
+            .and_then(|value_sequence| self.enter(value_sequence, store));
+        let (store, environment, top, argument_sequence) = match entered {
+            Ok(entered) => entered,
+            Err(error) => return (Err(error), Trace::default()),
+        };
+
+        let mut context = Context::new(arithmetic, None, true);
+        let result = match self.3.exec(&store, &environment, top, &mut context) {
+            Ok(store) => Ok(store.lookup_sequence(&argument_sequence)),
+            Err(Stop::Error(error)) => Err(error),
+            Err(Stop::Diverged(..)) => unreachable!("evaluation without limits doesn't give up"),
+        };
+        (result, context.trace.unwrap_or_default())
+    }
+
+    // `Program::eval` that gives up once it takes more steps or nests more calls than
+    // `limits` allow
+    pub fn eval_with_limits(
+        &self,
+        value_sequence: Vec<Value>,
+        limits: Limits,
+    ) -> Result<Outcome, Diagnostic<RuntimeError<'a>>> {
+        let (store, environment, top, argument_sequence) =
+            self.enter(value_sequence, Store::default())?;
+
+        match self.3.exec(
+            &store,
+            &environment,
+            top,
+            &mut Context::new(Unbounded, Some(limits), false),
+        ) {
+            Ok(store) => Ok(Outcome::Terminated(
+                store.lookup_sequence(&argument_sequence),
             )),
-            Declarations::Sequence(other, declaration) => {
-                let (environment, top) = other.eval()?;
-                let (environment, top) = declaration.eval(&environment, top)?;
+            Err(Stop::Diverged(divergence, store)) => Ok(Outcome::Diverged(divergence, store)),
+            Err(Stop::Error(error)) => Err(error),
+        }
+    }
 
-                Ok((environment, top))
+    // the store, environment and next free address the body runs in, and the addresses
+    // of the parameters, the store is `store` with the globals allocated and the inputs
+    // written to the parameters
+    pub(crate) fn enter(
+        &self,
+        value_sequence: Vec<Value>,
+        store: Store,
+    ) -> Result<(Store, Environment<'a>, Address, Vec<Address>), Diagnostic<RuntimeError<'a>>> {
+        let Program(declarations, _, parameters, ..) = self;
+        let (environment, mut store, top) = declarations.eval(store)?;
+
+        let n = value_sequence.len();
+        if n != parameters.len() {
+            return Err(RuntimeError::ArityMismatch {
+                expected: parameters.len(),
+                found: n,
             }
+            .into());
         }
+
+        let argument_sequence: Vec<usize> = (top..(top + n)).collect();
+
+        let var = parameters.eval(&argument_sequence, &environment.0)?;
+        let environment = (var, environment.1.clone());
+
+        for (address, value) in argument_sequence.iter().zip(value_sequence) {
+            store = store.update(*address, value)
+        }
+
+        Ok((store, environment, top + n, argument_sequence))
+    }
+}
+
+impl<'a> Declarations<'a> {
+    fn eval(
+        &self,
+        store: Store,
+    ) -> Result<(Environment<'a>, Store, Address), Diagnostic<RuntimeError<'a>>> {
+        let environment = (VariableEnvironment::init(), ProcedureEnvironment::init());
+        Declaration::eval_all(&self.sequence(), &environment, store, 0)
     }
 }
 
@@ -53,54 +303,74 @@ impl<'a> Parameters<'a> {
         &self,
         address_sequence: &[usize],
         environment: &VariableEnvironment<'a>,
-    ) -> Result<VariableEnvironment<'a>, String> {
-        match self {
-            Parameters::Empty => Ok(environment.clone()),
-            Parameters::Sequence(others, variable, _) => {
-                if let Some((address, address_sequence)) = address_sequence.split_last() {
-                    let environment = others.eval(address_sequence, environment)?;
-                    let environment = environment.update(&variable.0, address);
-                    Ok(environment)
-                } else {
-                    Err("address sequence size mismatch".to_string())
-                }
+    ) -> Result<VariableEnvironment<'a>, Diagnostic<RuntimeError<'a>>> {
+        match (self, address_sequence.split_last()) {
+            (Parameters::Empty, None) => Ok(environment.clone()),
+            (Parameters::Sequence(others, variable, _), Some((address, address_sequence))) => {
+                let environment = others.eval(address_sequence, environment)?;
+                let environment = environment.update(&variable.0, address);
+                Ok(environment)
             }
+            (Parameters::Spanned(_, parameters), _) => {
+                parameters.eval(address_sequence, environment)
+            }
+            _ => Err(RuntimeError::ArityMismatch {
+                expected: self.len(),
+                found: address_sequence.len(),
+            }
+            .into()),
         }
     }
 }
 
 impl<'a> Declaration<'a> {
-    fn eval(
+    // a run of consecutive procedures is declared at once so that they can call each
+    // other
+    pub(crate) fn eval_all(
+        declarations: &[&Declaration<'a>],
+        environment: &Environment<'a>,
+        store: Store,
+        top: Address,
+    ) -> Result<(Environment<'a>, Store, Address), Diagnostic<RuntimeError<'a>>> {
+        let (mut environment, mut store, mut top) = (environment.clone(), store, top);
+        let mut index = 0;
+        while index < declarations.len() {
+            let group: Vec<_> = declarations[index..]
+                .iter()
+                .map_while(|declaration| Definition::of(declaration))
+                .collect();
+            if group.is_empty() {
+                (environment, store, top) = declarations[index].eval(&environment, store, top)?;
+                index += 1;
+            } else {
+                index += group.len();
+                let procedures = unfold(&Rc::new(group), &environment);
+                environment = (environment.0, procedures);
+            }
+        }
+        Ok((environment, store, top))
+    }
+
+    pub(crate) fn eval(
         &self,
         environment: &Environment<'a>,
+        store: Store,
         top: Address,
-    ) -> Result<(Environment<'a>, Address), String> {
+    ) -> Result<(Environment<'a>, Store, Address), Diagnostic<RuntimeError<'a>>> {
         match self {
-            Declaration::Variable(identifier, _) => {
+            Declaration::Variable(identifier, sort) => {
                 let environment = (
                     environment.0.update(identifier, &top),
                     environment.1.clone(),
                 );
+                let store = store.allocate(top, *sort);
                 let top = top + 1;
-                Ok((environment, top))
-            }
-            Declaration::Procedure(identifier, in_params, out_params, body) => {
-                let procedure = Procedure {
-                    in_params: in_params.clone(),
-                    out_params: out_params.clone(),
-                    environment: environment.clone(),
-                    body: body.clone(),
-                };
-                let (_, in_sorts) = in_params.typecheck()?;
-                let (_, out_sorts) = out_params.typecheck()?;
-                let environment = (
-                    environment.0.clone(),
-                    environment
-                        .1
-                        .update(&(*identifier, (in_sorts, out_sorts)), &procedure),
-                );
-                Ok((environment, top))
+                Ok((environment, store, top))
             }
+            Declaration::Procedure(..) => Declaration::eval_all(&[self], environment, store, top),
+            Declaration::Spanned(span, declaration) => declaration
+                .eval(environment, store, top)
+                .map_err(|error| error.or_span(*span)),
         }
     }
 }
@@ -109,80 +379,134 @@ impl<'a> Command<'a> {
     pub fn eval(
         &self,
         store: &Store,
-        environment: &Environment,
+        environment: &Environment<'a>,
         top: Address,
-    ) -> Result<Store, String> {
-        match self {
+    ) -> Result<Store, Diagnostic<RuntimeError<'a>>> {
+        match self.exec(
+            store,
+            environment,
+            top,
+            &mut Context::new(Unbounded, None, false),
+        ) {
+            Ok(store) => Ok(store),
+            Err(Stop::Error(error)) => Err(error),
+            Err(Stop::Diverged(..)) => unreachable!("evaluation without limits doesn't give up"),
+        }
+    }
+
+    fn exec<A: Arithmetic>(
+        &self,
+        store: &Store,
+        environment: &Environment<'a>,
+        top: Address,
+        context: &mut Context<'a, A>,
+    ) -> Result<Store, Stop<'a>> {
+        if !matches!(self, Command::Spanned(..)) {
+            context.step(store)?;
+        }
+        let entry = match self {
+            Command::Spanned(..) | Command::Sequence(..) => None,
+            _ => context.begin(self, top, &environment.0),
+        };
+        let result = match self {
             Command::Assign(identifier, expression) => {
                 let address = environment.0.lookup(*identifier)?;
-                Ok(store.update(address, expression.eval(store, environment)?))
+                Ok(store.update(
+                    address,
+                    expression.eval_in(store, environment, &context.arithmetic)?,
+                ))
             }
-            Command::Var(identifier, _, command) => {
+            Command::Var(identifier, sort, command) => {
                 let environment = (
                     environment.0.update(identifier, &top),
                     environment.1.clone(),
                 );
+                let store = store.allocate(top, *sort);
                 let top = top + 1;
-                command.eval(store, &environment, top)
+                command.exec(&store, &environment, top, context)
             }
             Command::Sequence(first, second) => {
-                second.eval(&first.eval(store, environment, top)?, environment, top)
+                let store = first.exec(store, environment, top, context)?;
+                second.exec(&store, environment, top, context)
             }
             Command::IfElse(expression, if_branch, else_branch) => {
-                if expression.eval(store, environment)? == Value::True {
-                    if_branch.eval(store, environment, top)
+                if expression.eval_in(store, environment, &context.arithmetic)? == Value::True {
+                    if_branch.exec(store, environment, top, context)
                 } else {
-                    else_branch.eval(store, environment, top)
+                    else_branch.exec(store, environment, top, context)
                 }
             }
             Command::If(expression, if_branch) => {
-                if expression.eval(store, environment)? == Value::True {
-                    if_branch.eval(store, environment, top)
+                if expression.eval_in(store, environment, &context.arithmetic)? == Value::True {
+                    if_branch.exec(store, environment, top, context)
                 } else {
                     Ok(store.clone())
                 }
             }
-            Command::While(expression, body) => {
-                fn w(
-                    expression: &Expression,
-                    body: &Command,
-                    store: &Store,
-                    environment: &Environment,
-                    top: Address,
-                ) -> Result<Store, String> {
-                    if expression.eval(store, environment)? == Value::True {
-                        let store = body.eval(store, environment, top)?;
-                        w(expression, body, &store, environment, top)
-                    } else {
-                        Ok(store.clone())
-                    }
+            // the least fixpoint of w ↦ (σ ↦ if E(σ) then w(C(σ)) else σ), approximated by
+            // iterating rather than recursing so that long loops don't exhaust the stack
+            Command::While(expression, body, _) => {
+                let mut store = store.clone();
+                while expression.eval_in(&store, environment, &context.arithmetic)? == Value::True {
+                    store = body.exec(&store, environment, top, context)?;
+                    context.step(&store)?;
                 }
-                w(expression, body, store, environment, top)
+                Ok(store)
             }
-            Command::Call(_, expressions, variables, signature) => {
+            Command::Call(identifier, expressions, variables, signature) => {
                 if let Some(signature) = signature {
-                    let vs = expressions.eval(store, environment)?;
-                    let n = vs.len();
-                    let mut store = store.clone();
-                    let mut as1 = vec![];
-                    for i in 0..n {
-                        as1.push(top + i);
-                        store = store.update(top + i, vs[i].clone());
-                    }
+                    let (store, as1, top) =
+                        store_arguments(expressions, store, environment, top, &context.arithmetic)?;
                     let as2 = variables.eval(environment)?;
 
                     let procedure = environment.1.lookup(signature)?;
-                    procedure.call(&as1, &as2, top + n, &store)
+                    context.call(&store, *identifier, &as1, &as2)?;
+                    let store = procedure.call(&as1, &as2, top, &store, context)?;
+                    context.ret(*identifier, &as1, &as2);
+                    Ok(store)
                 } else {
-                    Err("call hasn't been annotated".to_string())
+                    Err(RuntimeError::UnannotatedCall(*identifier).into())
                 }
             }
+            Command::Spanned(span, command) => command
+                .exec(store, environment, top, context)
+                .map_err(|stop| match stop {
+                    Stop::Error(error) => Stop::Error(error.or_span(*span)),
+                    diverged => diverged,
+                }),
+        };
+        if let Ok(after) = &result {
+            context.end(entry, store, after);
         }
+        result
+    }
+}
+
+// stores the values of the in arguments of a call from `top` on, returns the new store,
+// their addresses and the next free address
+pub(crate) fn store_arguments<'a, A: Arithmetic>(
+    expressions: &Expressions<'a>,
+    store: &Store,
+    environment: &Environment<'a>,
+    top: Address,
+    arithmetic: &A,
+) -> Result<(Store, Vec<Address>, Address), Diagnostic<RuntimeError<'a>>> {
+    let vs = expressions.eval_in(store, environment, arithmetic)?;
+    let n = vs.len();
+    let mut store = store.clone();
+    let mut as1 = vec![];
+    for (i, value) in vs.into_iter().enumerate() {
+        as1.push(top + i);
+        store = store.update(top + i, value);
     }
+    Ok((store, as1, top + n))
 }
 
 impl<'a> Variables<'a> {
-    fn eval(&self, environment: &Environment) -> Result<Vec<Address>, String> {
+    pub(crate) fn eval(
+        &self,
+        environment: &Environment,
+    ) -> Result<Vec<Address>, Diagnostic<RuntimeError<'a>>> {
         match self {
             Variables::Empty => Ok(Vec::new()),
             Variables::Sequence(first, rest) => {
@@ -196,30 +520,56 @@ impl<'a> Variables<'a> {
     }
 }
 
-fn extract_numerals(
+// the inputs of a program as represented by `arithmetic`
+fn represent<'a, A: Arithmetic>(
+    value_sequence: Vec<Value>,
+    arithmetic: &A,
+) -> Result<Vec<Value>, Diagnostic<RuntimeError<'a>>> {
+    value_sequence
+        .into_iter()
+        .map(|value| match value {
+            Value::Numeral(numeral) => Ok(Value::Numeral(arithmetic.represent(numeral)?)),
+            value => Ok(value),
+        })
+        .collect()
+}
+
+fn extract_numerals<'a, A: Arithmetic>(
     store: &Store,
     environment: &Environment,
-    left: &Expression,
-    right: &Expression,
-) -> Result<(i32, i32), String> {
-    if let Value::Numeral(left) = left.eval(store, environment)? {
-        if let Value::Numeral(right) = right.eval(store, environment)? {
-            Ok((left, right))
-        } else {
-            Err("right value of the expression is not a number".into())
-        }
-    } else {
-        Err("left value of the expression is not a number".into())
+    left: &Expression<'a>,
+    right: &Expression<'a>,
+    arithmetic: &A,
+) -> Result<(Integer, Integer), Diagnostic<RuntimeError<'a>>> {
+    match (
+        left.eval_in(store, environment, arithmetic)?,
+        right.eval_in(store, environment, arithmetic)?,
+    ) {
+        (Value::Numeral(left), Value::Numeral(right)) => Ok((left, right)),
+        (Value::Numeral(_), value) | (value, _) => Err(RuntimeError::NotANumeral(value).into()),
     }
 }
 
 impl<'a> Expressions<'a> {
-    pub fn eval(&self, store: &Store, environment: &Environment) -> Result<Vec<Value>, String> {
+    pub fn eval(
+        &self,
+        store: &Store,
+        environment: &Environment,
+    ) -> Result<Vec<Value>, Diagnostic<RuntimeError<'a>>> {
+        self.eval_in(store, environment, &Unbounded)
+    }
+
+    pub fn eval_in<A: Arithmetic>(
+        &self,
+        store: &Store,
+        environment: &Environment,
+        arithmetic: &A,
+    ) -> Result<Vec<Value>, Diagnostic<RuntimeError<'a>>> {
         match self {
             Expressions::Empty => Ok(Vec::new()),
             Expressions::Sequence(first, rest) => {
-                let value = first.eval(store, environment)?;
-                let rest = rest.eval(store, environment)?;
+                let value = first.eval_in(store, environment, arithmetic)?;
+                let rest = rest.eval_in(store, environment, arithmetic)?;
                 let mut result = vec![value];
                 result.extend(rest);
                 Ok(result)
@@ -229,58 +579,77 @@ impl<'a> Expressions<'a> {
 }
 
 impl<'a> Expression<'a> {
-    pub fn eval(&self, store: &Store, environment: &Environment) -> Result<Value, String> {
+    pub fn eval(
+        &self,
+        store: &Store,
+        environment: &Environment,
+    ) -> Result<Value, Diagnostic<RuntimeError<'a>>> {
+        self.eval_in(store, environment, &Unbounded)
+    }
+
+    pub fn eval_in<A: Arithmetic>(
+        &self,
+        store: &Store,
+        environment: &Environment,
+        arithmetic: &A,
+    ) -> Result<Value, Diagnostic<RuntimeError<'a>>> {
         match self {
-            Expression::Value(val) => Ok(val.clone()),
-            Expression::Variable(identifier) => {
-                Ok(store.lookup(&environment.0.lookup(*identifier)?).clone())
+            Expression::Value(Value::Numeral(numeral)) => {
+                Ok(Value::Numeral(arithmetic.represent(numeral.clone())?))
             }
+            Expression::Value(val) => Ok(val.clone()),
+            Expression::Variable(identifier) => store
+                .lookup(&environment.0.lookup(*identifier)?)
+                .cloned()
+                .ok_or_else(|| RuntimeError::Uninitialized(*identifier).into()),
             Expression::Sum(left, right) => {
-                let (left, right) = extract_numerals(store, environment, left, right)?;
-                Ok(Value::Numeral(left + right))
+                let (left, right) = extract_numerals(store, environment, left, right, arithmetic)?;
+                Ok(Value::Numeral(arithmetic.sum(left, right)?))
             }
             Expression::Difference(left, right) => {
-                let (left, right) = extract_numerals(store, environment, left, right)?;
-                Ok(Value::Numeral(left - right))
+                let (left, right) = extract_numerals(store, environment, left, right, arithmetic)?;
+                Ok(Value::Numeral(arithmetic.difference(left, right)?))
             }
             Expression::Product(left, right) => {
-                let (left, right) = extract_numerals(store, environment, left, right)?;
-                Ok(Value::Numeral(left * right))
+                let (left, right) = extract_numerals(store, environment, left, right, arithmetic)?;
+                Ok(Value::Numeral(arithmetic.product(left, right)?))
             }
             Expression::Division(left, right) => {
-                let (left, right) = extract_numerals(store, environment, left, right)?;
-                Ok(Value::Numeral(
-                    left.checked_div(right)
-                        .ok_or_else(|| "division by 0".to_string())?,
-                ))
+                let (left, right) = extract_numerals(store, environment, left, right, arithmetic)?;
+                Ok(Value::Numeral(arithmetic.quotient(left, right)?))
             }
-            Expression::Negative(expr) => {
-                if let Value::Numeral(num) = expr.eval(store, environment)? {
-                    Ok(Value::Numeral(-num))
-                } else {
-                    Err("can only negate numbers".into())
-                }
+            Expression::Remainder(left, right) => {
+                let (left, right) = extract_numerals(store, environment, left, right, arithmetic)?;
+                Ok(Value::Numeral(arithmetic.remainder(left, right)?))
             }
-            Expression::Equal(left, right) => {
-                let left = left.eval(store, environment)?;
-                let right = right.eval(store, environment)?;
-                if left == right {
-                    Ok(Value::True)
-                } else {
-                    Ok(Value::False)
-                }
+            Expression::Negative(expr) => match expr.eval_in(store, environment, arithmetic)? {
+                Value::Numeral(num) => Ok(Value::Numeral(arithmetic.negative(num)?)),
+                value => Err(RuntimeError::NotANumeral(value).into()),
+            },
+            Expression::Equal(left, right)
+            | Expression::NotEqual(left, right)
+            | Expression::Equivalent(left, right) => {
+                let left = left.eval_in(store, environment, arithmetic)?;
+                let right = right.eval_in(store, environment, arithmetic)?;
+                let equal = matches!(self, Expression::NotEqual(..)) != (left == right);
+                Ok(if equal { Value::True } else { Value::False })
             }
-            Expression::LessThanOrEqual(left, right) => {
-                let (left, right) = extract_numerals(store, environment, left, right)?;
-                if left <= right {
-                    Ok(Value::True)
-                } else {
-                    Ok(Value::False)
-                }
+            Expression::LessThan(left, right)
+            | Expression::LessThanOrEqual(left, right)
+            | Expression::GreaterThan(left, right)
+            | Expression::GreaterThanOrEqual(left, right) => {
+                let (left, right) = extract_numerals(store, environment, left, right, arithmetic)?;
+                let holds = match self {
+                    Expression::LessThan(..) => left < right,
+                    Expression::LessThanOrEqual(..) => left <= right,
+                    Expression::GreaterThan(..) => left > right,
+                    _ => left >= right,
+                };
+                Ok(if holds { Value::True } else { Value::False })
             }
             Expression::And(left, right) => {
-                if let Value::True = left.eval(store, environment)? {
-                    if let Value::True = right.eval(store, environment)? {
+                if let Value::True = left.eval_in(store, environment, arithmetic)? {
+                    if let Value::True = right.eval_in(store, environment, arithmetic)? {
                         Ok(Value::True)
                     } else {
                         Ok(Value::False)
@@ -290,55 +659,140 @@ impl<'a> Expression<'a> {
                 }
             }
             Expression::Or(left, right) => {
-                if let Value::True = left.eval(store, environment)? {
+                if let Value::True = left.eval_in(store, environment, arithmetic)? {
+                    Ok(Value::True)
+                } else if let Value::True = right.eval_in(store, environment, arithmetic)? {
+                    Ok(Value::True)
+                } else {
+                    Ok(Value::False)
+                }
+            }
+            // like ¬left ∨ right, the right operand isn't evaluated if the left one is false
+            Expression::Implies(left, right) => {
+                if let Value::False = left.eval_in(store, environment, arithmetic)? {
                     Ok(Value::True)
-                } else if let Value::True = right.eval(store, environment)? {
+                } else if let Value::True = right.eval_in(store, environment, arithmetic)? {
                     Ok(Value::True)
                 } else {
                     Ok(Value::False)
                 }
             }
             Expression::Not(expr) => {
-                if let Value::True = expr.eval(store, environment)? {
+                if let Value::True = expr.eval_in(store, environment, arithmetic)? {
                     Ok(Value::False)
                 } else {
                     Ok(Value::True)
                 }
             }
+            Expression::Spanned(span, expr) => expr
+                .eval_in(store, environment, arithmetic)
+                .map_err(|error| error.or_span(*span)),
+        }
+    }
+}
+
+pub type Address = usize;
+
+// the value a fresh variable of each sort starts out with, variables of sorts without
+// one start out unassigned
+#[derive(Debug, Clone, PartialEq)]
+pub struct Defaults {
+    values: HashMap<String, Value>,
+}
+
+impl Defaults {
+    pub fn none() -> Defaults {
+        Defaults {
+            values: HashMap::new(),
         }
     }
+    pub fn with(mut self, sort: Sort, value: Value) -> Defaults {
+        self.values.insert(sort.0 .0.to_string(), value);
+        self
+    }
+    pub fn of(&self, sort: Sort) -> Option<&Value> {
+        self.values.get(sort.0 .0)
+    }
 }
 
-type Address = usize;
+// 0 and false
+impl Default for Defaults {
+    fn default() -> Self {
+        Defaults::none()
+            .with(INT_SORT, 0.into())
+            .with(BOOL_SORT, Value::False)
+    }
+}
 
-#[derive(Debug, Clone)]
+// the addresses that weren't written are unassigned, a fresh variable is written with
+// the default of its sort unless the store is strict
+#[derive(Debug, Clone, PartialEq)]
 pub struct Store {
-    map: HashMap<Address, Value>,
-    default: Value,
+    pub(crate) map: Map<Address, Value>,
+    pub(crate) defaults: Option<Rc<Defaults>>,
 }
 
 impl Store {
     pub fn update(&self, address: Address, value: Value) -> Store {
-        let mut clone = self.clone();
-        clone.map.insert(address, value);
-        clone
+        Store {
+            map: self.map.insert(address, value),
+            defaults: self.defaults.clone(),
+        }
     }
-    pub fn lookup(&self, address: &Address) -> &Value {
-        self.map.get(address).unwrap_or(&self.default)
+    // a fresh variable of `sort` at `address`, regardless of what a previous one left
+    // there
+    pub fn allocate(&self, address: Address, sort: Sort) -> Store {
+        let default = self
+            .defaults
+            .as_ref()
+            .and_then(|defaults| defaults.of(sort));
+        match default {
+            Some(value) => self.update(address, value.clone()),
+            None => Store {
+                map: self.map.remove(&address),
+                defaults: self.defaults.clone(),
+            },
+        }
+    }
+    pub fn lookup(&self, address: &Address) -> Option<&Value> {
+        self.map.get(address)
+    }
+    // the addresses have to have been written if the store is strict
+    pub fn lookup_sequence(&self, address_sequence: &[Address]) -> Vec<Value> {
+        address_sequence
+            .iter()
+            .map(|address| {
+                self.lookup(address)
+                    .expect("the address has been written")
+                    .clone()
+            })
+            .collect()
+    }
+    pub fn init(defaults: Defaults) -> Store {
+        Store {
+            defaults: Some(Rc::new(defaults)),
+            map: Map::new(),
+        }
     }
-    pub fn init(default: Value) -> Store {
+    pub fn strict() -> Store {
         Store {
-            default,
-            map: HashMap::new(),
+            defaults: None,
+            map: Map::new(),
         }
     }
 }
 
-type Environment<'a> = (VariableEnvironment<'a>, ProcedureEnvironment<'a>);
+impl Default for Store {
+    fn default() -> Self {
+        Store::init(Defaults::default())
+    }
+}
+
+pub type Environment<'a> = (VariableEnvironment<'a>, ProcedureEnvironment<'a>);
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct VariableEnvironment<'a> {
-    identifiers_to_addresses: HashMap<Identifier<'a>, Address>,
+    pub(crate) identifiers_to_addresses: Map<Identifier<'a>, Address>,
 }
 
 impl<'a> VariableEnvironment<'a> {
@@ -347,26 +801,26 @@ impl<'a> VariableEnvironment<'a> {
         identifier: &Identifier<'a>,
         address: &Address,
     ) -> VariableEnvironment<'a> {
-        let mut clone = self.clone();
-        clone.identifiers_to_addresses.insert(*identifier, *address);
-        clone
+        VariableEnvironment {
+            identifiers_to_addresses: self.identifiers_to_addresses.insert(*identifier, *address),
+        }
     }
-    pub fn lookup(&self, identifier: Identifier) -> Result<Address, String> {
+    pub fn lookup<'b>(&self, identifier: Identifier<'b>) -> Result<Address, RuntimeError<'b>> {
         self.identifiers_to_addresses
             .get(&identifier)
-            .ok_or(format!("unknown identifier {identifier}"))
+            .ok_or(RuntimeError::UnknownIdentifier(identifier))
             .copied()
     }
     pub fn init() -> VariableEnvironment<'a> {
         VariableEnvironment {
-            identifiers_to_addresses: HashMap::new(),
+            identifiers_to_addresses: Map::new(),
         }
     }
 }
 
 #[derive(Clone)]
 pub struct ProcedureEnvironment<'a> {
-    procedures: HashMap<Signature<'a>, Procedure<'a>>,
+    pub(crate) procedures: Map<Signature<'a>, Procedure<'a>>,
 }
 
 impl<'a> ProcedureEnvironment<'a> {
@@ -375,54 +829,113 @@ impl<'a> ProcedureEnvironment<'a> {
         signature: &Signature<'a>,
         procedure: &Procedure<'a>,
     ) -> ProcedureEnvironment<'a> {
-        let mut clone = self.clone();
-        clone
-            .procedures
-            .insert(signature.clone(), procedure.clone());
-        clone
+        ProcedureEnvironment {
+            procedures: self.procedures.insert(signature.clone(), procedure.clone()),
+        }
     }
-    pub fn lookup(&self, signature: &Signature<'a>) -> Result<Procedure, String> {
+    pub fn lookup(&self, signature: &Signature<'a>) -> Result<Procedure<'a>, RuntimeError<'a>> {
         self.procedures
             .get(signature)
-            .ok_or(format!("unknown signature {signature:?}"))
+            .ok_or_else(|| RuntimeError::UnknownSignature(signature.clone()))
             .cloned()
     }
     pub fn init() -> ProcedureEnvironment<'a> {
         ProcedureEnvironment {
-            procedures: HashMap::new(),
+            procedures: Map::new(),
         }
     }
 }
 
 #[derive(Clone)]
-pub struct Procedure<'a> {
+struct Definition<'a> {
+    signature: Signature<'a>,
     in_params: Parameters<'a>,
     out_params: Parameters<'a>,
-    environment: Environment<'a>,
     body: Command<'a>,
 }
 
+impl<'a> Definition<'a> {
+    fn of(declaration: &Declaration<'a>) -> Option<Definition<'a>> {
+        match declaration.unspanned() {
+            Declaration::Procedure(_, in_params, out_params, body, _) => Some(Definition {
+                signature: declaration.signature()?,
+                in_params: in_params.clone(),
+                out_params: out_params.clone(),
+                body: body.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+// a procedure of a group declared together, closed over the environment the group was
+// declared in
+#[derive(Clone)]
+pub struct Procedure<'a> {
+    group: Rc<Vec<Definition<'a>>>,
+    index: usize,
+    environment: Environment<'a>,
+}
+
+// The procedures of a group are the least fixpoint of
+// π ↦ environment.1[pᵢ ↦ ⟨definitionᵢ, environment.0, π⟩], which is unfolded one step
+// each time one of them is called instead of being built as a cyclic structure.
+fn unfold<'a>(
+    group: &Rc<Vec<Definition<'a>>>,
+    environment: &Environment<'a>,
+) -> ProcedureEnvironment<'a> {
+    let mut procedures = environment.1.clone();
+    for (index, definition) in group.iter().enumerate() {
+        let procedure = Procedure {
+            group: group.clone(),
+            index,
+            environment: environment.clone(),
+        };
+        procedures = procedures.update(&definition.signature, &procedure);
+    }
+    procedures
+}
+
 impl<'a> Procedure<'a> {
-    fn call(
+    fn call<A: Arithmetic>(
         &self,
         address_sequence_in: &[Address],
         address_sequence_out: &[Address],
         top: Address,
         store: &Store,
-    ) -> Result<Store, String> {
-        let var = self
+        context: &mut Context<'a, A>,
+    ) -> Result<Store, Stop<'a>> {
+        let environment = self.environment(address_sequence_in, address_sequence_out)?;
+        self.body().exec(store, &environment, top, context)
+    }
+
+    pub(crate) fn body(&self) -> &Command<'a> {
+        &self.group[self.index].body
+    }
+
+    // the environment the body runs in when called with the given argument addresses
+    pub(crate) fn environment(
+        &self,
+        address_sequence_in: &[Address],
+        address_sequence_out: &[Address],
+    ) -> Result<Environment<'a>, Diagnostic<RuntimeError<'a>>> {
+        let definition = &self.group[self.index];
+        let var = definition
             .in_params
             .eval(address_sequence_in, &self.environment.0)?;
-        let var = self.out_params.eval(address_sequence_out, &var)?;
+        let var = definition.out_params.eval(address_sequence_out, &var)?;
 
-        self.body
-            .eval(store, &(var, self.environment.1.clone()), top)
+        let procedures = unfold(&self.group, &self.environment);
+        Ok((var, procedures))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::gcd::{a_b_gcd_parameter_sequence, generate_gcd};
+    use crate::{
+        gcd::{a_b_gcd_parameter_sequence, generate_gcd},
+        persistent::baseline::copying,
+    };
 
     use super::*;
 
@@ -494,12 +1007,174 @@ mod tests {
     fn test_eval_expression() -> Result<(), String> {
         let exp = Expression::Sum(Expression::Product(2.into(), 3.into()).into(), 4.into());
         assert_eq!(
-            Value::Numeral(10),
+            Value::from(10),
             exp.eval(
-                &Store::init(Value::Numeral(0)),
+                &Store::default(),
                 &(VariableEnvironment::init(), ProcedureEnvironment::init())
             )?
         );
         Ok(())
     }
+
+    #[test]
+    fn test_eval_operators() -> Result<(), String> {
+        let environment = (VariableEnvironment::init(), ProcedureEnvironment::init());
+        let eval = |source| -> Result<Value, String> {
+            Ok(Expression::parse(source)?.eval(&Store::default(), &environment)?)
+        };
+        assert_eq!(eval("-7 % 2")?, Value::from(-1));
+        assert_eq!(eval("7 % -2")?, Value::from(1));
+        assert_eq!(eval("-7 / 2 * 2 + -7 % 2")?, Value::from(-7));
+        assert_eq!(eval("1 < 2 ∧ 2 > 1 ∧ 2 ≥ 2 ∧ 1 ≠ 2")?, Value::True);
+        assert_eq!(eval("2 < 2 ∨ 1 > 2 ∨ 1 ≥ 2 ∨ true ≠ true")?, Value::False);
+        assert_eq!(eval("false ⇒ false")?, Value::True);
+        assert_eq!(eval("true ⇒ false")?, Value::False);
+        assert_eq!(eval("false ⇔ 1 > 2")?, Value::True);
+        // the conclusion isn't evaluated if the premise is false
+        assert_eq!(eval("1 = 0 ⇒ 1/0 = 0")?, Value::True);
+        assert!(eval("true ⇔ 1/0 = 0").is_err());
+        assert!(eval("1 % 0").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_error_span() -> Result<(), String> {
+        let source = "program p(a:Int, b:Int) {\n  a := 1 + a / b;\n}";
+        let program = Program::parse(source)?;
+        let annotated_program = program.typecheck()?;
+        let error = annotated_program
+            .eval(vec![1.into(), 0.into()])
+            .unwrap_err();
+        assert_eq!(error.error, RuntimeError::DivisionByZero);
+        assert_eq!(error.span, Some(Span { start: 37, end: 42 }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_arity_mismatch() -> Result<(), String> {
+        let program = generate_gcd(a_b_gcd_parameter_sequence());
+        let annotated_program = program.typecheck()?;
+        let error = annotated_program
+            .eval(vec![60.into(), 12.into()])
+            .unwrap_err();
+        assert_eq!(
+            error.error,
+            RuntimeError::ArityMismatch {
+                expected: 4,
+                found: 2
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_recursion() -> Result<(), String> {
+        let program = Program::parse(include_str!("../programs/recursion.fsp"))?;
+        let annotated_program = program.typecheck()?;
+
+        let result_sequence = annotated_program.eval(vec![5.into(), 0.into(), Value::False])?;
+        assert_eq!(result_sequence, vec![5.into(), 120.into(), Value::False]);
+        let result_sequence = annotated_program.eval(vec![0.into(), 0.into(), Value::False])?;
+        assert_eq!(result_sequence, vec![0.into(), 1.into(), Value::True]);
+        let result_sequence = annotated_program.eval(vec![8.into(), 0.into(), Value::False])?;
+        assert_eq!(result_sequence, vec![8.into(), 40320.into(), Value::True]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_with_limits() -> Result<(), String> {
+        let limits = Limits {
+            steps: 1000,
+            depth: 50,
+        };
+        let program = generate_gcd(a_b_gcd_parameter_sequence());
+        let annotated_program = program.typecheck()?;
+        let input_sequence = vec![24.into(), 60.into(), 0.into(), 0.into()];
+        assert_eq!(
+            annotated_program.eval_with_limits(input_sequence, limits)?,
+            Outcome::Terminated(vec![24.into(), 60.into(), 12.into(), 2.into()])
+        );
+
+        let source = "program p(a:Int) {\n  while true do a := a+1;\n}";
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        let Outcome::Diverged(divergence, store) =
+            annotated_program.eval_with_limits(vec![0.into()], limits)?
+        else {
+            panic!("while true terminated");
+        };
+        assert_eq!(divergence, Divergence::OutOfFuel(1000));
+        assert_eq!(store.lookup(&0), Some(&Value::from(500)));
+
+        let source = "procedure f(n:Int; ref r:Int) { call f(n+1; r); }
+program p(a:Int) { call f(0; a); }";
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        let Outcome::Diverged(divergence, store) =
+            annotated_program.eval_with_limits(vec![0.into()], limits)?
+        else {
+            panic!("infinite recursion terminated");
+        };
+        assert_eq!(divergence, Divergence::TooDeep(50));
+        // the argument of the call that went too deep
+        assert_eq!(store.lookup(&51), Some(&Value::from(50)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_defaults() -> Result<(), String> {
+        let source = "var g:Bool;
+program p(a:Int, b:Bool, c:Bool) {
+  { var x:Int; x := 5; }
+  { var y:Int; a := y; }
+  var f:Bool;
+  b := f = false;
+  c := g;
+}";
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        let input_sequence = vec![1.into(), Value::False, Value::True];
+        assert_eq!(
+            annotated_program.eval(input_sequence.clone())?,
+            vec![0.into(), Value::True, Value::False]
+        );
+
+        let defaults = Defaults::default().with(INT_SORT, 7.into());
+        let result_sequence =
+            annotated_program.eval_in(input_sequence.clone(), Unbounded, Store::init(defaults))?;
+        assert_eq!(result_sequence, vec![7.into(), Value::True, Value::False]);
+
+        let defaults = Defaults::none().with(INT_SORT, 0.into());
+        let error = annotated_program
+            .eval_in(input_sequence, Unbounded, Store::init(defaults))
+            .unwrap_err();
+        assert_eq!(error.error, RuntimeError::Uninitialized("f".into()));
+        Ok(())
+    }
+
+    // cargo test --release bench_gcd -- --ignored --nocapture
+    // times a long-running gcd with persistent stores and environments, and with ones
+    // that copy a `HashMap` for every change, as they did before
+    #[test]
+    #[ignore]
+    fn bench_gcd() -> Result<(), String> {
+        let program = generate_gcd(a_b_gcd_parameter_sequence());
+        let annotated_program = program.typecheck()?;
+        let input_sequence = vec![100_000.into(), 99_999.into(), 0.into(), 0.into()];
+        let start = std::time::Instant::now();
+        let result_sequence = annotated_program.eval(input_sequence.clone())?;
+        let persistent = start.elapsed();
+        let start = std::time::Instant::now();
+        let copied_sequence = copying(|| annotated_program.eval(input_sequence))?;
+        let copied = start.elapsed();
+        println!("gcd(100000*99999, 100000+99999) in {persistent:?}, {copied:?} copying a HashMap");
+        assert_eq!(result_sequence[2], 1.into());
+        assert_eq!(result_sequence, copied_sequence);
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_long_loop() -> Result<(), String> {
+        let source = "program p(a:Int) {\n  while ¬(a = 0) do a := a-1;\n}";
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        assert_eq!(annotated_program.eval(vec![100000.into()])?, vec![0.into()]);
+        Ok(())
+    }
 }
@@ -66,6 +66,7 @@ pub fn generate_gcd(gcd_parameter_sequence: Parameters<'static>) -> Program<'sta
             Command::Assign("r".into(), Expression::Difference("r".into(), "b".into())).into(),
         )
         .into(),
+        None,
     );
 
     let c = Declaration::Variable("c".into(), "Int".into());
@@ -94,6 +95,7 @@ pub fn generate_gcd(gcd_parameter_sequence: Parameters<'static>) -> Program<'sta
             )
             .into(),
         ),
+        Specification::default(),
     );
 
     let while_body = Command::Var(
@@ -106,8 +108,7 @@ pub fn generate_gcd(gcd_parameter_sequence: Parameters<'static>) -> Program<'sta
                 Expressions::Sequence(
                     "a".into(),
                     Expressions::Sequence("b".into(), Expressions::Empty.into()).into(),
-                )
-                ,
+                ),
                 Variables::Sequence(
                     "c".into(),
                     Variables::Sequence("a".into(), Variables::Empty.into()).into(),
@@ -157,6 +158,7 @@ pub fn generate_gcd(gcd_parameter_sequence: Parameters<'static>) -> Program<'sta
                             .into(),
                     ),
                     while_body.into(),
+                    None,
                 )
                 .into(),
                 Command::Sequence(
@@ -167,6 +169,7 @@ pub fn generate_gcd(gcd_parameter_sequence: Parameters<'static>) -> Program<'sta
             )
             .into(),
         ),
+        Specification::default(),
     );
 
     let declarations = Declarations::Sequence(
@@ -210,7 +213,13 @@ pub fn generate_gcd(gcd_parameter_sequence: Parameters<'static>) -> Program<'sta
         None,
     );
 
-    let program = Program(declarations, "gcd".into(), parameters, main);
+    let program = Program(
+        declarations,
+        "gcd".into(),
+        parameters,
+        main,
+        Specification::default(),
+    );
 
     program
 }
@@ -0,0 +1,784 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    diagnostic::Diagnostics,
+    typecheck::{Signature, TypeError, VariableTypingMap, ERROR_SORT},
+    types::*,
+};
+
+pub type Substitution<'a> = HashMap<Identifier<'a>, Expression<'a>>;
+
+// what a verification condition establishes if it holds
+#[derive(Debug, Clone, PartialEq)]
+pub enum Obligation<'a> {
+    // the precondition implies the weakest precondition of the body for the postcondition
+    Procedure(Identifier<'a>),
+    Program(Identifier<'a>),
+    // the invariant and the loop condition imply the weakest precondition of the loop body
+    // for the invariant
+    Preserved,
+    // the invariant and the negated loop condition imply what has to hold after the loop
+    Exit,
+}
+
+// a formula that has to hold for all values of its free variables
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationCondition<'a> {
+    pub obligation: Obligation<'a>,
+    pub span: Option<Span>,
+    pub formula: Expression<'a>,
+    // the free variables of the formula and their sorts, in the order they occur
+    pub variables: Vec<(Identifier<'a>, Sort<'a>)>,
+}
+
+impl<'a> Program<'a> {
+    // the conditions under which every procedure and the program meet their
+    // specifications, assuming that the procedures they call meet theirs
+    pub fn verification_conditions(
+        &self,
+    ) -> Result<Vec<VerificationCondition<'a>>, Diagnostics<TypeError<'a>>> {
+        let program = self.typecheck()?;
+        let Program(declarations, name, parameters, body, specification) = &program;
+        let mut generator = Generator::new(declarations);
+        for declaration in declarations.sequence() {
+            if let Declaration::Procedure(name, in_params, out_params, body, specification) =
+                declaration.unspanned()
+            {
+                generator.scope(&[in_params, out_params]);
+                generator.span = declaration.span();
+                let obligation = Obligation::Procedure(*name);
+                generator.procedure(obligation, &in_params.identifiers(), body, specification);
+            }
+        }
+        generator.scope(&[parameters]);
+        generator.span = body.span();
+        generator.procedure(Obligation::Program(*name), &[], body, specification);
+        Ok(generator.conditions)
+    }
+}
+
+// what a call can rely on
+struct Contract<'a> {
+    in_params: Vec<Identifier<'a>>,
+    out_params: Vec<Identifier<'a>>,
+    specification: Specification<'a>,
+    // the global variables the procedure may change besides its out parameters
+    modifies: HashSet<Identifier<'a>>,
+}
+
+struct Generator<'a> {
+    contracts: HashMap<Signature<'a>, Contract<'a>>,
+    globals: VariableTypingMap<'a>,
+    // of the variables in scope and of every fresh variable so far
+    sorts: VariableTypingMap<'a>,
+    conditions: Vec<VerificationCondition<'a>>,
+    // of the innermost command that has one
+    span: Option<Span>,
+    // the precondition of the procedure or program whose body is being generated for,
+    // about the values it started with, which holds throughout the body
+    frame: Expression<'a>,
+    fresh: usize,
+}
+
+impl<'a> Generator<'a> {
+    fn new(declarations: &Declarations<'a>) -> Generator<'a> {
+        let declarations = declarations.sequence();
+        let globals: VariableTypingMap = declarations
+            .iter()
+            .filter_map(|declaration| match declaration.unspanned() {
+                Declaration::Variable(identifier, sort) => Some((*identifier, *sort)),
+                _ => None,
+            })
+            .collect();
+
+        let mut contracts = HashMap::new();
+        let mut calls = HashMap::new();
+        for declaration in declarations {
+            let Declaration::Procedure(_, in_params, out_params, body, specification) =
+                declaration.unspanned()
+            else {
+                continue;
+            };
+            let Some(signature) = declaration.signature() else {
+                continue;
+            };
+            let mut locals: HashSet<_> = in_params.identifiers().into_iter().collect();
+            locals.extend(out_params.identifiers());
+            let (mut written, mut called) = (HashSet::new(), Vec::new());
+            writes(body, &locals, &mut written, &mut called);
+            written.retain(|identifier| globals.contains_key(identifier));
+            calls.insert(signature.clone(), called);
+            let contract = Contract {
+                in_params: in_params.identifiers(),
+                out_params: out_params.identifiers(),
+                specification: specification.clone(),
+                modifies: written,
+            };
+            contracts.insert(signature, contract);
+        }
+
+        // a procedure also changes what the procedures it calls change, recursion makes
+        // this a fixpoint
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (signature, called) in &calls {
+                let modifies: Vec<_> = called
+                    .iter()
+                    .filter_map(|callee| contracts.get(callee))
+                    .flat_map(|contract| contract.modifies.iter().copied())
+                    .collect();
+                let contract = contracts.get_mut(signature).unwrap();
+                for identifier in modifies {
+                    changed |= contract.modifies.insert(identifier);
+                }
+            }
+        }
+
+        Generator {
+            contracts,
+            sorts: globals.clone(),
+            globals,
+            conditions: Vec::new(),
+            span: None,
+            frame: Expression::Value(Value::True),
+            fresh: 0,
+        }
+    }
+
+    fn scope(&mut self, parameters: &[&Parameters<'a>]) {
+        self.sorts = self.globals.clone();
+        for parameters in parameters {
+            self.sorts
+                .extend(parameters.identifiers().into_iter().zip(parameters.sorts()));
+        }
+    }
+
+    fn condition(&mut self, obligation: Obligation<'a>, formula: Expression<'a>) {
+        let mut identifiers = Vec::new();
+        free_variables(&formula, &mut identifiers);
+        let variables = identifiers
+            .into_iter()
+            .map(|identifier| (identifier, self.sort(identifier)))
+            .collect();
+        self.conditions.push(VerificationCondition {
+            obligation,
+            span: self.span,
+            formula,
+            variables,
+        });
+    }
+
+    fn sort(&self, identifier: Identifier<'a>) -> Sort<'a> {
+        self.sorts.get(&identifier).copied().unwrap_or(ERROR_SORT)
+    }
+
+    // in parameters are copies of the arguments, so in the postcondition they stand for
+    // the values the procedure was called with even if the body changes them
+    fn procedure(
+        &mut self,
+        obligation: Obligation<'a>,
+        in_params: &[Identifier<'a>],
+        body: &Command<'a>,
+        specification: &Specification<'a>,
+    ) {
+        let (mut written, mut called) = (HashSet::new(), Vec::new());
+        writes(body, &HashSet::new(), &mut written, &mut called);
+        let (mut initial, mut current) = (Substitution::new(), Substitution::new());
+        for parameter in in_params
+            .iter()
+            .filter(|parameter| written.contains(parameter))
+        {
+            let fresh = self.fresh(*parameter, self.sort(*parameter));
+            initial.insert(*parameter, Expression::Variable(fresh));
+            current.insert(fresh, Expression::Variable(*parameter));
+        }
+
+        // loops can rely on the precondition, with names for the initial values of the
+        // variables that the body or the procedures it calls may change
+        let changed: HashSet<_> = (called.iter())
+            .filter_map(|callee| self.contracts.get(callee))
+            .flat_map(|contract| contract.modifies.iter().copied())
+            .chain(written)
+            .collect();
+        let mut frame = initial.clone();
+        let mut variables = Vec::new();
+        free_variables(&clause(&specification.requires), &mut variables);
+        for variable in variables {
+            if changed.contains(&variable) && !frame.contains_key(&variable) {
+                let fresh = self.fresh(variable, self.sort(variable));
+                frame.insert(variable, Expression::Variable(fresh));
+            }
+        }
+        self.frame = clause(&specification.requires).substitute(&frame);
+
+        let span = self.span;
+        let postcondition = clause(&specification.ensures).substitute(&initial);
+        let precondition = self.wp(body, postcondition).substitute(&current);
+        self.span = span;
+        let formula = implies(clause(&specification.requires), precondition);
+        self.condition(obligation, formula);
+    }
+
+    fn wp(&mut self, command: &Command<'a>, postcondition: Expression<'a>) -> Expression<'a> {
+        match command {
+            Command::Assign(identifier, expression) => {
+                let substitution = HashMap::from([(*identifier, expression.clone())]);
+                and(defined(expression), postcondition.substitute(&substitution))
+            }
+            // the local variable is renamed so that it can't be confused with a variable
+            // of the same name in the postcondition
+            Command::Var(identifier, sort, command) => {
+                let fresh = self.fresh(*identifier, *sort);
+                self.wp(&command.rename(*identifier, fresh), postcondition)
+            }
+            Command::Sequence(first, second) => {
+                let postcondition = self.wp(second, postcondition);
+                self.wp(first, postcondition)
+            }
+            Command::IfElse(condition, if_branch, else_branch) => {
+                let if_branch = self.wp(if_branch, postcondition.clone());
+                let else_branch = self.wp(else_branch, postcondition);
+                and(
+                    defined(condition),
+                    and(
+                        implies(condition.clone(), if_branch),
+                        implies(not(condition.clone()), else_branch),
+                    ),
+                )
+            }
+            Command::If(condition, if_branch) => {
+                let if_branch = self.wp(if_branch, postcondition.clone());
+                and(
+                    defined(condition),
+                    and(
+                        implies(condition.clone(), if_branch),
+                        implies(not(condition.clone()), postcondition),
+                    ),
+                )
+            }
+            // the condition has to be defined before every test, a missing invariant is true
+            Command::While(condition, body, invariant) => {
+                let span = self.span;
+                let invariant = and(clause(invariant), defined(condition));
+                let preserved = self.wp(body, invariant.clone());
+                self.span = span;
+                let premise = and(self.frame.clone(), invariant.clone());
+                let formula = implies(and(premise.clone(), condition.clone()), preserved);
+                self.condition(Obligation::Preserved, formula);
+                let formula = implies(and(premise, not(condition.clone())), postcondition);
+                self.condition(Obligation::Exit, formula);
+                invariant
+            }
+            // the precondition has to hold for the arguments, the postcondition can be
+            // assumed for fresh values of the out variables and the changed globals
+            Command::Call(_, expressions, variables, signature) => {
+                let Some(contract) = signature.as_ref().and_then(|s| self.contracts.get(s)) else {
+                    // calls that didn't typecheck
+                    return Expression::Value(Value::False);
+                };
+                let arguments = expressions.sequence();
+                let variables = variables.sequence();
+                let mut before = Substitution::new();
+                let mut after = Substitution::new();
+                let mut havoc = Substitution::new();
+                for (parameter, argument) in contract.in_params.iter().zip(&arguments) {
+                    before.insert(*parameter, argument.clone());
+                    after.insert(*parameter, argument.clone());
+                }
+                let modifies: Vec<_> = contract.modifies.iter().copied().collect();
+                let out_params = contract.out_params.clone();
+                let specification = contract.specification.clone();
+                for (parameter, variable) in out_params.iter().zip(&variables) {
+                    let fresh = Expression::Variable(self.fresh(*variable, self.sort(*variable)));
+                    before.insert(*parameter, Expression::Variable(*variable));
+                    after.insert(*parameter, fresh.clone());
+                    havoc.insert(*variable, fresh);
+                }
+                for global in modifies {
+                    let fresh = Expression::Variable(self.fresh(global, self.globals[&global]));
+                    after.insert(global, fresh.clone());
+                    havoc.insert(global, fresh);
+                }
+                let defined = arguments
+                    .iter()
+                    .fold(Expression::Value(Value::True), |formula, argument| {
+                        and(formula, defined(argument))
+                    });
+                and(
+                    defined,
+                    and(
+                        clause(&specification.requires).substitute(&before),
+                        implies(
+                            clause(&specification.ensures).substitute(&after),
+                            postcondition.substitute(&havoc),
+                        ),
+                    ),
+                )
+            }
+            Command::Spanned(span, command) => {
+                let outer = self.span.replace(*span);
+                let precondition = self.wp(command, postcondition);
+                self.span = outer;
+                precondition
+            }
+        }
+    }
+
+    fn fresh(&mut self, identifier: Identifier<'a>, sort: Sort<'a>) -> Identifier<'a> {
+        self.fresh += 1;
+        let fresh = identifier.fresh(self.fresh);
+        self.sorts.insert(fresh, sort);
+        fresh
+    }
+}
+
+// the variables a command may change, not counting those it declares itself, and the
+// procedures it calls
+fn writes<'a>(
+    command: &Command<'a>,
+    locals: &HashSet<Identifier<'a>>,
+    written: &mut HashSet<Identifier<'a>>,
+    called: &mut Vec<Signature<'a>>,
+) {
+    match command {
+        Command::Assign(identifier, _) => {
+            if !locals.contains(identifier) {
+                written.insert(*identifier);
+            }
+        }
+        Command::Var(identifier, _, command) => {
+            let mut locals = locals.clone();
+            locals.insert(*identifier);
+            writes(command, &locals, written, called);
+        }
+        Command::Sequence(first, second) | Command::IfElse(_, first, second) => {
+            writes(first, locals, written, called);
+            writes(second, locals, written, called);
+        }
+        Command::If(_, command) | Command::While(_, command, _) => {
+            writes(command, locals, written, called)
+        }
+        Command::Call(_, _, variables, signature) => {
+            for variable in variables.sequence() {
+                if !locals.contains(&variable) {
+                    written.insert(variable);
+                }
+            }
+            called.extend(signature.clone());
+        }
+        Command::Spanned(_, command) => writes(command, locals, written, called),
+    }
+}
+
+pub(crate) fn free_variables<'a>(
+    expression: &Expression<'a>,
+    identifiers: &mut Vec<Identifier<'a>>,
+) {
+    match expression {
+        Expression::Value(_) => {}
+        Expression::Variable(identifier) => {
+            if !identifiers.contains(identifier) {
+                identifiers.push(*identifier);
+            }
+        }
+        Expression::Negative(expression)
+        | Expression::Not(expression)
+        | Expression::Spanned(_, expression) => free_variables(expression, identifiers),
+        Expression::Sum(left, right)
+        | Expression::Difference(left, right)
+        | Expression::Product(left, right)
+        | Expression::Division(left, right)
+        | Expression::Remainder(left, right)
+        | Expression::Equal(left, right)
+        | Expression::NotEqual(left, right)
+        | Expression::LessThan(left, right)
+        | Expression::LessThanOrEqual(left, right)
+        | Expression::GreaterThan(left, right)
+        | Expression::GreaterThanOrEqual(left, right)
+        | Expression::And(left, right)
+        | Expression::Or(left, right)
+        | Expression::Implies(left, right)
+        | Expression::Equivalent(left, right) => {
+            free_variables(left, identifiers);
+            free_variables(right, identifiers);
+        }
+    }
+}
+
+fn clause<'a>(clause: &Option<Expression<'a>>) -> Expression<'a> {
+    clause.clone().unwrap_or(Expression::Value(Value::True))
+}
+
+// formulas are built without the conjuncts and premises that are trivially true
+fn and<'a>(left: Expression<'a>, right: Expression<'a>) -> Expression<'a> {
+    match (left.unspanned(), right.unspanned()) {
+        (Expression::Value(Value::True), _) => right,
+        (_, Expression::Value(Value::True)) => left,
+        _ => Expression::And(left.into(), right.into()),
+    }
+}
+
+fn implies<'a>(premise: Expression<'a>, conclusion: Expression<'a>) -> Expression<'a> {
+    match (premise.unspanned(), conclusion.unspanned()) {
+        (Expression::Value(Value::True), _) | (_, Expression::Value(Value::True)) => conclusion,
+        _ => Expression::Or(not(premise).into(), conclusion.into()),
+    }
+}
+
+fn not(expression: Expression) -> Expression {
+    Expression::Not(expression.into())
+}
+
+// an expression can be evaluated if none of its divisors is 0
+fn defined<'a>(expression: &Expression<'a>) -> Expression<'a> {
+    match expression {
+        Expression::Value(_) | Expression::Variable(_) => Expression::Value(Value::True),
+        Expression::Negative(expression)
+        | Expression::Not(expression)
+        | Expression::Spanned(_, expression) => defined(expression),
+        Expression::Division(left, right) | Expression::Remainder(left, right) => {
+            let divisor = Expression::Equal(right.clone(), Box::new(0.into()));
+            and(and(defined(left), defined(right)), not(divisor))
+        }
+        Expression::Sum(left, right)
+        | Expression::Difference(left, right)
+        | Expression::Product(left, right)
+        | Expression::Equal(left, right)
+        | Expression::NotEqual(left, right)
+        | Expression::LessThan(left, right)
+        | Expression::LessThanOrEqual(left, right)
+        | Expression::GreaterThan(left, right)
+        | Expression::GreaterThanOrEqual(left, right)
+        | Expression::And(left, right)
+        | Expression::Or(left, right)
+        | Expression::Implies(left, right)
+        | Expression::Equivalent(left, right) => and(defined(left), defined(right)),
+    }
+}
+
+impl<'a> Expression<'a> {
+    // replaces every variable in the substitution at once
+    pub fn substitute(&self, substitution: &Substitution<'a>) -> Expression<'a> {
+        let binary = |left: &Expression<'a>, right: &Expression<'a>| {
+            (
+                Box::new(left.substitute(substitution)),
+                Box::new(right.substitute(substitution)),
+            )
+        };
+        match self {
+            Expression::Value(_) => self.clone(),
+            Expression::Variable(identifier) => substitution
+                .get(identifier)
+                .cloned()
+                .unwrap_or_else(|| self.clone()),
+            Expression::Sum(left, right) => {
+                let (left, right) = binary(left, right);
+                Expression::Sum(left, right)
+            }
+            Expression::Difference(left, right) => {
+                let (left, right) = binary(left, right);
+                Expression::Difference(left, right)
+            }
+            Expression::Product(left, right) => {
+                let (left, right) = binary(left, right);
+                Expression::Product(left, right)
+            }
+            Expression::Division(left, right) => {
+                let (left, right) = binary(left, right);
+                Expression::Division(left, right)
+            }
+            Expression::Remainder(left, right) => {
+                let (left, right) = binary(left, right);
+                Expression::Remainder(left, right)
+            }
+            Expression::Equal(left, right) => {
+                let (left, right) = binary(left, right);
+                Expression::Equal(left, right)
+            }
+            Expression::NotEqual(left, right) => {
+                let (left, right) = binary(left, right);
+                Expression::NotEqual(left, right)
+            }
+            Expression::LessThan(left, right) => {
+                let (left, right) = binary(left, right);
+                Expression::LessThan(left, right)
+            }
+            Expression::LessThanOrEqual(left, right) => {
+                let (left, right) = binary(left, right);
+                Expression::LessThanOrEqual(left, right)
+            }
+            Expression::GreaterThan(left, right) => {
+                let (left, right) = binary(left, right);
+                Expression::GreaterThan(left, right)
+            }
+            Expression::GreaterThanOrEqual(left, right) => {
+                let (left, right) = binary(left, right);
+                Expression::GreaterThanOrEqual(left, right)
+            }
+            Expression::And(left, right) => {
+                let (left, right) = binary(left, right);
+                Expression::And(left, right)
+            }
+            Expression::Or(left, right) => {
+                let (left, right) = binary(left, right);
+                Expression::Or(left, right)
+            }
+            Expression::Implies(left, right) => {
+                let (left, right) = binary(left, right);
+                Expression::Implies(left, right)
+            }
+            Expression::Equivalent(left, right) => {
+                let (left, right) = binary(left, right);
+                Expression::Equivalent(left, right)
+            }
+            Expression::Negative(expression) => {
+                Expression::Negative(expression.substitute(substitution).into())
+            }
+            Expression::Not(expression) => {
+                Expression::Not(expression.substitute(substitution).into())
+            }
+            Expression::Spanned(span, expression) => {
+                Expression::Spanned(*span, expression.substitute(substitution).into())
+            }
+        }
+    }
+}
+
+impl<'a> Command<'a> {
+    // renames the variable `from` up to where a var declares it again
+    pub fn rename(&self, from: Identifier<'a>, to: Identifier<'a>) -> Command<'a> {
+        let substitution = HashMap::from([(from, Expression::Variable(to))]);
+        let expression = |expression: &Expression<'a>| expression.substitute(&substitution);
+        let command = |command: &Command<'a>| Box::new(command.rename(from, to));
+        let variable = |identifier: &Identifier<'a>| {
+            if *identifier == from {
+                to
+            } else {
+                *identifier
+            }
+        };
+        match self {
+            Command::Assign(identifier, value) => {
+                Command::Assign(variable(identifier), expression(value))
+            }
+            Command::Var(identifier, ..) if *identifier == from => self.clone(),
+            Command::Var(identifier, sort, body) => Command::Var(*identifier, *sort, command(body)),
+            Command::Sequence(first, second) => Command::Sequence(command(first), command(second)),
+            Command::IfElse(condition, if_branch, else_branch) => Command::IfElse(
+                expression(condition),
+                command(if_branch),
+                command(else_branch),
+            ),
+            Command::If(condition, if_branch) => {
+                Command::If(expression(condition), command(if_branch))
+            }
+            Command::While(condition, body, invariant) => Command::While(
+                expression(condition),
+                command(body),
+                invariant.as_ref().map(expression),
+            ),
+            Command::Call(name, expressions, variables, signature) => {
+                let expressions = expressions
+                    .sequence()
+                    .iter()
+                    .rev()
+                    .fold(Expressions::Empty, |rest, argument| {
+                        Expressions::Sequence(expression(argument), rest.into())
+                    });
+                let variables =
+                    variables
+                        .sequence()
+                        .iter()
+                        .rev()
+                        .fold(Variables::Empty, |rest, identifier| {
+                            Variables::Sequence(Variable(variable(identifier)), rest.into())
+                        });
+                Command::Call(*name, expressions, variables, signature.clone())
+            }
+            Command::Spanned(span, body) => Command::Spanned(*span, command(body)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Write,
+        process::{Command as Process, Stdio},
+    };
+
+    use crate::{
+        bounded::Inputs,
+        eval::{ProcedureEnvironment, Store, VariableEnvironment},
+        smt::script,
+        typecheck::BOOL_SORT,
+    };
+
+    use super::*;
+
+    fn formulas(source: &str) -> Result<Vec<String>, String> {
+        let conditions = Program::parse(source)?.verification_conditions()?;
+        Ok(conditions
+            .iter()
+            .map(|condition| condition.to_string())
+            .collect())
+    }
+
+    #[test]
+    fn test_assignment() -> Result<(), String> {
+        let source = "program p(x:Int) requires 0 ≤ x ensures 1 ≤ x {\n  x := x+1;\n}";
+        assert_eq!(
+            formulas(source)?,
+            vec!["program p meets its specification: (¬(0≤x)∨1≤(x+1))"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_loop() -> Result<(), String> {
+        let source = "program p(n:Int, i:Int, s:Int) requires 0 ≤ n ensures s = n {
+  i := 0; s := 0;
+  while ¬(i = n) invariant s = i do { i := i+1; s := s+1; }
+}";
+        assert_eq!(
+            formulas(source)?,
+            vec![
+                "the loop preserves its invariant: (¬(((0≤n∧s=i)∧¬(i=n)))∨(s+1)=(i+1))",
+                "the invariant holds what follows the loop: (¬(((0≤n∧s=i)∧¬(¬(i=n))))∨s=n)",
+                "program p meets its specification: (¬(0≤n)∨0=0)",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_call() -> Result<(), String> {
+        let source = "var g:Int;
+procedure inc(a:Int; ref b:Int) requires 0 ≤ a ensures b = a+1 {
+  a := a+1;
+  b := a;
+  g := b;
+}
+program p(x:Int, y:Int) ensures y = x+1 ∧ g = y {
+  call inc(x; y);
+}";
+        let conditions = Program::parse(source)?.verification_conditions()?;
+        assert_eq!(conditions.len(), 2);
+        assert_eq!(
+            conditions[0].obligation,
+            Obligation::Procedure("inc".into())
+        );
+        assert_eq!(conditions[0].formula.to_string(), "(¬(0≤a)∨(a+1)=(a+1))");
+        // the call only promises what inc ensures, not what it does to g
+        assert_eq!(
+            conditions[1].to_string(),
+            "program p meets its specification: (0≤x∧(¬(y'2=(x+1))∨(y'2=(x+1)∧g'3=y'2)))"
+        );
+        Ok(())
+    }
+
+    // the loop only preserves q ≤ a - r because the precondition says that 1 ≤ b
+    const DIVISION: &str = "procedure div(a:Int, b:Int; ref q:Int, r:Int)
+  requires 0 ≤ a ∧ 1 ≤ b ensures a = q*b+r ∧ 0 ≤ r ∧ r < b ∧ q ≤ a {
+  q := 0;
+  r := a;
+  while b ≤ r invariant a = q*b+r ∧ 0 ≤ r ∧ 0 ≤ q ∧ q ≤ a-r do {
+    q := q+1;
+    r := r-b;
+  }
+}
+program p(x:Int) {
+  x := 0;
+}";
+
+    // whether the condition holds for every value of its variables in -4..=4
+    fn holds_for_small_values(condition: &VerificationCondition) -> bool {
+        let domains: Vec<Vec<Value>> = (condition.variables.iter())
+            .map(|(_, sort)| match *sort == BOOL_SORT {
+                true => vec![Value::False, Value::True],
+                false => (-4..=4).map(Value::from).collect(),
+            })
+            .collect();
+        Inputs::new(&domains).all(|values| {
+            let mut store = Store::default();
+            let mut variable_environment = VariableEnvironment::init();
+            for (address, ((identifier, _), value)) in
+                condition.variables.iter().zip(values).enumerate()
+            {
+                store = store.update(address, value);
+                variable_environment = variable_environment.update(identifier, &address);
+            }
+            let environment = (variable_environment, ProcedureEnvironment::init());
+            condition.formula.eval(&store, &environment) == Ok(Value::True)
+        })
+    }
+
+    #[test]
+    fn test_loop_in_procedure() -> Result<(), String> {
+        let conditions = Program::parse(DIVISION)?.verification_conditions()?;
+        let obligations: Vec<_> = conditions.iter().map(|c| &c.obligation).collect();
+        assert_eq!(
+            obligations,
+            vec![
+                &Obligation::Preserved,
+                &Obligation::Exit,
+                &Obligation::Procedure("div".into()),
+                &Obligation::Program("p".into()),
+            ]
+        );
+        // the precondition is assumed for the loop
+        assert!(conditions[0]
+            .formula
+            .to_string()
+            .starts_with("(¬((((0≤a∧1≤b)∧"));
+        for condition in &conditions {
+            assert!(holds_for_small_values(condition), "{condition}");
+        }
+        Ok(())
+    }
+
+    // cargo test z3 -- --ignored, with z3 on the path
+    #[test]
+    #[ignore]
+    fn test_loop_in_procedure_z3() -> Result<(), String> {
+        let conditions = Program::parse(DIVISION)?.verification_conditions()?;
+        let mut z3 = Process::new("z3")
+            .arg("-in")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|error| error.to_string())?;
+        let mut stdin = z3.stdin.take().expect("stdin is piped");
+        stdin
+            .write_all(script(&conditions).as_bytes())
+            .map_err(|error| error.to_string())?;
+        drop(stdin);
+        let output = z3.wait_with_output().map_err(|error| error.to_string())?;
+        let answers = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(answers.lines().collect::<Vec<_>>(), vec!["unsat"; 4]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_division_is_defined() -> Result<(), String> {
+        let source = "program p(x:Int, y:Int) {\n  y := 1/x;\n}";
+        assert_eq!(
+            formulas(source)?,
+            vec!["program p meets its specification: ¬(x=0)"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_specification_type_error() {
+        let source = "program p(x:Int) requires x ensures x ≤ 1 {\n  x := 1;\n}";
+        let program = Program::parse(source).unwrap();
+        let diagnostics = program.verification_conditions().unwrap_err();
+        assert_eq!(diagnostics.0.len(), 1);
+        assert!(matches!(
+            diagnostics.0[0].error,
+            TypeError::NonBoolCondition(_)
+        ));
+    }
+}
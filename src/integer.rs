@@ -0,0 +1,445 @@
+use std::{
+    cmp::Ordering,
+    ops::{Add, Mul, Neg, Sub},
+    str::FromStr,
+};
+
+use crate::eval::RuntimeError;
+
+// an integer of arbitrary size, its magnitude in base 2^32 with the least significant
+// digit first and without leading zeros, so that 0 has no digits and isn't negative
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Integer {
+    negative: bool,
+    digits: Vec<u32>,
+}
+
+// how the evaluation represents the exact result of a numeral or an arithmetic
+// operation, the default are the mathematical integers of the book
+pub trait Arithmetic {
+    fn represent<'a>(&self, integer: Integer) -> Result<Integer, RuntimeError<'a>>;
+
+    // what `represent` does with integers outside of the i32 range, none if it keeps
+    // them, so that an analysis can follow it
+    fn overflow(&self) -> Option<Overflow>;
+
+    fn sum<'a>(&self, left: Integer, right: Integer) -> Result<Integer, RuntimeError<'a>> {
+        self.represent(left + right)
+    }
+
+    fn difference<'a>(&self, left: Integer, right: Integer) -> Result<Integer, RuntimeError<'a>> {
+        self.represent(left - right)
+    }
+
+    fn product<'a>(&self, left: Integer, right: Integer) -> Result<Integer, RuntimeError<'a>> {
+        self.represent(left * right)
+    }
+
+    // rounds towards 0
+    fn quotient<'a>(&self, left: Integer, right: Integer) -> Result<Integer, RuntimeError<'a>> {
+        let quotient = left
+            .checked_div(&right)
+            .ok_or(RuntimeError::DivisionByZero)?;
+        self.represent(quotient)
+    }
+
+    // has the sign of `left`, so that it is `left - right * quotient`
+    fn remainder<'a>(&self, left: Integer, right: Integer) -> Result<Integer, RuntimeError<'a>> {
+        let remainder = left
+            .checked_rem(&right)
+            .ok_or(RuntimeError::DivisionByZero)?;
+        self.represent(remainder)
+    }
+
+    fn negative<'a>(&self, integer: Integer) -> Result<Integer, RuntimeError<'a>> {
+        self.represent(-integer)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Unbounded;
+
+// 32 bit two's complement integers, results out of range are treated according to the
+// overflow mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct I32(pub Overflow);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overflow {
+    Checked,
+    Wrapping,
+    Saturating,
+}
+
+impl Arithmetic for Unbounded {
+    fn represent<'a>(&self, integer: Integer) -> Result<Integer, RuntimeError<'a>> {
+        Ok(integer)
+    }
+
+    fn overflow(&self) -> Option<Overflow> {
+        None
+    }
+}
+
+impl Arithmetic for I32 {
+    fn represent<'a>(&self, integer: Integer) -> Result<Integer, RuntimeError<'a>> {
+        if integer.to_i32().is_some() {
+            return Ok(integer);
+        }
+        match self.0 {
+            Overflow::Checked => Err(RuntimeError::Overflow(integer)),
+            Overflow::Wrapping => {
+                let low = integer.digits.first().copied().unwrap_or(0);
+                let low = if integer.negative {
+                    low.wrapping_neg()
+                } else {
+                    low
+                };
+                Ok(Integer::from(low as i32))
+            }
+            Overflow::Saturating if integer.negative => Ok(Integer::from(i32::MIN)),
+            Overflow::Saturating => Ok(Integer::from(i32::MAX)),
+        }
+    }
+
+    fn overflow(&self) -> Option<Overflow> {
+        Some(self.0)
+    }
+}
+
+impl Integer {
+    fn new(negative: bool, digits: Vec<u32>) -> Integer {
+        let digits = trim(digits);
+        Integer {
+            negative: negative && !digits.is_empty(),
+            digits,
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn abs(&self) -> Integer {
+        Integer::new(false, self.digits.clone())
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.digits.len() > 2 {
+            return None;
+        }
+        let magnitude = self
+            .digits
+            .iter()
+            .rev()
+            .fold(0i128, |magnitude, digit| magnitude << 32 | *digit as i128);
+        let value = if self.negative { -magnitude } else { magnitude };
+        i64::try_from(value).ok()
+    }
+
+    pub fn to_i32(&self) -> Option<i32> {
+        self.to_i64().and_then(|value| i32::try_from(value).ok())
+    }
+
+    // rounds towards 0 like i32::checked_div, but a quotient out of the i32 range is
+    // still exact
+    pub fn checked_div(&self, divisor: &Integer) -> Option<Integer> {
+        if divisor.is_zero() {
+            return None;
+        }
+        let (quotient, _) = divide(&self.digits, &divisor.digits);
+        Some(Integer::new(self.negative != divisor.negative, quotient))
+    }
+
+    // the remainder of `checked_div`, like i32::checked_rem
+    pub fn checked_rem(&self, divisor: &Integer) -> Option<Integer> {
+        if divisor.is_zero() {
+            return None;
+        }
+        let (_, remainder) = divide(&self.digits, &divisor.digits);
+        Some(Integer::new(self.negative, remainder))
+    }
+
+    // the digits of the decimal representation of the magnitude, most significant first
+    pub(crate) fn decimal(&self) -> String {
+        let mut chunks = Vec::new();
+        let mut digits = self.digits.clone();
+        while !digits.is_empty() {
+            let (quotient, remainder) = divide(&digits, &[1_000_000_000]);
+            chunks.push(remainder.first().copied().unwrap_or(0));
+            digits = quotient;
+        }
+        match chunks.split_last() {
+            None => "0".to_string(),
+            Some((first, rest)) => rest
+                .iter()
+                .rev()
+                .fold(first.to_string(), |text, chunk| format!("{text}{chunk:09}")),
+        }
+    }
+}
+
+fn compare(left: &[u32], right: &[u32]) -> Ordering {
+    left.len()
+        .cmp(&right.len())
+        .then_with(|| left.iter().rev().cmp(right.iter().rev()))
+}
+
+fn add(left: &[u32], right: &[u32]) -> Vec<u32> {
+    let mut digits = Vec::with_capacity(left.len().max(right.len()) + 1);
+    let mut carry = 0;
+    for index in 0..left.len().max(right.len()) {
+        let digit =
+            *left.get(index).unwrap_or(&0) as u64 + *right.get(index).unwrap_or(&0) as u64 + carry;
+        digits.push(digit as u32);
+        carry = digit >> 32;
+    }
+    digits.push(carry as u32);
+    digits
+}
+
+// left has to be at least as large as right
+fn subtract(left: &[u32], right: &[u32]) -> Vec<u32> {
+    let mut digits = Vec::with_capacity(left.len());
+    let mut borrow = 0;
+    for (index, digit) in left.iter().enumerate() {
+        let subtrahend = *right.get(index).unwrap_or(&0) as i64 + borrow;
+        let mut digit = *digit as i64 - subtrahend;
+        borrow = 0;
+        if digit < 0 {
+            digit += 1 << 32;
+            borrow = 1;
+        }
+        digits.push(digit as u32);
+    }
+    digits
+}
+
+fn multiply(left: &[u32], right: &[u32]) -> Vec<u32> {
+    let mut digits = vec![0u32; left.len() + right.len()];
+    for (i, l) in left.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, r) in right.iter().enumerate() {
+            let digit = digits[i + j] as u64 + *l as u64 * *r as u64 + carry;
+            digits[i + j] = digit as u32;
+            carry = digit >> 32;
+        }
+        digits[i + right.len()] = carry as u32;
+    }
+    digits
+}
+
+fn trim(mut digits: Vec<u32>) -> Vec<u32> {
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    digits
+}
+
+// the quotient and remainder of the magnitudes without leading zeros, bit by bit, the
+// divisor isn't 0
+fn divide(dividend: &[u32], divisor: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if let [divisor] = divisor {
+        let mut quotient = vec![0; dividend.len()];
+        let mut remainder = 0u64;
+        for (index, digit) in dividend.iter().enumerate().rev() {
+            let current = remainder << 32 | *digit as u64;
+            quotient[index] = (current / *divisor as u64) as u32;
+            remainder = current % *divisor as u64;
+        }
+        return (trim(quotient), trim(vec![remainder as u32]));
+    }
+    let mut quotient = vec![0; dividend.len()];
+    let mut remainder: Vec<u32> = Vec::new();
+    for bit in (0..dividend.len() * 32).rev() {
+        remainder = add(&remainder, &remainder);
+        remainder[0] |= dividend[bit / 32] >> (bit % 32) & 1;
+        remainder = trim(remainder);
+        if compare(&remainder, divisor) != Ordering::Less {
+            remainder = trim(subtract(&remainder, divisor));
+            quotient[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    (trim(quotient), remainder)
+}
+
+impl From<i64> for Integer {
+    fn from(value: i64) -> Self {
+        let magnitude = value.unsigned_abs();
+        Integer::new(value < 0, vec![magnitude as u32, (magnitude >> 32) as u32])
+    }
+}
+
+impl From<i32> for Integer {
+    fn from(value: i32) -> Self {
+        Integer::from(value as i64)
+    }
+}
+
+impl FromStr for Integer {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (negative, magnitude) = match text.strip_prefix('-') {
+            Some(magnitude) => (true, magnitude),
+            None => (false, text),
+        };
+        if magnitude.is_empty() || !magnitude.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!("invalid numeral {text}"));
+        }
+        let digits = magnitude.bytes().fold(Vec::new(), |digits, digit| {
+            trim(add(&multiply(&digits, &[10]), &[(digit - b'0') as u32]))
+        });
+        Ok(Integer::new(negative, digits))
+    }
+}
+
+impl PartialOrd for Integer {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Integer {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compare(&self.digits, &other.digits),
+            (true, true) => compare(&other.digits, &self.digits),
+        }
+    }
+}
+
+impl Neg for Integer {
+    type Output = Integer;
+
+    fn neg(self) -> Integer {
+        Integer::new(!self.negative, self.digits)
+    }
+}
+
+impl Add for Integer {
+    type Output = Integer;
+
+    fn add(self, other: Integer) -> Integer {
+        if self.negative == other.negative {
+            return Integer::new(self.negative, add(&self.digits, &other.digits));
+        }
+        match compare(&self.digits, &other.digits) {
+            Ordering::Less => Integer::new(other.negative, subtract(&other.digits, &self.digits)),
+            _ => Integer::new(self.negative, subtract(&self.digits, &other.digits)),
+        }
+    }
+}
+
+impl Sub for Integer {
+    type Output = Integer;
+
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn sub(self, other: Integer) -> Integer {
+        self + -other
+    }
+}
+
+impl Mul for Integer {
+    type Output = Integer;
+
+    fn mul(self, other: Integer) -> Integer {
+        let digits = multiply(&self.digits, &other.digits);
+        Integer::new(self.negative != other.negative, digits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn integer(text: &str) -> Integer {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let big = integer("-123456789012345678901234567890");
+        assert_eq!(big.to_string(), "-123456789012345678901234567890");
+        assert_eq!(
+            (big.clone() * big.clone()).to_string(),
+            "15241578753238836750495351562536198787501905199875019052100"
+        );
+        assert_eq!(
+            (big.clone() + integer("123456789012345678901234567891")).to_string(),
+            "1"
+        );
+        assert_eq!(
+            (integer("4294967296") - integer("4294967297")).to_string(),
+            "-1"
+        );
+        assert_eq!(
+            (big.clone() * big.clone()).checked_div(&big),
+            Some(big.clone())
+        );
+        assert_eq!(
+            big.checked_div(&integer("1000000000000")),
+            Some(integer("-123456789012345678"))
+        );
+        assert_eq!(
+            integer("-7").checked_div(&integer("2")),
+            Some(integer("-3"))
+        );
+        assert_eq!(integer("7").checked_div(&integer("0")), None);
+        assert_eq!(
+            integer("-7").checked_rem(&integer("2")),
+            Some(integer("-1"))
+        );
+        assert_eq!(integer("7").checked_rem(&integer("-2")), Some(integer("1")));
+        assert_eq!(big.checked_rem(&big), Some(integer("0")));
+        assert_eq!(integer("7").checked_rem(&integer("0")), None);
+        assert_eq!(integer("0").to_string(), "0");
+        assert_eq!(integer("-0"), Integer::from(0));
+        assert!("1-2".parse::<Integer>().is_err());
+    }
+
+    #[test]
+    fn test_conversions() {
+        for value in [0, 1, -1, i32::MAX, i32::MIN] {
+            assert_eq!(Integer::from(value).to_i32(), Some(value));
+        }
+        for value in [i64::MAX, i64::MIN, 1 << 32] {
+            assert_eq!(Integer::from(value).to_i64(), Some(value));
+        }
+        assert_eq!(Integer::from(i64::MIN).to_i32(), None);
+        assert_eq!(integer("9223372036854775808").to_i64(), None);
+        assert!(integer("-5") < integer("3"));
+        assert!(integer("-5") < integer("-3"));
+        assert!(integer("4294967296") > integer("4294967295"));
+    }
+
+    #[test]
+    fn test_i32() {
+        let sum = Integer::from(i32::MAX) + Integer::from(1);
+        assert_eq!(
+            I32(Overflow::Checked).represent(sum.clone()),
+            Err(RuntimeError::Overflow(sum.clone()))
+        );
+        assert_eq!(
+            I32(Overflow::Wrapping).represent(sum.clone()),
+            Ok(Integer::from(i32::MIN))
+        );
+        assert_eq!(
+            I32(Overflow::Saturating).represent(-(sum.clone() * sum.clone())),
+            Ok(Integer::from(i32::MIN))
+        );
+        let quotient = I32(Overflow::Wrapping).quotient(i32::MIN.into(), (-1).into());
+        assert_eq!(quotient, Ok(Integer::from(i32::MIN)));
+        assert_eq!(
+            I32(Overflow::Wrapping).product(integer("65536"), integer("-65537")),
+            Ok(Integer::from(65536i32.wrapping_mul(-65537)))
+        );
+        assert_eq!(Unbounded.represent(sum.clone()), Ok(sum));
+    }
+}
@@ -1,23 +1,30 @@
-use gcd::*;
-
-use crate::gcd::generate_gcd;
+use std::process::ExitCode;
 
+pub mod absint;
+pub mod assignment;
+pub mod bounded;
+pub mod bytecode;
+pub mod cfg;
+pub mod cli;
+pub mod dataflow;
+pub mod diagnostic;
 pub mod display;
 pub mod eval;
 pub mod gcd;
+pub mod hoare;
+pub mod integer;
+pub mod optimize;
+pub mod parser;
+pub mod persistent;
+pub mod pretty;
+pub mod repl;
+pub mod smallstep;
+pub mod smt;
+pub mod trace;
 pub mod typecheck;
 pub mod types;
 
-fn main() {
-    let program = generate_gcd(a_b_gcd_parameter_sequence());
-    match program.typecheck() {
-        Ok(annotated_program) => {
-            let value_sequence = vec![60.into(), 24.into(), 0.into(), 0.into()]; // gcd(60,24) = 12
-            match annotated_program.eval(value_sequence.clone()) {
-                Ok(result) => println!("result: {result:?} for inputs {value_sequence:?} to program:\n{program}"),
-                Err(reason) => println!("exec failed: {reason} for program:\n{program}"),
-            }
-        }
-        Err(reason) => println!("typecheck failed: {reason} for program:\n{program}"),
-    }
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    cli::main(&args)
 }
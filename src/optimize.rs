@@ -0,0 +1,870 @@
+use std::collections::HashSet;
+
+use crate::{
+    cfg::{Cfg, Point, Statement},
+    dataflow::{summaries, Liveness, Results},
+    eval::{ProcedureEnvironment, Store, VariableEnvironment},
+    hoare::{free_variables, Substitution},
+    integer::{Overflow, I32},
+    types::*,
+};
+
+// the passes `Program::optimize` runs, all of them by default
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Passes {
+    // operations on values computed at compile time, and ∧, ∨ and ⇒ with a value on the
+    // left
+    pub fold: bool,
+    // variables replaced by the value they were last assigned on every path
+    pub propagate: bool,
+    // ifs whose condition is a value replaced by the branch taken
+    pub branches: bool,
+    // assignments to variables that aren't live and vars whose variable is never used
+    pub dead_code: bool,
+}
+
+impl Passes {
+    pub const NONE: Passes = Passes {
+        fold: false,
+        propagate: false,
+        branches: false,
+        dead_code: false,
+    };
+}
+
+impl Default for Passes {
+    fn default() -> Self {
+        Passes {
+            fold: true,
+            propagate: true,
+            branches: true,
+            dead_code: true,
+        }
+    }
+}
+
+impl<'a> Program<'a> {
+    // the program has to have been typechecked, the passes run in turn until none of them
+    // changes it; the result gives what `Program::eval` gives for every input, but can
+    // succeed where the original reads an unassigned variable with `--strict` or
+    // overflows with `--i32 checked`, in code that is removed
+    pub fn optimize(&self, passes: &Passes) -> Program<'a> {
+        let mut program = self.clone();
+        loop {
+            let mut next = program.clone();
+            if passes.fold {
+                next = next.map_bodies(|_, body| map_expressions(body, &fold));
+            }
+            if passes.propagate {
+                let globals = next.0.sequence().into_iter();
+                let globals: HashSet<_> = globals
+                    .filter_map(|declaration| match declaration.unspanned() {
+                        Declaration::Variable(identifier, _) => Some(*identifier),
+                        _ => None,
+                    })
+                    .collect();
+                let cfgs = next.cfgs();
+                next = next.map_bodies(|graph, body| {
+                    let scope = Scope {
+                        globals: &globals,
+                        aliased: aliased(&cfgs[graph]),
+                    };
+                    propagate(body, &mut Substitution::new(), &scope)
+                });
+            }
+            if passes.branches {
+                next = next
+                    .map_bodies(|_, body| remove(body, &mut branch).unwrap_or_else(|| last(body)));
+            }
+            if passes.dead_code {
+                next = next.eliminate_dead_code();
+            }
+            if next == program {
+                return program;
+            }
+            program = next;
+        }
+    }
+
+    // the program with every procedure body and then the program body replaced by `f`
+    // of it and its index among them
+    fn map_bodies(&self, mut f: impl FnMut(usize, &Command<'a>) -> Command<'a>) -> Program<'a> {
+        let Program(declarations, name, parameters, body, specification) = self;
+        let mut index = 0;
+        let declarations = map_declarations(declarations, &mut index, &mut f);
+        let body = f(index, body);
+        Program(
+            declarations,
+            *name,
+            parameters.clone(),
+            body,
+            specification.clone(),
+        )
+    }
+
+    // the liveness of the variables is found on the control-flow graphs, whose points
+    // are the commands in the order `commands` visits them
+    fn eliminate_dead_code(&self) -> Program<'a> {
+        let cfgs = self.cfgs();
+        let summaries = summaries(&cfgs);
+        self.map_bodies(|graph, body| {
+            let cfg = &cfgs[graph];
+            let results = Results::new(cfg, Liveness::new(cfg, &summaries));
+            let aliased = aliased(cfg);
+            let mut commands = Vec::new();
+            self::commands(body, &mut commands);
+            let mut dead = HashSet::new();
+            for (command, point) in commands.into_iter().zip(&cfg.points) {
+                let Point { block, index } = *point;
+                if !matches!(command, Command::Assign(..)) {
+                    continue;
+                }
+                // with the name the graph gives the variable
+                let (Statement::Assign(variable, expression), _) =
+                    &cfg.blocks[block].statements[index]
+                else {
+                    unreachable!("an assignment is lowered to one");
+                };
+                let live = results.at(Point {
+                    block,
+                    index: index + 1,
+                });
+                // the value can still be read through a variable that shares its address
+                let read = match aliased.contains(variable) {
+                    true => aliased.iter().any(|variable| live.contains(variable)),
+                    false => live.contains(variable),
+                };
+                if !read && !can_fail(expression) {
+                    dead.insert(command as *const Command);
+                }
+            }
+            let mut step = |command| dead_code(command, &dead);
+            remove(body, &mut step).unwrap_or_else(|| last(body))
+        })
+    }
+}
+
+fn map_declarations<'a>(
+    declarations: &Declarations<'a>,
+    index: &mut usize,
+    f: &mut impl FnMut(usize, &Command<'a>) -> Command<'a>,
+) -> Declarations<'a> {
+    match declarations {
+        Declarations::Empty => Declarations::Empty,
+        Declarations::Sequence(rest, declaration) => {
+            let rest = map_declarations(rest, index, f);
+            Declarations::Sequence(rest.into(), map_declaration(declaration, index, f))
+        }
+    }
+}
+
+fn map_declaration<'a>(
+    declaration: &Declaration<'a>,
+    index: &mut usize,
+    f: &mut impl FnMut(usize, &Command<'a>) -> Command<'a>,
+) -> Declaration<'a> {
+    match declaration {
+        Declaration::Variable(..) => declaration.clone(),
+        Declaration::Procedure(name, in_params, out_params, body, specification) => {
+            let body = f(*index, body);
+            *index += 1;
+            Declaration::Procedure(
+                *name,
+                in_params.clone(),
+                out_params.clone(),
+                body,
+                specification.clone(),
+            )
+        }
+        Declaration::Spanned(span, declaration) => {
+            Declaration::Spanned(*span, map_declaration(declaration, index, f).into())
+        }
+    }
+}
+
+// the commands other than sequences in the order they were written, a command before
+// its parts, like `Program::cfgs` records their points
+fn commands<'c, 'a>(command: &'c Command<'a>, commands: &mut Vec<&'c Command<'a>>) {
+    match command {
+        Command::Sequence(first, second) => {
+            self::commands(first, commands);
+            self::commands(second, commands);
+        }
+        Command::Spanned(_, command) => self::commands(command, commands),
+        _ => {
+            commands.push(command);
+            match command {
+                Command::Var(_, _, body) | Command::If(_, body) | Command::While(_, body, _) => {
+                    self::commands(body, commands)
+                }
+                Command::IfElse(_, if_branch, else_branch) => {
+                    self::commands(if_branch, commands);
+                    self::commands(else_branch, commands);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn operands<'e, 'a>(expression: &'e Expression<'a>) -> Vec<&'e Expression<'a>> {
+    match expression {
+        Expression::Value(_) | Expression::Variable(_) => Vec::new(),
+        Expression::Negative(operand)
+        | Expression::Not(operand)
+        | Expression::Spanned(_, operand) => vec![operand],
+        Expression::Sum(left, right)
+        | Expression::Difference(left, right)
+        | Expression::Product(left, right)
+        | Expression::Division(left, right)
+        | Expression::Remainder(left, right)
+        | Expression::Equal(left, right)
+        | Expression::NotEqual(left, right)
+        | Expression::LessThan(left, right)
+        | Expression::LessThanOrEqual(left, right)
+        | Expression::GreaterThan(left, right)
+        | Expression::GreaterThanOrEqual(left, right)
+        | Expression::And(left, right)
+        | Expression::Or(left, right)
+        | Expression::Implies(left, right)
+        | Expression::Equivalent(left, right) => vec![left, right],
+    }
+}
+
+// the expression with `f` of each operand in its place
+fn map_operands<'a>(
+    expression: &Expression<'a>,
+    f: &impl Fn(&Expression<'a>) -> Expression<'a>,
+) -> Expression<'a> {
+    let unary = |operand: &Expression<'a>| Box::new(f(operand));
+    let binary =
+        |left: &Expression<'a>, right: &Expression<'a>| (Box::new(f(left)), Box::new(f(right)));
+    match expression {
+        Expression::Value(_) | Expression::Variable(_) => expression.clone(),
+        Expression::Negative(operand) => Expression::Negative(unary(operand)),
+        Expression::Not(operand) => Expression::Not(unary(operand)),
+        Expression::Spanned(span, operand) => Expression::Spanned(*span, unary(operand)),
+        Expression::Sum(left, right) => {
+            let (left, right) = binary(left, right);
+            Expression::Sum(left, right)
+        }
+        Expression::Difference(left, right) => {
+            let (left, right) = binary(left, right);
+            Expression::Difference(left, right)
+        }
+        Expression::Product(left, right) => {
+            let (left, right) = binary(left, right);
+            Expression::Product(left, right)
+        }
+        Expression::Division(left, right) => {
+            let (left, right) = binary(left, right);
+            Expression::Division(left, right)
+        }
+        Expression::Remainder(left, right) => {
+            let (left, right) = binary(left, right);
+            Expression::Remainder(left, right)
+        }
+        Expression::Equal(left, right) => {
+            let (left, right) = binary(left, right);
+            Expression::Equal(left, right)
+        }
+        Expression::NotEqual(left, right) => {
+            let (left, right) = binary(left, right);
+            Expression::NotEqual(left, right)
+        }
+        Expression::LessThan(left, right) => {
+            let (left, right) = binary(left, right);
+            Expression::LessThan(left, right)
+        }
+        Expression::LessThanOrEqual(left, right) => {
+            let (left, right) = binary(left, right);
+            Expression::LessThanOrEqual(left, right)
+        }
+        Expression::GreaterThan(left, right) => {
+            let (left, right) = binary(left, right);
+            Expression::GreaterThan(left, right)
+        }
+        Expression::GreaterThanOrEqual(left, right) => {
+            let (left, right) = binary(left, right);
+            Expression::GreaterThanOrEqual(left, right)
+        }
+        Expression::And(left, right) => {
+            let (left, right) = binary(left, right);
+            Expression::And(left, right)
+        }
+        Expression::Or(left, right) => {
+            let (left, right) = binary(left, right);
+            Expression::Or(left, right)
+        }
+        Expression::Implies(left, right) => {
+            let (left, right) = binary(left, right);
+            Expression::Implies(left, right)
+        }
+        Expression::Equivalent(left, right) => {
+            let (left, right) = binary(left, right);
+            Expression::Equivalent(left, right)
+        }
+    }
+}
+
+fn value<'e>(expression: &'e Expression) -> Option<&'e Value> {
+    match expression.unspanned() {
+        Expression::Value(value) => Some(value),
+        _ => None,
+    }
+}
+
+// an operation is only computed at compile time if it gives a value in 32 bits, so that
+// the result is the same with every `Arithmetic`, and one that fails is left to fail at
+// runtime
+fn fold<'a>(expression: &Expression<'a>) -> Expression<'a> {
+    let expression = map_operands(expression, &fold);
+    // the right operand of ∧, ∨ and ⇒ isn't evaluated if the left one decides the value,
+    // otherwise it is the value
+    if let Expression::And(left, right)
+    | Expression::Or(left, right)
+    | Expression::Implies(left, right) = &expression
+    {
+        match (&expression, value(left)) {
+            (_, None) => {}
+            (Expression::And(..), Some(Value::False)) => return Expression::Value(Value::False),
+            (Expression::Or(..), Some(Value::True))
+            | (Expression::Implies(..), Some(Value::False)) => {
+                return Expression::Value(Value::True)
+            }
+            _ => return (**right).clone(),
+        }
+    }
+    let operands = operands(&expression);
+    if matches!(expression, Expression::Spanned(..))
+        || operands.is_empty()
+        || !operands.iter().all(|operand| value(operand).is_some())
+    {
+        return expression;
+    }
+    let environment = (VariableEnvironment::init(), ProcedureEnvironment::init());
+    let arithmetic = I32(Overflow::Checked);
+    match expression.eval_in(&Store::default(), &environment, &arithmetic) {
+        Ok(value) => Expression::Value(value),
+        Err(_) => expression,
+    }
+}
+
+// whether evaluating the expression can fail with unbounded integers, only a division
+// by something else than a non-zero value can
+fn can_fail(expression: &Expression) -> bool {
+    let divisor = match expression {
+        Expression::Division(_, divisor) | Expression::Remainder(_, divisor) => Some(divisor),
+        _ => None,
+    };
+    let divides_safely = match divisor.map(|divisor| value(divisor)) {
+        None => true,
+        Some(Some(Value::Numeral(numeral))) => !numeral.is_zero(),
+        Some(_) => false,
+    };
+    !divides_safely || operands(expression).into_iter().any(can_fail)
+}
+
+fn map_expressions<'a>(
+    command: &Command<'a>,
+    f: &impl Fn(&Expression<'a>) -> Expression<'a>,
+) -> Command<'a> {
+    let map = |command: &Command<'a>| Box::new(map_expressions(command, f));
+    match command {
+        Command::Assign(identifier, expression) => Command::Assign(*identifier, f(expression)),
+        Command::Var(identifier, sort, body) => Command::Var(*identifier, *sort, map(body)),
+        Command::Sequence(first, second) => Command::Sequence(map(first), map(second)),
+        Command::IfElse(condition, if_branch, else_branch) => {
+            Command::IfElse(f(condition), map(if_branch), map(else_branch))
+        }
+        Command::If(condition, if_branch) => Command::If(f(condition), map(if_branch)),
+        Command::While(condition, body, invariant) => {
+            Command::While(f(condition), map(body), invariant.clone())
+        }
+        Command::Call(identifier, expressions, variables, signature) => {
+            let expressions = expressions
+                .sequence()
+                .iter()
+                .rev()
+                .fold(Expressions::Empty, |rest, expression| {
+                    Expressions::Sequence(f(expression), rest.into())
+                });
+            Command::Call(
+                *identifier,
+                expressions,
+                variables.clone(),
+                signature.clone(),
+            )
+        }
+        Command::Spanned(span, command) => Command::Spanned(*span, map(command)),
+    }
+}
+
+// the variables of a procedure that may share their address with another one: a ref
+// parameter can be passed a global or the same variable as another ref parameter
+fn aliased<'a>(cfg: &Cfg<'a>) -> HashSet<Identifier<'a>> {
+    match cfg.signature {
+        Some(_) => cfg.out_params.iter().chain(&cfg.globals).copied().collect(),
+        None => HashSet::new(),
+    }
+}
+
+// what `propagate` needs to know about the variables in scope
+struct Scope<'v, 'a> {
+    globals: &'v HashSet<Identifier<'a>>,
+    // those in scope that may share their address, a write to one can change the others
+    aliased: HashSet<Identifier<'a>>,
+}
+
+// the variables known to hold a value are replaced by it, `constants` maps them to the
+// value as an expression; a call can change its ref arguments, any global and so any
+// variable that may share its address with one
+fn propagate<'a>(
+    command: &Command<'a>,
+    constants: &mut Substitution<'a>,
+    scope: &Scope<'_, 'a>,
+) -> Command<'a> {
+    let substitute = |expression: &Expression<'a>, constants: &Substitution<'a>| {
+        if constants.is_empty() {
+            expression.clone()
+        } else {
+            expression.substitute(constants)
+        }
+    };
+    match command {
+        Command::Assign(identifier, expression) => {
+            let expression = substitute(expression, constants);
+            if scope.aliased.contains(identifier) {
+                constants.retain(|variable, _| !scope.aliased.contains(variable));
+            }
+            match value(&expression) {
+                Some(value) => constants.insert(*identifier, Expression::Value(value.clone())),
+                None => constants.remove(identifier),
+            };
+            Command::Assign(*identifier, expression)
+        }
+        // the variable hides the one of the same name until the end of its scope
+        Command::Var(identifier, sort, body) => {
+            let hidden = constants.remove(identifier);
+            let body = if scope.aliased.contains(identifier) {
+                let mut aliased = scope.aliased.clone();
+                aliased.remove(identifier);
+                let scope = Scope { aliased, ..*scope };
+                propagate(body, constants, &scope)
+            } else {
+                propagate(body, constants, scope)
+            };
+            constants.remove(identifier);
+            constants.extend(hidden.map(|value| (*identifier, value)));
+            Command::Var(*identifier, *sort, body.into())
+        }
+        Command::Sequence(first, second) => {
+            let first = propagate(first, constants, scope);
+            let second = propagate(second, constants, scope);
+            Command::Sequence(first.into(), second.into())
+        }
+        Command::IfElse(condition, if_branch, else_branch) => {
+            let condition = substitute(condition, constants);
+            let mut other = constants.clone();
+            let if_branch = propagate(if_branch, constants, scope);
+            let else_branch = propagate(else_branch, &mut other, scope);
+            constants.retain(|identifier, value| other.get(identifier) == Some(value));
+            Command::IfElse(condition, if_branch.into(), else_branch.into())
+        }
+        Command::If(condition, if_branch) => {
+            let condition = substitute(condition, constants);
+            let mut taken = constants.clone();
+            let if_branch = propagate(if_branch, &mut taken, scope);
+            constants.retain(|identifier, value| taken.get(identifier) == Some(value));
+            Command::If(condition, if_branch.into())
+        }
+        // only the variables the body doesn't write keep their value around the loop
+        Command::While(condition, body, invariant) => {
+            let (mut written, mut calls) = (HashSet::new(), false);
+            writes(body, &mut written, &mut calls);
+            if calls {
+                written.extend(scope.globals);
+            }
+            if written
+                .iter()
+                .any(|variable| scope.aliased.contains(variable))
+            {
+                written.extend(&scope.aliased);
+            }
+            constants.retain(|identifier, _| !written.contains(identifier));
+            let condition = substitute(condition, constants);
+            let body = propagate(body, &mut constants.clone(), scope);
+            Command::While(condition, body.into(), invariant.clone())
+        }
+        Command::Call(identifier, expressions, variables, signature) => {
+            let expressions =
+                expressions
+                    .sequence()
+                    .iter()
+                    .rev()
+                    .fold(Expressions::Empty, |rest, expression| {
+                        Expressions::Sequence(substitute(expression, constants), rest.into())
+                    });
+            let arguments = variables.sequence();
+            constants.retain(|identifier, _| {
+                !arguments.contains(identifier)
+                    && !scope.globals.contains(identifier)
+                    && !scope.aliased.contains(identifier)
+            });
+            Command::Call(
+                *identifier,
+                expressions,
+                variables.clone(),
+                signature.clone(),
+            )
+        }
+        Command::Spanned(span, command) => {
+            Command::Spanned(*span, propagate(command, constants, scope).into())
+        }
+    }
+}
+
+// the variables the command assigns or passes as ref arguments, and whether it calls a
+// procedure
+fn writes<'a>(command: &Command<'a>, written: &mut HashSet<Identifier<'a>>, calls: &mut bool) {
+    match command {
+        Command::Assign(identifier, _) => {
+            written.insert(*identifier);
+        }
+        Command::Var(_, _, command)
+        | Command::If(_, command)
+        | Command::While(_, command, _)
+        | Command::Spanned(_, command) => writes(command, written, calls),
+        Command::Sequence(first, second) | Command::IfElse(_, first, second) => {
+            writes(first, written, calls);
+            writes(second, written, calls);
+        }
+        Command::Call(_, _, variables, _) => {
+            written.extend(variables.sequence());
+            *calls = true;
+        }
+    }
+}
+
+// whether the identifier occurs anywhere in the command
+fn mentions(command: &Command, identifier: Identifier) -> bool {
+    let occurs = |expression: &Expression| {
+        let mut variables = Vec::new();
+        free_variables(expression, &mut variables);
+        variables.contains(&identifier)
+    };
+    match command {
+        Command::Assign(variable, expression) => *variable == identifier || occurs(expression),
+        Command::Var(variable, _, body) => *variable == identifier || mentions(body, identifier),
+        Command::Sequence(first, second) => {
+            mentions(first, identifier) || mentions(second, identifier)
+        }
+        Command::IfElse(condition, if_branch, else_branch) => {
+            occurs(condition)
+                || mentions(if_branch, identifier)
+                || mentions(else_branch, identifier)
+        }
+        Command::If(condition, body) | Command::While(condition, body, _) => {
+            occurs(condition) || mentions(body, identifier)
+        }
+        Command::Call(_, expressions, arguments, _) => {
+            arguments.sequence().contains(&identifier) || expressions.sequence().iter().any(occurs)
+        }
+        Command::Spanned(_, command) => mentions(command, identifier),
+    }
+}
+
+// what `remove` does with a command
+enum Step<'c, 'a> {
+    Keep,
+    Remove,
+    // with one of its parts
+    Replace(&'c Command<'a>),
+}
+
+// the command without the commands `step` removes or replaces, none if nothing is left;
+// `step` sees each command other than a sequence before its parts, and a body that
+// can't be left empty keeps the last command it had
+fn remove<'c, 'a>(
+    command: &'c Command<'a>,
+    step: &mut impl FnMut(&'c Command<'a>) -> Step<'c, 'a>,
+) -> Option<Command<'a>> {
+    match command {
+        Command::Sequence(first, second) => {
+            return match (remove(first, step), remove(second, step)) {
+                (Some(first), Some(second)) => Some(Command::Sequence(first.into(), second.into())),
+                (first, second) => first.or(second),
+            }
+        }
+        Command::Spanned(span, command) => {
+            return remove(command, step).map(|command| Command::Spanned(*span, command.into()))
+        }
+        _ => {}
+    }
+    match step(command) {
+        Step::Keep => {}
+        Step::Remove => return None,
+        Step::Replace(part) => return remove(part, step),
+    }
+    Some(match command {
+        Command::Assign(..) | Command::Call(..) => command.clone(),
+        // a var without a command to be in scope for has no effect
+        Command::Var(identifier, sort, body) => {
+            Command::Var(*identifier, *sort, remove(body, step)?.into())
+        }
+        Command::IfElse(condition, if_branch, else_branch) => {
+            match (remove(if_branch, step), remove(else_branch, step)) {
+                (Some(if_branch), Some(else_branch)) => {
+                    Command::IfElse(condition.clone(), if_branch.into(), else_branch.into())
+                }
+                (Some(if_branch), None) => Command::If(condition.clone(), if_branch.into()),
+                (None, Some(else_branch)) => {
+                    let condition = Expression::Not(condition.clone().into());
+                    Command::If(condition, else_branch.into())
+                }
+                (None, None) if can_fail(condition) => {
+                    Command::If(condition.clone(), last(if_branch).into())
+                }
+                (None, None) => return None,
+            }
+        }
+        Command::If(condition, if_branch) => match remove(if_branch, step) {
+            Some(if_branch) => Command::If(condition.clone(), if_branch.into()),
+            None if can_fail(condition) => Command::If(condition.clone(), last(if_branch).into()),
+            None => return None,
+        },
+        // the loop may not terminate
+        Command::While(condition, body, invariant) => {
+            let body = remove(body, step).unwrap_or_else(|| last(body));
+            Command::While(condition.clone(), body.into(), invariant.clone())
+        }
+        Command::Sequence(..) | Command::Spanned(..) => unreachable!("handled above"),
+    })
+}
+
+// the commands `remove` removes have no effect, so any one of them can stand in for an
+// empty body
+fn last<'a>(command: &Command<'a>) -> Command<'a> {
+    let sequence = command.sequence();
+    (*sequence.last().expect("a sequence has commands")).clone()
+}
+
+// an if whose condition is a value becomes the branch taken
+fn branch<'c, 'a>(command: &'c Command<'a>) -> Step<'c, 'a> {
+    match command {
+        Command::IfElse(condition, if_branch, else_branch) => match value(condition) {
+            Some(Value::True) => Step::Replace(if_branch),
+            Some(Value::False) => Step::Replace(else_branch),
+            _ => Step::Keep,
+        },
+        Command::If(condition, if_branch) => match value(condition) {
+            Some(Value::True) => Step::Replace(if_branch),
+            Some(Value::False) => Step::Remove,
+            _ => Step::Keep,
+        },
+        _ => Step::Keep,
+    }
+}
+
+// dead assignments are removed, and vars whose variable isn't used become their body
+fn dead_code<'c, 'a>(command: &'c Command<'a>, dead: &HashSet<*const Command<'a>>) -> Step<'c, 'a> {
+    match command {
+        _ if dead.contains(&(command as *const Command)) => Step::Remove,
+        Command::Var(identifier, _, body) if !mentions(body, *identifier) => Step::Replace(body),
+        _ => Step::Keep,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bounded::Inputs,
+        eval::{Limits, Outcome, RuntimeError},
+        typecheck::BOOL_SORT,
+    };
+
+    const SOURCE: &str = "var g:Int;
+procedure inc(;ref r:Int) { r := r + g; }
+program p(a:Int, b:Int) {
+  var x:Int;
+  var unused:Int;
+  x := 2 * 3 + 1;
+  unused := a;
+  if x < 5 then { a := 1 / 0; } else { a := a + x; }
+  b := x * b;
+  if true ∧ b = 0 then b := 7; else b := b - 1;
+  call inc(;b);
+  x := b;
+}";
+
+    // the body of the program optimized with the passes
+    fn optimized(passes: Passes) -> Result<Command<'static>, String> {
+        let program = Program::parse(SOURCE)?.typecheck()?;
+        Ok(program.optimize(&passes).3)
+    }
+
+    // asserts that the passes optimize the body of the program to the commands
+    fn assert_body(passes: Passes, commands: &str) -> Result<(), String> {
+        let declarations = &SOURCE[..SOURCE.find("program").unwrap()];
+        let source = format!("{declarations}program p(a:Int, b:Int) {{ {commands} }}");
+        let expected = Program::parse(&source)?.typecheck()?.3;
+        assert_eq!(optimized(passes)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_passes() -> Result<(), String> {
+        let fold = Passes {
+            fold: true,
+            ..Passes::NONE
+        };
+        assert_body(
+            fold,
+            "var x:Int; var unused:Int; x := 7; unused := a;
+                if x < 5 then { a := 1 / 0; } else { a := a + x; }
+                b := x * b; if b = 0 then b := 7; else b := b - 1; call inc(;b); x := b;",
+        )?;
+        // the value of x doesn't fold to one without folding
+        let propagate = Passes {
+            propagate: true,
+            ..Passes::NONE
+        };
+        assert_eq!(
+            optimized(propagate)?,
+            Program::parse(SOURCE)?.typecheck()?.3
+        );
+        let dead_code = Passes {
+            dead_code: true,
+            ..Passes::NONE
+        };
+        assert_body(
+            dead_code,
+            "var x:Int; x := 2 * 3 + 1;
+                if x < 5 then { a := 1 / 0; } else { a := a + x; }
+                b := x * b; if true ∧ b = 0 then b := 7; else b := b - 1; call inc(;b);",
+        )?;
+        // the division by 0 goes with the branch, x with its last use
+        assert_body(
+            Passes::default(),
+            "a := a + 7; b := 7 * b;
+                if b = 0 then b := 7; else b := b - 1; call inc(;b);",
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_kept() -> Result<(), String> {
+        // an assignment that can fail stays, and so does a body that would be left empty
+        let source =
+            "procedure f(a:Int; ref r:Int) { var y:Int; y := a / r; if a < 0 then r := r; }
+program p(a:Int) { if false then a := 1; }";
+        let program = Program::parse(source)?.typecheck()?;
+        assert_eq!(program.optimize(&Passes::default()), program);
+        Ok(())
+    }
+
+    // the passes one at a time and all of them together
+    fn configurations() -> Vec<Passes> {
+        let none = Passes::NONE;
+        vec![
+            Passes { fold: true, ..none },
+            Passes {
+                propagate: true,
+                ..none
+            },
+            Passes {
+                branches: true,
+                ..none
+            },
+            Passes {
+                dead_code: true,
+                ..none
+            },
+            Passes::default(),
+        ]
+    }
+
+    // the values of the parameters, none if the evaluation ran out of fuel
+    fn outcome<'a>(
+        program: &Program<'a>,
+        inputs: Vec<Value>,
+    ) -> Result<Option<Vec<Value>>, RuntimeError<'a>> {
+        let limits = Limits {
+            steps: 2000,
+            depth: 50,
+        };
+        match program.eval_with_limits(inputs, limits) {
+            Ok(Outcome::Terminated(outputs)) => Ok(Some(outputs)),
+            Ok(Outcome::Diverged(..)) => Ok(None),
+            Err(error) => Err(error.error),
+        }
+    }
+
+    // wherever the program terminates or fails for Int parameters in -2..=2, the
+    // optimized program has to do the same
+    fn agree(source: &str) -> Result<(), String> {
+        let program = Program::parse(source)?.typecheck()?;
+        let domains: Vec<_> = (program.2.sorts().iter())
+            .map(|sort| match *sort == BOOL_SORT {
+                true => vec![Value::False, Value::True],
+                false => (-2..=2).map(Value::from).collect(),
+            })
+            .collect();
+        for passes in configurations() {
+            let optimized = program.optimize(&passes);
+            for inputs in Inputs::new(&domains) {
+                let expected = outcome(&program, inputs.clone());
+                if expected != Ok(None) {
+                    let found = outcome(&optimized, inputs.clone());
+                    assert_eq!(found, expected, "{passes:?} on {inputs:?}");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_meaning() -> Result<(), String> {
+        agree(SOURCE)?;
+        agree(
+            "var g:Int;
+procedure add(n:Int; ref r:Int) { var t:Int; t := n * 0; r := r + g + n; g := 2; }
+program p(a:Int, b:Int, c:Bool) {
+  var x:Int;
+  x := 2 * 3 + 1;
+  g := x - 7;
+  var y:Int;
+  y := a / b;
+  if x < 5 ∨ c then { a := a + x; } else { var x:Bool; x := c ∧ false; b := b - 1; }
+  while 0 < a do { a := a - x; x := 1; }
+  call add(x; b);
+  if ¬c ⇒ g = 2 then b := b + g;
+  x := b;
+}",
+        )?;
+        agree(include_str!("../programs/gcd.fsp"))?;
+        agree(include_str!("../programs/division.fsp"))?;
+        agree(include_str!("../programs/recursion.fsp"))
+    }
+
+    #[test]
+    fn test_meaning_aliased() -> Result<(), String> {
+        // a ref parameter can be passed a global, or the same variable as another one
+        agree(
+            "var g:Int;
+procedure f(;ref r:Int) { r := 1; g := 2; r := r + 0; }
+procedure h(;ref r:Int, s:Int) { r := 1; s := 2; r := r + 0; }
+procedure k(;ref r:Int) { r := 1; var x:Int; x := g; r := 2; g := g + x; }
+procedure l(;ref r:Int, s:Int) { r := 1; call f(;s); s := r + s; }
+program p(a:Int, b:Int, c:Bool) {
+  if c then call f(;g); else call f(;a);
+  a := a + g;
+  call h(;b, b);
+  call k(;g);
+  b := b + g;
+  call l(;a, a);
+}",
+        )
+    }
+}
@@ -0,0 +1,822 @@
+use crate::{diagnostic::Diagnostic, types::*};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TokenKind {
+    Identifier,
+    Numeral,
+    Symbol,
+    End,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    kind: TokenKind,
+    text: &'a str,
+    start: usize,
+}
+
+// longer symbols first so that ":=" is not read as ":"
+static SYMBOLS: [&str; 24] = [
+    ":=", ":", ";", ",", "(", ")", "{", "}", "+", "-", "*", "/", "%", "=", "≠", "<", "≤", ">", "≥",
+    "∧", "∨", "⇒", "⇔", "¬",
+];
+
+// ASCII spellings of the operators, longer ones first so that "<=>" is not read as "<="
+pub(crate) static ASCII: [(&str, &str); 8] = [
+    ("<=>", "⇔"),
+    ("=>", "⇒"),
+    ("<=", "≤"),
+    (">=", "≥"),
+    ("!=", "≠"),
+    ("&&", "∧"),
+    ("||", "∨"),
+    ("!", "¬"),
+];
+
+static KEYWORDS: [&str; 15] = [
+    "var",
+    "procedure",
+    "ref",
+    "program",
+    "if",
+    "then",
+    "else",
+    "while",
+    "do",
+    "call",
+    "requires",
+    "ensures",
+    "invariant",
+    "true",
+    "false",
+];
+
+impl<'a> Program<'a> {
+    pub fn parse(source: &'a str) -> Result<Program<'a>, Diagnostic<String>> {
+        let mut parser = Parser::new(source)?;
+        let program = parser.program()?;
+        parser.finish()?;
+        Ok(program)
+    }
+}
+
+impl<'a> Declarations<'a> {
+    pub fn parse(source: &'a str) -> Result<Declarations<'a>, Diagnostic<String>> {
+        let mut parser = Parser::new(source)?;
+        let mut declarations = Declarations::Empty;
+        while parser.peek().kind != TokenKind::End {
+            declarations = Declarations::Sequence(declarations.into(), parser.declaration()?);
+        }
+        Ok(declarations)
+    }
+}
+
+impl<'a> Command<'a> {
+    pub fn parse(source: &'a str) -> Result<Command<'a>, Diagnostic<String>> {
+        let mut parser = Parser::new(source)?;
+        let command = parser.commands()?;
+        parser.finish()?;
+        Ok(command)
+    }
+}
+
+impl<'a> Expression<'a> {
+    pub fn parse(source: &'a str) -> Result<Expression<'a>, Diagnostic<String>> {
+        let mut parser = Parser::new(source)?;
+        let expression = parser.expression()?;
+        parser.finish()?;
+        Ok(expression)
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token<'_>>, Diagnostic<String>> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    while let Some(c) = source[start..].chars().next() {
+        let rest = &source[start..];
+        if c.is_whitespace() {
+            start += c.len_utf8();
+            continue;
+        }
+        if rest.starts_with("//") {
+            start += rest.find('\n').unwrap_or(rest.len());
+            continue;
+        }
+        let (kind, length) = if c.is_alphabetic() || c == '_' {
+            let length = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            (TokenKind::Identifier, length)
+        } else if c.is_ascii_digit() {
+            let length = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            (TokenKind::Numeral, length)
+        } else if let Some((ascii, _)) = ASCII.iter().find(|(ascii, _)| rest.starts_with(ascii)) {
+            (TokenKind::Symbol, ascii.len())
+        } else if let Some(symbol) = SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+            (TokenKind::Symbol, symbol.len())
+        } else {
+            let span = Span {
+                start,
+                end: start + c.len_utf8(),
+            };
+            return Err(Diagnostic::new(format!("unexpected character {c:?}"), span));
+        };
+        tokens.push(Token {
+            kind,
+            text: &rest[..length],
+            start,
+        });
+        start += length;
+    }
+    tokens.push(Token {
+        kind: TokenKind::End,
+        text: "",
+        start: source.len(),
+    });
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    position: usize,
+    // end of the last token consumed, where the span of a finished node ends
+    end: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Result<Parser<'a>, Diagnostic<String>> {
+        Ok(Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+            end: 0,
+        })
+    }
+
+    fn peek(&self) -> Token<'a> {
+        self.tokens[self.position]
+    }
+
+    fn advance(&mut self) -> Token<'a> {
+        let token = self.peek();
+        if token.kind != TokenKind::End {
+            self.position += 1;
+            self.end = token.start + token.text.len();
+        }
+        token
+    }
+
+    // operators match their ASCII spelling too
+    fn is(&self, text: &str) -> bool {
+        let token = self.peek();
+        let ascii = ASCII.iter().find(|(ascii, _)| *ascii == token.text);
+        token.kind != TokenKind::End
+            && (token.text == text || ascii.is_some_and(|(_, unicode)| *unicode == text))
+    }
+
+    fn eat(&mut self, text: &str) -> bool {
+        if self.is(text) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), Diagnostic<String>> {
+        if self.eat(text) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("`{text}`")))
+        }
+    }
+
+    fn start(&self) -> usize {
+        self.peek().start
+    }
+
+    fn span(&self, start: usize) -> Span {
+        Span {
+            start,
+            end: self.end,
+        }
+    }
+
+    fn error(&self, expected: &str) -> Diagnostic<String> {
+        let token = self.peek();
+        let found = match token.kind {
+            TokenKind::End => "end of input".to_string(),
+            _ => format!("`{}`", token.text),
+        };
+        let span = Span {
+            start: token.start,
+            end: token.start + token.text.len(),
+        };
+        Diagnostic::new(format!("expected {expected} but found {found}"), span)
+    }
+
+    fn finish(&self) -> Result<(), Diagnostic<String>> {
+        match self.peek().kind {
+            TokenKind::End => Ok(()),
+            _ => Err(self.error("end of input")),
+        }
+    }
+
+    fn at_identifier(&self) -> bool {
+        let token = self.peek();
+        token.kind == TokenKind::Identifier && !KEYWORDS.contains(&token.text)
+    }
+
+    fn identifier(&mut self) -> Result<Identifier<'a>, Diagnostic<String>> {
+        if self.at_identifier() {
+            Ok(Identifier(self.advance().text, None))
+        } else {
+            Err(self.error("identifier"))
+        }
+    }
+
+    fn sort(&mut self) -> Result<Sort<'a>, Diagnostic<String>> {
+        Ok(Sort(self.identifier()?))
+    }
+
+    // declarations 'program' I '(' parameters ')' specification '{' commands '}'
+    fn program(&mut self) -> Result<Program<'a>, Diagnostic<String>> {
+        let mut declarations = Declarations::Empty;
+        while !self.is("program") {
+            declarations = Declarations::Sequence(declarations.into(), self.declaration()?);
+        }
+        self.expect("program")?;
+        let identifier = self.identifier()?;
+        self.expect("(")?;
+        let parameters = self.parameters()?;
+        self.expect(")")?;
+        let specification = self.specification()?;
+        let body = self.block()?;
+        Ok(Program(
+            declarations,
+            identifier,
+            parameters,
+            body,
+            specification,
+        ))
+    }
+
+    // ['requires' E] ['ensures' E]
+    fn specification(&mut self) -> Result<Specification<'a>, Diagnostic<String>> {
+        let mut specification = Specification::default();
+        if self.eat("requires") {
+            specification.requires = Some(self.expression()?);
+        }
+        if self.eat("ensures") {
+            specification.ensures = Some(self.expression()?);
+        }
+        Ok(specification)
+    }
+
+    // 'var' I ':' S ';'
+    // | 'procedure' I '(' parameters [';' 'ref' parameters] ')' specification '{' commands '}'
+    // the span of a procedure only covers its head
+    fn declaration(&mut self) -> Result<Declaration<'a>, Diagnostic<String>> {
+        let start = self.start();
+        if self.eat("var") {
+            let identifier = self.identifier()?;
+            self.expect(":")?;
+            let sort = self.sort()?;
+            self.expect(";")?;
+            let declaration = Declaration::Variable(identifier, sort);
+            Ok(Declaration::Spanned(self.span(start), declaration.into()))
+        } else if self.eat("procedure") {
+            let identifier = self.identifier()?;
+            self.expect("(")?;
+            let in_params = self.parameters()?;
+            let out_params = if self.eat(";") {
+                self.expect("ref")?;
+                self.parameters()?
+            } else {
+                Parameters::Empty
+            };
+            self.expect(")")?;
+            let span = self.span(start);
+            let specification = self.specification()?;
+            let body = self.block()?;
+            let declaration =
+                Declaration::Procedure(identifier, in_params, out_params, body, specification);
+            Ok(Declaration::Spanned(span, declaration.into()))
+        } else {
+            Err(self.error("declaration"))
+        }
+    }
+
+    // [I ':' S {',' I ':' S}], each sequence node spanning its last parameter
+    fn parameters(&mut self) -> Result<Parameters<'a>, Diagnostic<String>> {
+        let mut parameters = Parameters::Empty;
+        if !self.at_identifier() {
+            return Ok(parameters);
+        }
+        loop {
+            let start = self.start();
+            let variable = Variable(self.identifier()?);
+            self.expect(":")?;
+            let sort = self.sort()?;
+            parameters = Parameters::Spanned(
+                self.span(start),
+                Parameters::Sequence(parameters.into(), variable, sort).into(),
+            );
+            if !self.eat(",") {
+                return Ok(parameters);
+            }
+        }
+    }
+
+    fn block(&mut self) -> Result<Command<'a>, Diagnostic<String>> {
+        self.expect("{")?;
+        let command = self.commands()?;
+        self.expect("}")?;
+        Ok(command)
+    }
+
+    // a non-empty command sequence up to the end of the enclosing block,
+    // the span of a var only covers its declaration
+    fn commands(&mut self) -> Result<Command<'a>, Diagnostic<String>> {
+        let start = self.start();
+        if self.eat("var") {
+            let identifier = self.identifier()?;
+            self.expect(":")?;
+            let sort = self.sort()?;
+            self.expect(";")?;
+            let span = self.span(start);
+            let command = Command::Var(identifier, sort, self.commands()?.into());
+            return Ok(Command::Spanned(span, command.into()));
+        }
+        let first = self.command()?;
+        if self.is("}") || self.peek().kind == TokenKind::End {
+            Ok(first)
+        } else {
+            Ok(Command::Sequence(first.into(), self.commands()?.into()))
+        }
+    }
+
+    fn command(&mut self) -> Result<Command<'a>, Diagnostic<String>> {
+        let start = self.start();
+        let command = if self.is("{") {
+            return self.block();
+        } else if self.eat("if") {
+            let condition = self.expression()?;
+            self.expect("then")?;
+            let if_branch = self.branch()?;
+            if self.eat("else") {
+                let else_branch = self.branch()?;
+                Command::IfElse(condition, if_branch.into(), else_branch.into())
+            } else {
+                Command::If(condition, if_branch.into())
+            }
+        } else if self.eat("while") {
+            let condition = self.expression()?;
+            let invariant = if self.eat("invariant") {
+                Some(self.expression()?)
+            } else {
+                None
+            };
+            self.expect("do")?;
+            let body = self.branch()?;
+            Command::While(condition, body.into(), invariant)
+        } else if self.eat("call") {
+            let identifier = self.identifier()?;
+            self.expect("(")?;
+            let expressions = self.expressions()?;
+            let variables = if self.eat(";") {
+                self.variables()?
+            } else {
+                Variables::Empty
+            };
+            self.expect(")")?;
+            self.expect(";")?;
+            Command::Call(identifier, expressions, variables, None)
+        } else if self.at_identifier() {
+            let identifier = self.identifier()?;
+            self.expect(":=")?;
+            let expression = self.expression()?;
+            self.expect(";")?;
+            Command::Assign(identifier, expression)
+        } else {
+            return Err(self.error("command"));
+        };
+        Ok(Command::Spanned(self.span(start), command.into()))
+    }
+
+    // branches and loop bodies are blocks or, as in the book, a single command
+    fn branch(&mut self) -> Result<Command<'a>, Diagnostic<String>> {
+        if self.is("{") {
+            self.block()
+        } else {
+            self.command()
+        }
+    }
+
+    fn expressions(&mut self) -> Result<Expressions<'a>, Diagnostic<String>> {
+        if self.is(";") || self.is(")") {
+            return Ok(Expressions::Empty);
+        }
+        let first = self.expression()?;
+        let rest = if self.eat(",") {
+            self.expressions()?
+        } else {
+            Expressions::Empty
+        };
+        Ok(Expressions::Sequence(first, rest.into()))
+    }
+
+    fn variables(&mut self) -> Result<Variables<'a>, Diagnostic<String>> {
+        if self.is(")") {
+            return Ok(Variables::Empty);
+        }
+        let first = Variable(self.identifier()?);
+        let rest = if self.eat(",") {
+            self.variables()?
+        } else {
+            Variables::Empty
+        };
+        Ok(Variables::Sequence(first, rest.into()))
+    }
+
+    fn spanned(&self, start: usize, expression: Expression<'a>) -> Expression<'a> {
+        Expression::Spanned(self.span(start), expression.into())
+    }
+
+    // precedence from loosest to tightest: ⇔, ⇒, ∨, ∧, = ≠ < ≤ > ≥, + -, * / %,
+    // prefix - ¬
+    fn expression(&mut self) -> Result<Expression<'a>, Diagnostic<String>> {
+        let start = self.start();
+        let mut left = self.implication()?;
+        while self.eat("⇔") {
+            let right = self.implication()?;
+            left = self.spanned(start, Expression::Equivalent(left.into(), right.into()));
+        }
+        Ok(left)
+    }
+
+    // `a ⇒ b ⇒ c` is `a ⇒ (b ⇒ c)`
+    fn implication(&mut self) -> Result<Expression<'a>, Diagnostic<String>> {
+        let start = self.start();
+        let left = self.disjunction()?;
+        if !self.eat("⇒") {
+            return Ok(left);
+        }
+        let right = self.implication()?;
+        Ok(self.spanned(start, Expression::Implies(left.into(), right.into())))
+    }
+
+    fn disjunction(&mut self) -> Result<Expression<'a>, Diagnostic<String>> {
+        let start = self.start();
+        let mut left = self.conjunction()?;
+        while self.eat("∨") {
+            let right = self.conjunction()?;
+            left = self.spanned(start, Expression::Or(left.into(), right.into()));
+        }
+        Ok(left)
+    }
+
+    fn conjunction(&mut self) -> Result<Expression<'a>, Diagnostic<String>> {
+        let start = self.start();
+        let mut left = self.comparison()?;
+        while self.eat("∧") {
+            let right = self.comparison()?;
+            left = self.spanned(start, Expression::And(left.into(), right.into()));
+        }
+        Ok(left)
+    }
+
+    // comparisons don't associate, nested ones need parentheses
+    fn comparison(&mut self) -> Result<Expression<'a>, Diagnostic<String>> {
+        let start = self.start();
+        let left = self.additive()?;
+        let comparison = if self.eat("=") {
+            Expression::Equal
+        } else if self.eat("≠") {
+            Expression::NotEqual
+        } else if self.eat("<") {
+            Expression::LessThan
+        } else if self.eat("≤") {
+            Expression::LessThanOrEqual
+        } else if self.eat(">") {
+            Expression::GreaterThan
+        } else if self.eat("≥") {
+            Expression::GreaterThanOrEqual
+        } else {
+            return Ok(left);
+        };
+        let expression = comparison(left.into(), self.additive()?.into());
+        Ok(self.spanned(start, expression))
+    }
+
+    fn additive(&mut self) -> Result<Expression<'a>, Diagnostic<String>> {
+        let start = self.start();
+        let mut left = self.multiplicative()?;
+        loop {
+            let expression = if self.eat("+") {
+                Expression::Sum(left.into(), self.multiplicative()?.into())
+            } else if self.eat("-") {
+                Expression::Difference(left.into(), self.multiplicative()?.into())
+            } else {
+                return Ok(left);
+            };
+            left = self.spanned(start, expression);
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expression<'a>, Diagnostic<String>> {
+        let start = self.start();
+        let mut left = self.unary()?;
+        loop {
+            let expression = if self.eat("*") {
+                Expression::Product(left.into(), self.unary()?.into())
+            } else if self.eat("/") {
+                Expression::Division(left.into(), self.unary()?.into())
+            } else if self.eat("%") {
+                Expression::Remainder(left.into(), self.unary()?.into())
+            } else {
+                return Ok(left);
+            };
+            left = self.spanned(start, expression);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expression<'a>, Diagnostic<String>> {
+        let start = self.start();
+        let expression = if self.eat("-") {
+            // `-3` is a negative literal, `-(3)` the negation of a positive one
+            if self.peek().kind == TokenKind::Numeral {
+                self.numeral(start, true)?
+            } else {
+                Expression::Negative(self.unary()?.into())
+            }
+        } else if self.eat("¬") {
+            Expression::Not(self.unary()?.into())
+        } else {
+            return self.primary();
+        };
+        Ok(self.spanned(start, expression))
+    }
+
+    fn primary(&mut self) -> Result<Expression<'a>, Diagnostic<String>> {
+        let start = self.start();
+        let expression = if self.peek().kind == TokenKind::Numeral {
+            self.numeral(start, false)?
+        } else if self.eat("true") {
+            Expression::Value(Value::True)
+        } else if self.eat("false") {
+            Expression::Value(Value::False)
+        } else if self.at_identifier() {
+            Expression::Variable(self.identifier()?)
+        } else if self.eat("(") {
+            let expression = self.expression()?;
+            self.expect(")")?;
+            return Ok(expression);
+        } else {
+            return Err(self.error("expression"));
+        };
+        Ok(self.spanned(start, expression))
+    }
+
+    fn numeral(
+        &mut self,
+        start: usize,
+        negative: bool,
+    ) -> Result<Expression<'a>, Diagnostic<String>> {
+        let token = self.advance();
+        let text = if negative {
+            format!("-{}", token.text)
+        } else {
+            token.text.to_string()
+        };
+        match text.parse() {
+            Ok(numeral) => Ok(Expression::Value(Value::Numeral(numeral))),
+            Err(error) => Err(Diagnostic::new(error, self.span(start))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gcd::{a_b_gcd_parameter_sequence, generate_gcd};
+
+    use super::*;
+
+    // what printing and parsing keep of a command: neither call annotations nor how
+    // sequences are nested, so they are nested to the right
+    fn syntax<'a>(command: &Command<'a>) -> Command<'a> {
+        let mut commands = Vec::new();
+        flatten(command, &mut commands);
+        let mut commands = commands.into_iter().rev().map(|command| match command {
+            Command::Var(identifier, sort, body) => {
+                Command::Var(*identifier, *sort, syntax(body).into())
+            }
+            Command::IfElse(condition, if_branch, else_branch) => Command::IfElse(
+                condition.clone(),
+                syntax(if_branch).into(),
+                syntax(else_branch).into(),
+            ),
+            Command::If(condition, branch) => Command::If(condition.clone(), syntax(branch).into()),
+            Command::While(condition, body, invariant) => {
+                Command::While(condition.clone(), syntax(body).into(), invariant.clone())
+            }
+            Command::Call(identifier, expressions, variables, _) => {
+                Command::Call(*identifier, expressions.clone(), variables.clone(), None)
+            }
+            command => command.clone(),
+        });
+        let last = commands.next().expect("a sequence has a command");
+        commands.fold(last, |rest, command| {
+            Command::Sequence(command.into(), rest.into())
+        })
+    }
+
+    fn flatten<'c, 'a>(command: &'c Command<'a>, commands: &mut Vec<&'c Command<'a>>) {
+        match command.unspanned() {
+            Command::Sequence(first, second) => {
+                flatten(first, commands);
+                flatten(second, commands);
+            }
+            command => commands.push(command),
+        }
+    }
+
+    fn declarations_syntax<'a>(declarations: &Declarations<'a>) -> Declarations<'a> {
+        match declarations {
+            Declarations::Empty => Declarations::Empty,
+            Declarations::Sequence(rest, declaration) => {
+                let declaration = match declaration.unspanned() {
+                    Declaration::Procedure(name, in_params, out_params, body, specification) => {
+                        Declaration::Procedure(
+                            *name,
+                            in_params.clone(),
+                            out_params.clone(),
+                            syntax(body),
+                            specification.clone(),
+                        )
+                    }
+                    declaration => declaration.clone(),
+                };
+                Declarations::Sequence(declarations_syntax(rest).into(), declaration)
+            }
+        }
+    }
+
+    fn program_syntax<'a>(program: &Program<'a>) -> Program<'a> {
+        let Program(declarations, name, parameters, body, specification) = program;
+        Program(
+            declarations_syntax(declarations),
+            *name,
+            parameters.clone(),
+            syntax(body),
+            specification.clone(),
+        )
+    }
+
+    // whether the programs print the same
+    fn same_syntax(left: &Program, right: &Program) -> bool {
+        program_syntax(left) == program_syntax(right)
+    }
+
+    #[test]
+    fn test_parse_gcd_source() -> Result<(), String> {
+        let program = Program::parse(include_str!("../programs/gcd.fsp"))?;
+        assert!(same_syntax(
+            &program,
+            &generate_gcd(a_b_gcd_parameter_sequence())
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_display_gcd() -> Result<(), String> {
+        let program = generate_gcd(a_b_gcd_parameter_sequence());
+        let source = format!("{program}");
+        assert!(same_syntax(&Program::parse(&source)?, &program));
+
+        let annotated_program = program.typecheck()?;
+        let source = format!("{annotated_program}");
+        assert!(same_syntax(&Program::parse(&source)?, &annotated_program));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_display_command() -> Result<(), String> {
+        let command = Command::Sequence(
+            Command::Sequence(
+                Command::Var(
+                    "x".into(),
+                    "Bool".into(),
+                    Command::If(
+                        Expression::Equal(
+                            Expression::Equal("a".into(), "b".into()).into(),
+                            Expression::Value(Value::True).into(),
+                        ),
+                        Command::Assign(
+                            "a".into(),
+                            Expression::Difference(
+                                Expression::Negative((-3).into()).into(),
+                                (-4).into(),
+                            ),
+                        )
+                        .into(),
+                    )
+                    .into(),
+                )
+                .into(),
+                Command::Call("p".into(), Expressions::Empty, Variables::Empty, None).into(),
+            )
+            .into(),
+            Command::While(
+                Expression::Not(
+                    Expression::LessThanOrEqual(
+                        "a".into(),
+                        Expression::Equal(Expression::Value(Value::False).into(), "b".into())
+                            .into(),
+                    )
+                    .into(),
+                ),
+                Command::Assign("a".into(), Expression::Value(i32::MIN.into())).into(),
+                Some(Expression::LessThanOrEqual("a".into(), 0.into())),
+            )
+            .into(),
+        );
+        let source = format!("{command}");
+        assert_eq!(syntax(&Command::parse(&source)?), syntax(&command));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_precedence() -> Result<(), String> {
+        assert_eq!(
+            Expression::parse("a+b*c = -d ∨ ¬x ∧ y")?,
+            Expression::Or(
+                Expression::Equal(
+                    Expression::Sum(
+                        "a".into(),
+                        Expression::Product("b".into(), "c".into()).into()
+                    )
+                    .into(),
+                    Expression::Negative("d".into()).into(),
+                )
+                .into(),
+                Expression::And(Expression::Not("x".into()).into(), "y".into()).into(),
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_operators() -> Result<(), String> {
+        assert_eq!(
+            Expression::parse("a ⇒ b ⇒ c ∨ d ⇔ x%2 ≠ 0")?,
+            Expression::Equivalent(
+                Expression::Implies(
+                    "a".into(),
+                    Expression::Implies("b".into(), Expression::Or("c".into(), "d".into()).into())
+                        .into()
+                )
+                .into(),
+                Expression::NotEqual(Expression::Remainder("x".into(), 2.into()).into(), 0.into())
+                    .into(),
+            )
+        );
+        let expression = Expression::parse("(a < b) = (c ≥ d) ⇔ (e > f ⇒ g)")?;
+        assert_eq!(expression.to_string(), "((a<b)=(c≥d)⇔(e>f⇒g))");
+        assert_eq!(Expression::parse(&expression.to_string())?, expression);
+        assert!(Expression::parse("a < b < c").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let source = "x := 1;\nwhile x do\n  x := ;";
+        let error = Command::parse(source).unwrap_err();
+        assert_eq!(error.error, "expected expression but found `;`");
+        assert_eq!(
+            error.render(source),
+            "error: expected expression but found `;`\n --> 3:8\n  |\n3 |   x := ;\n  |        ^"
+        );
+
+        let error = Expression::parse("a = b = c").unwrap_err();
+        assert_eq!(error.error, "expected end of input but found `=`");
+        assert_eq!(error.span, Some(Span { start: 6, end: 7 }));
+    }
+
+    #[test]
+    fn test_parse_spans() -> Result<(), String> {
+        let source = "x := a + b*c;";
+        let command = Command::parse(source)?;
+        assert_eq!(command.span(), Some(Span { start: 0, end: 13 }));
+        let Command::Assign(_, expression) = command.unspanned() else {
+            panic!("expected assignment");
+        };
+        assert_eq!(expression.span(), Some(Span { start: 5, end: 12 }));
+        let Expression::Sum(_, right) = expression.unspanned() else {
+            panic!("expected sum");
+        };
+        assert_eq!(right.span(), Some(Span { start: 9, end: 12 }));
+        Ok(())
+    }
+}
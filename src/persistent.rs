@@ -0,0 +1,411 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    rc::Rc,
+};
+
+// bits of the hash used per level of the trie
+const BITS: u32 = 5;
+const MASK: u64 = (1 << BITS) - 1;
+
+// an immutable map, a hash array mapped trie: inserting or removing an entry copies only
+// the nodes on the path to it and shares the rest with the map it was made from, so
+// updates take O(log n) and clones O(1)
+pub struct Map<K, V> {
+    root: Rc<Node<K, V>>,
+    len: usize,
+}
+
+enum Node<K, V> {
+    // the children present among the 32 a level can have, in the order of their bits
+    Branch(u32, Vec<Rc<Node<K, V>>>),
+    // the entries whose keys have this hash
+    Leaf(u64, Vec<(K, V)>),
+}
+
+fn hash<K: Hash>(key: &K) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+// the bit of the child for `hash` on the level at `shift` and its index among the
+// children present
+fn position(bitmap: u32, hash: u64, shift: u32) -> (u32, usize) {
+    let bit = 1 << ((hash >> shift) & MASK);
+    (bit, (bitmap & (bit - 1)).count_ones() as usize)
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> Map<K, V> {
+    pub fn new() -> Map<K, V> {
+        Map {
+            root: Rc::new(Node::Branch(0, Vec::new())),
+            len: 0,
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        let hash = hash(key);
+        let mut node = &self.root;
+        let mut shift = 0;
+        loop {
+            match &**node {
+                Node::Branch(bitmap, children) => {
+                    let (bit, index) = position(*bitmap, hash, shift);
+                    if bitmap & bit == 0 {
+                        return None;
+                    }
+                    node = &children[index];
+                    shift += BITS;
+                }
+                Node::Leaf(leaf_hash, entries) => {
+                    if *leaf_hash != hash {
+                        return None;
+                    }
+                    let (_, value) = entries.iter().find(|(k, _)| k == key)?;
+                    return Some(value);
+                }
+            }
+        }
+    }
+
+    // the map with `key` bound to `value`, replacing what it was bound to before
+    pub fn insert(&self, key: K, value: V) -> Map<K, V> {
+        let (root, added) = insert(&self.root, hash(&key), 0, key, value);
+        Map {
+            root: Rc::new(root),
+            len: self.len + added as usize,
+        }
+    }
+
+    // the map without `key`
+    pub fn remove(&self, key: &K) -> Map<K, V> {
+        match remove(&self.root, hash(key), 0, key) {
+            None => self.clone(),
+            Some(root) => Map {
+                root: root.unwrap_or_else(|| Rc::new(Node::Branch(0, Vec::new()))),
+                len: self.len - 1,
+            },
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // in no particular order
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            nodes: vec![std::slice::from_ref(&self.root).iter()],
+            entries: [].iter(),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+}
+
+// returns the new node and whether the key wasn't there before
+fn insert<K: Eq + Clone, V: Clone>(
+    node: &Rc<Node<K, V>>,
+    hash: u64,
+    shift: u32,
+    key: K,
+    value: V,
+) -> (Node<K, V>, bool) {
+    match &**node {
+        Node::Branch(bitmap, children) => {
+            let (bit, index) = position(*bitmap, hash, shift);
+            let mut children = children.clone();
+            if bitmap & bit == 0 {
+                children.insert(index, Rc::new(Node::Leaf(hash, vec![(key, value)])));
+                (Node::Branch(bitmap | bit, children), true)
+            } else {
+                let (child, added) = insert(&children[index], hash, shift + BITS, key, value);
+                children[index] = Rc::new(child);
+                (Node::Branch(*bitmap, children), added)
+            }
+        }
+        Node::Leaf(leaf_hash, entries) if *leaf_hash == hash => {
+            let mut entries = entries.clone();
+            match entries.iter_mut().find(|(k, _)| *k == key) {
+                Some(entry) => {
+                    entry.1 = value;
+                    (Node::Leaf(hash, entries), false)
+                }
+                None => {
+                    entries.push((key, value));
+                    (Node::Leaf(hash, entries), true)
+                }
+            }
+        }
+        // the hashes differ on this level or a deeper one, where the leaf is pushed down
+        Node::Leaf(leaf_hash, _) => {
+            let (bit, _) = position(0, *leaf_hash, shift);
+            let branch = Rc::new(Node::Branch(bit, vec![node.clone()]));
+            insert(&branch, hash, shift, key, value)
+        }
+    }
+}
+
+// none if the key isn't there, otherwise the new node or none if it is left empty
+#[allow(clippy::type_complexity)]
+fn remove<K: Eq + Clone, V: Clone>(
+    node: &Rc<Node<K, V>>,
+    hash: u64,
+    shift: u32,
+    key: &K,
+) -> Option<Option<Rc<Node<K, V>>>> {
+    match &**node {
+        Node::Branch(bitmap, children) => {
+            let (bit, index) = position(*bitmap, hash, shift);
+            if bitmap & bit == 0 {
+                return None;
+            }
+            let child = remove(&children[index], hash, shift + BITS, key)?;
+            let mut children = children.clone();
+            let bitmap = match child {
+                Some(child) => {
+                    children[index] = child;
+                    *bitmap
+                }
+                None => {
+                    children.remove(index);
+                    bitmap & !bit
+                }
+            };
+            Some((bitmap != 0).then(|| Rc::new(Node::Branch(bitmap, children))))
+        }
+        Node::Leaf(leaf_hash, entries) => {
+            if *leaf_hash != hash {
+                return None;
+            }
+            let index = entries.iter().position(|(k, _)| k == key)?;
+            let mut entries = entries.clone();
+            entries.remove(index);
+            Some((!entries.is_empty()).then(|| Rc::new(Node::Leaf(hash, entries))))
+        }
+    }
+}
+
+pub struct Iter<'m, K, V> {
+    // the children of the branches on the path to the current leaf not visited yet
+    nodes: Vec<std::slice::Iter<'m, Rc<Node<K, V>>>>,
+    entries: std::slice::Iter<'m, (K, V)>,
+}
+
+impl<'m, K, V> Iterator for Iter<'m, K, V> {
+    type Item = (&'m K, &'m V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((key, value)) = self.entries.next() {
+                return Some((key, value));
+            }
+            match self.nodes.last_mut()?.next() {
+                None => {
+                    self.nodes.pop();
+                }
+                Some(node) => match &**node {
+                    Node::Branch(_, children) => self.nodes.push(children.iter()),
+                    Node::Leaf(_, entries) => self.entries = entries.iter(),
+                },
+            }
+        }
+    }
+}
+
+impl<K, V> Clone for Map<K, V> {
+    fn clone(&self) -> Self {
+        Map {
+            root: self.root.clone(),
+            len: self.len,
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> Default for Map<K, V> {
+    fn default() -> Self {
+        Map::new()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone + PartialEq> PartialEq for Map<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl<K: Hash + Eq + Clone + Debug, V: Clone + Debug> Debug for Map<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> FromIterator<(K, V)> for Map<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        iter.into_iter()
+            .fold(Map::new(), |map, (key, value)| map.insert(key, value))
+    }
+}
+
+// the map stores and environments used before they were persistent, a `HashMap` that is
+// copied for every change, so that tests can time the evaluation on both; maps made
+// within `copying` are baselines, all others persistent
+#[cfg(test)]
+pub(crate) mod baseline {
+    use std::{cell::Cell, collections::HashMap, fmt::Debug, hash::Hash};
+
+    thread_local! {
+        static COPYING: Cell<bool> = const { Cell::new(false) };
+    }
+
+    pub(crate) fn copying<T>(f: impl FnOnce() -> T) -> T {
+        let outer = COPYING.with(|copying| copying.replace(true));
+        let result = f();
+        COPYING.with(|copying| copying.set(outer));
+        result
+    }
+
+    #[derive(Clone)]
+    pub enum Map<K, V> {
+        Persistent(super::Map<K, V>),
+        Copied(HashMap<K, V>),
+    }
+
+    impl<K: Hash + Eq + Clone, V: Clone> Map<K, V> {
+        pub fn new() -> Map<K, V> {
+            if COPYING.with(Cell::get) {
+                Map::Copied(HashMap::new())
+            } else {
+                Map::Persistent(super::Map::new())
+            }
+        }
+
+        pub fn get(&self, key: &K) -> Option<&V> {
+            match self {
+                Map::Persistent(map) => map.get(key),
+                Map::Copied(map) => map.get(key),
+            }
+        }
+
+        pub fn insert(&self, key: K, value: V) -> Map<K, V> {
+            match self {
+                Map::Persistent(map) => Map::Persistent(map.insert(key, value)),
+                Map::Copied(map) => {
+                    let mut clone = map.clone();
+                    clone.insert(key, value);
+                    Map::Copied(clone)
+                }
+            }
+        }
+
+        pub fn remove(&self, key: &K) -> Map<K, V> {
+            match self {
+                Map::Persistent(map) => Map::Persistent(map.remove(key)),
+                Map::Copied(map) => {
+                    let mut clone = map.clone();
+                    clone.remove(key);
+                    Map::Copied(clone)
+                }
+            }
+        }
+
+        pub fn iter(&self) -> Box<dyn Iterator<Item = (&K, &V)> + '_> {
+            match self {
+                Map::Persistent(map) => Box::new(map.iter()),
+                Map::Copied(map) => Box::new(map.iter()),
+            }
+        }
+
+        pub fn keys(&self) -> impl Iterator<Item = &K> {
+            self.iter().map(|(key, _)| key)
+        }
+    }
+
+    impl<K: Hash + Eq + Clone, V: Clone + PartialEq> PartialEq for Map<K, V> {
+        fn eq(&self, other: &Self) -> bool {
+            self.iter().count() == other.iter().count()
+                && self
+                    .iter()
+                    .all(|(key, value)| other.get(key) == Some(value))
+        }
+    }
+
+    impl<K: Hash + Eq + Clone + Debug, V: Clone + Debug> Debug for Map<K, V> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_map().entries(self.iter()).finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    // keys with the same hash end up in one leaf
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Colliding(u32);
+
+    impl Hash for Colliding {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            (self.0 % 3).hash(state);
+        }
+    }
+
+    #[test]
+    fn test_map() {
+        let mut map = Map::new();
+        let mut reference = HashMap::new();
+        let mut versions = Vec::new();
+        for i in 0..2000u32 {
+            let key = (i * 7919) % 1000;
+            map = map.insert(key, i);
+            reference.insert(key, i);
+            if i % 3 == 0 {
+                map = map.remove(&(i % 500));
+                reference.remove(&(i % 500));
+            }
+            versions.push((map.clone(), reference.clone()));
+        }
+        // the earlier versions are left as they were
+        for (map, reference) in versions.iter().step_by(97) {
+            assert_eq!(map.len(), reference.len());
+            assert_eq!(map.iter().count(), reference.len());
+            for (key, value) in reference {
+                assert_eq!(map.get(key), Some(value));
+            }
+            assert_eq!(
+                map,
+                &map.iter().map(|(k, v)| (*k, *v)).collect::<Map<_, _>>()
+            );
+        }
+        let removed = map.remove(&5000);
+        assert_eq!(removed, map);
+        let emptied = map.keys().fold(map.clone(), |map, key| map.remove(key));
+        assert!(emptied.is_empty());
+        assert_ne!(emptied, map);
+    }
+
+    #[test]
+    fn test_collisions() {
+        let map: Map<_, _> = (0..10).map(|i| (Colliding(i), i)).collect();
+        assert_eq!(map.len(), 10);
+        let map = map.insert(Colliding(4), 40).remove(&Colliding(7));
+        assert_eq!(map.get(&Colliding(4)), Some(&40));
+        assert_eq!(map.get(&Colliding(7)), None);
+        assert_eq!(map.get(&Colliding(1)), Some(&1));
+        assert_eq!(map.len(), 9);
+    }
+}
@@ -0,0 +1,563 @@
+use crate::{parser::ASCII, types::*};
+
+// how the pretty-printer lays out a program
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Style {
+    // spaces per nesting level
+    pub indent: usize,
+    // lines are only broken where needed to stay within this many columns
+    pub width: usize,
+    // `<=` instead of `≤` and so on, the parser reads both
+    pub ascii: bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            indent: 2,
+            width: 80,
+            ascii: false,
+        }
+    }
+}
+
+impl<'a> Program<'a> {
+    pub fn pretty(&self, style: &Style) -> String {
+        Printer { style }.program(self).layout(style)
+    }
+}
+
+impl<'a> Command<'a> {
+    pub fn pretty(&self, style: &Style) -> String {
+        Printer { style }.command(self).layout(style)
+    }
+}
+
+impl<'a> Expression<'a> {
+    pub fn pretty(&self, style: &Style) -> String {
+        Printer { style }.expression(self).layout(style)
+    }
+}
+
+// a document in the style of Wadler's "A prettier printer"
+enum Doc {
+    Text(String),
+    // a space, or a line break if the group it is in doesn't fit on the line
+    Line,
+    // always a line break
+    Newline,
+    // one level deeper after the line breaks in it
+    Nest(Box<Doc>),
+    // the lines in it are either all spaces or all line breaks
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+fn text(text: impl Into<String>) -> Doc {
+    Doc::Text(text.into())
+}
+
+fn nest(doc: Doc) -> Doc {
+    Doc::Nest(doc.into())
+}
+
+fn group(doc: Doc) -> Doc {
+    Doc::Group(doc.into())
+}
+
+impl Doc {
+    fn layout(&self, style: &Style) -> String {
+        let mut output = String::new();
+        let mut column = 0;
+        // the indentation, whether the enclosing group is broken and the document, the
+        // next one last
+        let mut stack = vec![(0, true, self)];
+        while let Some((indent, broken, doc)) = stack.pop() {
+            match doc {
+                Doc::Text(text) => {
+                    output.push_str(text);
+                    column += text.chars().count();
+                }
+                Doc::Line if !broken => {
+                    output.push(' ');
+                    column += 1;
+                }
+                Doc::Line | Doc::Newline => {
+                    output.push('\n');
+                    output.push_str(&" ".repeat(indent));
+                    column = indent;
+                }
+                Doc::Nest(doc) => stack.push((indent + style.indent, broken, doc)),
+                Doc::Group(doc) => {
+                    let fits = !broken || fits(style.width.saturating_sub(column), doc, &stack);
+                    stack.push((indent, !fits, doc));
+                }
+                Doc::Concat(docs) => {
+                    stack.extend(docs.iter().rev().map(|doc| (indent, broken, doc)));
+                }
+            }
+        }
+        // blank lines between declarations would otherwise keep their indentation
+        output
+            .lines()
+            .map(str::trim_end)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+// whether the group on one line and what follows it up to the next line break fit in
+// `width` columns, groups that follow are assumed not to break
+fn fits(width: usize, group: &Doc, rest: &[(usize, bool, &Doc)]) -> bool {
+    let mut width = width.min(isize::MAX as usize) as isize;
+    let mut stack = vec![(false, group)];
+    let mut rest = rest.iter().rev();
+    loop {
+        let (broken, doc) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some((_, broken, doc)) => (*broken, *doc),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(text) => width -= text.chars().count() as isize,
+            Doc::Line if !broken => width -= 1,
+            Doc::Line | Doc::Newline => return true,
+            Doc::Nest(doc) => stack.push((broken, doc)),
+            Doc::Group(doc) => stack.push((false, doc)),
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (broken, doc))),
+        }
+        if width < 0 {
+            return false;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Associativity {
+    Left,
+    Right,
+    None,
+}
+
+// how tightly an expression binds its operands, from ⇔ at 1 to the primary expressions
+fn precedence(expression: &Expression) -> u8 {
+    match expression.unspanned() {
+        Expression::Equivalent(..) => 1,
+        Expression::Implies(..) => 2,
+        Expression::Or(..) => 3,
+        Expression::And(..) => 4,
+        Expression::Equal(..)
+        | Expression::NotEqual(..)
+        | Expression::LessThan(..)
+        | Expression::LessThanOrEqual(..)
+        | Expression::GreaterThan(..)
+        | Expression::GreaterThanOrEqual(..) => 5,
+        Expression::Sum(..) | Expression::Difference(..) => 6,
+        Expression::Product(..) | Expression::Division(..) | Expression::Remainder(..) => 7,
+        Expression::Negative(_) | Expression::Not(_) => 8,
+        Expression::Value(_) | Expression::Variable(_) | Expression::Spanned(..) => 9,
+    }
+}
+
+fn associativity(precedence: u8) -> Associativity {
+    match precedence {
+        2 => Associativity::Right,
+        5 => Associativity::None,
+        _ => Associativity::Left,
+    }
+}
+
+// the operator and operands of a binary expression
+fn binary<'e, 'a>(
+    expression: &'e Expression<'a>,
+) -> Option<(&'static str, &'e Expression<'a>, &'e Expression<'a>)> {
+    let (operator, left, right) = match expression.unspanned() {
+        Expression::Sum(left, right) => ("+", left, right),
+        Expression::Difference(left, right) => ("-", left, right),
+        Expression::Product(left, right) => ("*", left, right),
+        Expression::Division(left, right) => ("/", left, right),
+        Expression::Remainder(left, right) => ("%", left, right),
+        Expression::Equal(left, right) => ("=", left, right),
+        Expression::NotEqual(left, right) => ("≠", left, right),
+        Expression::LessThan(left, right) => ("<", left, right),
+        Expression::LessThanOrEqual(left, right) => ("≤", left, right),
+        Expression::GreaterThan(left, right) => (">", left, right),
+        Expression::GreaterThanOrEqual(left, right) => ("≥", left, right),
+        Expression::And(left, right) => ("∧", left, right),
+        Expression::Or(left, right) => ("∨", left, right),
+        Expression::Implies(left, right) => ("⇒", left, right),
+        Expression::Equivalent(left, right) => ("⇔", left, right),
+        _ => return None,
+    };
+    Some((operator, left, right))
+}
+
+struct Printer<'s> {
+    style: &'s Style,
+}
+
+impl<'s> Printer<'s> {
+    fn symbol(&self, symbol: &'static str) -> &'static str {
+        match ASCII.iter().find(|(_, unicode)| *unicode == symbol) {
+            Some((ascii, _)) if self.style.ascii => ascii,
+            _ => symbol,
+        }
+    }
+
+    fn program(&self, program: &Program) -> Doc {
+        let Program(declarations, name, parameters, body, specification) = program;
+        let declarations = declarations.sequence();
+        let mut docs = Vec::new();
+        for (index, declaration) in declarations.iter().enumerate() {
+            if index > 0 {
+                docs.push(Doc::Newline);
+                // procedures are set apart by blank lines
+                let previous = declarations[index - 1];
+                if is_procedure(previous) || is_procedure(declaration) {
+                    docs.push(Doc::Newline);
+                }
+            }
+            docs.push(self.declaration(declaration));
+        }
+        if !declarations.is_empty() {
+            docs.extend([Doc::Newline, Doc::Newline]);
+        }
+        let parameters = self.parameters(parameters, &Parameters::Empty);
+        docs.push(self.head(format!("program {name}"), parameters, specification, body));
+        Doc::Concat(docs)
+    }
+
+    fn declaration(&self, declaration: &Declaration) -> Doc {
+        match declaration {
+            Declaration::Variable(identifier, sort) => text(format!("var {identifier}:{sort};")),
+            Declaration::Procedure(name, in_params, out_params, body, specification) => {
+                let parameters = self.parameters(in_params, out_params);
+                self.head(format!("procedure {name}"), parameters, specification, body)
+            }
+            Declaration::Spanned(_, declaration) => self.declaration(declaration),
+        }
+    }
+
+    // the name and parameters of a procedure or program, then the specification, on the
+    // following lines if it doesn't fit, and the body; like those of conditions the
+    // continuation lines are indented twice to set them apart from the body
+    fn head(
+        &self,
+        head: String,
+        parameters: Doc,
+        specification: &Specification,
+        body: &Command,
+    ) -> Doc {
+        let mut clauses = Vec::new();
+        for (keyword, clause) in [
+            ("requires", &specification.requires),
+            ("ensures", &specification.ensures),
+        ] {
+            if let Some(clause) = clause {
+                clauses.extend([
+                    Doc::Line,
+                    text(format!("{keyword} ")),
+                    self.expression(clause),
+                ]);
+            }
+        }
+        Doc::Concat(vec![
+            group(Doc::Concat(vec![
+                text(format!("{head}(")),
+                parameters,
+                text(")"),
+                nest(nest(Doc::Concat(clauses))),
+            ])),
+            text(" "),
+            self.block(body),
+        ])
+    }
+
+    // on one line or with a line break after each comma and the semicolon
+    fn parameters(&self, in_params: &Parameters, out_params: &Parameters) -> Doc {
+        let list = |parameters: &Parameters| {
+            let mut docs = Vec::new();
+            for (index, (identifier, sort)) in parameters
+                .identifiers()
+                .into_iter()
+                .zip(parameters.sorts())
+                .enumerate()
+            {
+                if index > 0 {
+                    docs.extend([text(","), Doc::Line]);
+                }
+                docs.push(text(format!("{identifier}:{sort}")));
+            }
+            docs
+        };
+        let mut docs = list(in_params);
+        if !out_params.is_empty() {
+            docs.extend([text(";"), Doc::Line, text("ref ")]);
+            docs.extend(list(out_params));
+        }
+        group(nest(nest(Doc::Concat(docs))))
+    }
+
+    fn block(&self, command: &Command) -> Doc {
+        Doc::Concat(vec![
+            text("{"),
+            nest(Doc::Concat(vec![Doc::Newline, self.command(command)])),
+            Doc::Newline,
+            text("}"),
+        ])
+    }
+
+    fn command(&self, command: &Command) -> Doc {
+        match command {
+            Command::Assign(identifier, expression) => Doc::Concat(vec![
+                text(format!("{identifier} := ")),
+                self.expression(expression),
+                text(";"),
+            ]),
+            Command::Var(identifier, sort, rest) => Doc::Concat(vec![
+                text(format!("var {identifier}:{sort};")),
+                Doc::Newline,
+                self.command(rest),
+            ]),
+            // the scope of a var ends with the block it is declared in
+            Command::Sequence(first, rest) => {
+                let first = match first.unspanned() {
+                    Command::Var(..) => self.block(first),
+                    _ => self.command(first),
+                };
+                Doc::Concat(vec![first, Doc::Newline, self.command(rest)])
+            }
+            Command::IfElse(condition, if_branch, else_branch) => Doc::Concat(vec![
+                text("if "),
+                self.condition(condition),
+                text(" then "),
+                self.block(if_branch),
+                text(" else "),
+                self.block(else_branch),
+            ]),
+            Command::If(condition, if_branch) => Doc::Concat(vec![
+                text("if "),
+                self.condition(condition),
+                text(" then "),
+                self.block(if_branch),
+            ]),
+            Command::While(condition, body, invariant) => {
+                let mut docs = vec![text("while "), self.condition(condition)];
+                if let Some(invariant) = invariant {
+                    docs.extend([text(" invariant "), self.condition(invariant)]);
+                }
+                docs.extend([text(" do "), self.block(body)]);
+                Doc::Concat(docs)
+            }
+            Command::Call(name, expressions, variables, _) => {
+                let mut arguments = Vec::new();
+                for (index, expression) in expressions.sequence().iter().enumerate() {
+                    if index > 0 {
+                        arguments.extend([text(","), Doc::Line]);
+                    }
+                    arguments.push(self.expression(expression));
+                }
+                let variables = variables.sequence();
+                if !variables.is_empty() {
+                    arguments.push(text(";"));
+                    if !arguments.is_empty() {
+                        arguments.push(Doc::Line);
+                    }
+                    let variables: Vec<_> = variables.iter().map(ToString::to_string).collect();
+                    arguments.push(text(variables.join(", ")));
+                }
+                Doc::Concat(vec![
+                    text(format!("call {name}(")),
+                    group(nest(Doc::Concat(arguments))),
+                    text(");"),
+                ])
+            }
+            Command::Spanned(_, command) => self.command(command),
+        }
+    }
+
+    // the continuation lines of a condition are indented further than the block that
+    // follows it
+    fn condition(&self, expression: &Expression) -> Doc {
+        nest(self.expression(expression))
+    }
+
+    fn expression(&self, expression: &Expression) -> Doc {
+        match expression {
+            Expression::Value(value) => text(value.to_string()),
+            Expression::Variable(identifier) => text(identifier.to_string()),
+            // `-3` would be read as a negative numeral
+            Expression::Negative(operand)
+                if matches!(operand.unspanned(), Expression::Value(_)) =>
+            {
+                text(format!("-({operand})"))
+            }
+            Expression::Negative(operand) | Expression::Not(operand) => {
+                let operator = match expression {
+                    Expression::Negative(_) => "-",
+                    _ => self.symbol("¬"),
+                };
+                Doc::Concat(vec![
+                    text(operator),
+                    self.operand(operand, precedence(operand) < 8),
+                ])
+            }
+            Expression::Spanned(_, expression) => self.expression(expression),
+            _ => self.binary(expression),
+        }
+    }
+
+    // a chain of operators of the same precedence is one group, so that it is either on
+    // one line or has one operator at the start of each line
+    fn binary(&self, expression: &Expression) -> Doc {
+        let level = precedence(expression);
+        let associativity = associativity(level);
+        let (mut operators, mut operands) = (Vec::new(), Vec::new());
+        let mut next = expression;
+        while let Some((operator, left, right)) = binary(next).filter(|_| precedence(next) == level)
+        {
+            let (operand, rest) = match associativity {
+                Associativity::Right => (left, right),
+                _ => (right, left),
+            };
+            operators.push(operator);
+            operands.push(operand);
+            next = rest;
+            if associativity == Associativity::None {
+                break;
+            }
+        }
+        operands.push(next);
+        if associativity != Associativity::Right {
+            operators.reverse();
+            operands.reverse();
+        }
+        // operands of the same precedence left in the chain belong in parentheses, e.g.
+        // the right one of `a - (b - c)` or both of `(a = b) = c`
+        let mut operands = operands
+            .into_iter()
+            .map(|operand| self.operand(operand, precedence(operand) <= level));
+        let head = operands
+            .next()
+            .expect("a binary expression has two operands");
+        let mut rest = Vec::new();
+        for (operator, operand) in operators.into_iter().zip(operands) {
+            rest.extend([
+                Doc::Line,
+                text(format!("{} ", self.symbol(operator))),
+                operand,
+            ]);
+        }
+        group(Doc::Concat(vec![head, nest(Doc::Concat(rest))]))
+    }
+
+    fn operand(&self, expression: &Expression, parenthesized: bool) -> Doc {
+        if parenthesized {
+            Doc::Concat(vec![text("("), self.expression(expression), text(")")])
+        } else {
+            self.expression(expression)
+        }
+    }
+}
+
+fn is_procedure(declaration: &Declaration) -> bool {
+    matches!(declaration.unspanned(), Declaration::Procedure(..))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static PROGRAMS: [&str; 4] = [
+        include_str!("../programs/gcd.fsp"),
+        include_str!("../programs/division.fsp"),
+        include_str!("../programs/recursion.fsp"),
+        "procedure p(; ref r:Int) { r := 1; }
+program q(x:Int) {
+  { var t:Int; t := x; x := t; }
+  if x = 0 then if x < 0 then x := 1; else call p(; x);
+  while x ≤ 0 invariant x ≤ 0 ∨ x = 1 do x := x + 1;
+}",
+    ];
+
+    fn pretty(source: &str) -> Result<String, String> {
+        Ok(Expression::parse(source)?.pretty(&Style::default()))
+    }
+
+    #[test]
+    fn test_minimal_parentheses() -> Result<(), String> {
+        assert_eq!(pretty("((a+b)+c)")?, "a + b + c");
+        assert_eq!(pretty("a+(b+c)")?, "a + (b + c)");
+        assert_eq!(pretty("a-(b-c)*d")?, "a - (b - c) * d");
+        assert_eq!(pretty("(a ≤ b) = (c = d)")?, "(a ≤ b) = (c = d)");
+        assert_eq!(pretty("(a ∧ b) ∨ (c ∧ ¬(d))")?, "a ∧ b ∨ c ∧ ¬d");
+        assert_eq!(pretty("(a ⇒ b) ⇒ (c ⇒ d)")?, "(a ⇒ b) ⇒ c ⇒ d");
+        assert_eq!(
+            pretty("¬(a ∨ b) ⇔ -(x) % 2 < -(3)")?,
+            "¬(a ∨ b) ⇔ -x % 2 < -(3)"
+        );
+        assert_eq!(pretty("-(-3) - -3")?, "-(-3) - -3");
+        Ok(())
+    }
+
+    #[test]
+    fn test_line_width() -> Result<(), String> {
+        let source = "program p(x:Int, y:Int) requires 0 ≤ x ∧ 0 ≤ y ensures x = 0 {
+  while 0 < x ∧ x < 1000 ∧ y ≠ 0 do { x := x * (y + 100000) - y * (x + 200000) % 7; }
+}";
+        let program = Program::parse(source)?;
+        let style = Style {
+            width: 32,
+            ..Style::default()
+        };
+        assert_eq!(
+            program.pretty(&style),
+            "program p(x:Int, y:Int)
+    requires 0 ≤ x ∧ 0 ≤ y
+    ensures x = 0 {
+  while 0 < x
+      ∧ x < 1000
+      ∧ y ≠ 0 do {
+    x := x * (y + 100000)
+      - y * (x + 200000) % 7;
+  }
+}"
+        );
+        let style = Style {
+            indent: 4,
+            ascii: true,
+            ..Style::default()
+        };
+        assert_eq!(
+            program.pretty(&style),
+            "program p(x:Int, y:Int) requires 0 <= x && 0 <= y ensures x = 0 {
+    while 0 < x && x < 1000 && y != 0 do {
+        x := x * (y + 100000) - y * (x + 200000) % 7;
+    }
+}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<(), String> {
+        for source in PROGRAMS {
+            let program = Program::parse(source)?;
+            for width in [1, 20, 80] {
+                for ascii in [false, true] {
+                    let style = Style {
+                        width,
+                        ascii,
+                        ..Style::default()
+                    };
+                    let pretty = program.pretty(&style);
+                    assert_eq!(Program::parse(&pretty)?, program, "{pretty}");
+                    assert_eq!(Program::parse(&pretty)?.pretty(&style), pretty);
+                }
+            }
+        }
+        Ok(())
+    }
+}
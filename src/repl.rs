@@ -0,0 +1,304 @@
+use std::io::{self, BufRead, Write};
+
+use crate::{
+    diagnostic::{Diagnostic, Diagnostics},
+    eval::{Address, Environment, ProcedureEnvironment, Store, VariableEnvironment},
+    typecheck::{ProcedureTypingSet, VariableTypingMap},
+    types::*,
+};
+
+static HELP: &str = "inputs are declarations (var x:Int; procedure p(..) {..}), commands (x := 1;)
+or expressions (x+1), which are evaluated and printed with their sort
+  :store        print the store
+  :env          print the variable and procedure environment
+  :type EXPR    print the sort of EXPR without evaluating it
+  :reset        forget all declarations and values
+  :quit         leave
+";
+
+// the declarations and store shared by all inputs of a session; the typing context and
+// environment borrow from the text of the declarations, so they are rebuilt from it for
+// every input
+#[derive(Default)]
+pub struct Session {
+    declarations: Vec<String>,
+    store: Store,
+    top: Address,
+}
+
+// what the declarations of a session bind
+struct Context<'a> {
+    variable_typings: VariableTypingMap<'a>,
+    procedure_typings: ProcedureTypingSet<'a>,
+    environment: Environment<'a>,
+}
+
+impl<'a> Context<'a> {
+    fn new() -> Context<'a> {
+        Context {
+            variable_typings: VariableTypingMap::new(),
+            procedure_typings: ProcedureTypingSet::new(),
+            environment: (VariableEnvironment::init(), ProcedureEnvironment::init()),
+        }
+    }
+
+    // the context and store after the declarations in `source`, or the error to print
+    fn declare(
+        &self,
+        source: &'a str,
+        store: Store,
+        top: Address,
+    ) -> Result<(Context<'a>, Store, Address), String> {
+        let declarations =
+            Declarations::parse(source).map_err(|error| format!("{}\n", error.render(source)))?;
+
+        let mut diagnostics = Diagnostics::new();
+        let (variable_typings, procedure_typings, declarations) = Declaration::typecheck_all(
+            &declarations.sequence(),
+            &self.variable_typings,
+            &self.procedure_typings,
+            &mut diagnostics,
+        );
+        if !diagnostics.is_empty() {
+            return Err(format!("{}\n", diagnostics.render(source)));
+        }
+
+        let declarations: Vec<_> = declarations.iter().collect();
+        let (environment, store, top) =
+            Declaration::eval_all(&declarations, &self.environment, store, top)
+                .map_err(|error| format!("{}\n", error.render(source)))?;
+        let context = Context {
+            variable_typings,
+            procedure_typings,
+            environment,
+        };
+        Ok((context, store, top))
+    }
+}
+
+// the context of the declarations that succeeded so far, which succeed again and
+// allocate the same addresses
+fn context(declarations: &[String]) -> Context<'_> {
+    let (mut context, mut store, mut top) = (Context::new(), Store::default(), 0);
+    for source in declarations {
+        (context, store, top) = context
+            .declare(source, store, top)
+            .expect("declarations that succeeded once succeed again");
+    }
+    context
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session::default()
+    }
+
+    // runs one input and returns what should be printed, inputs that fail leave the
+    // session unchanged
+    pub fn input(&mut self, input: &str) -> String {
+        let input = input.trim();
+        match input.split_once(char::is_whitespace).unwrap_or((input, "")) {
+            ("", _) => String::new(),
+            (":store", _) => format!("{}\n", self.store),
+            (":env", _) => {
+                let environment = context(&self.declarations).environment;
+                format!("{}\n{}\n", environment.0, environment.1)
+            }
+            (":type", expression) => self.type_of(expression.trim()),
+            (":reset", _) => {
+                *self = Session::new();
+                String::new()
+            }
+            (":help", _) => HELP.into(),
+            (meta, _) if meta.starts_with(':') => format!("unknown command {meta}, try :help\n"),
+            ("var" | "procedure", _) => self.declare(input),
+            _ => self.execute(input),
+        }
+    }
+
+    fn declare(&mut self, source: &str) -> String {
+        let context = context(&self.declarations);
+        match context.declare(source, self.store.clone(), self.top) {
+            Ok((_, store, top)) => {
+                self.store = store;
+                self.top = top;
+                self.declarations.push(source.into());
+                String::new()
+            }
+            Err(error) => error,
+        }
+    }
+
+    fn execute(&mut self, source: &str) -> String {
+        match (Command::parse(source), Expression::parse(source)) {
+            (Ok(command), _) => self.run_command(source, command),
+            (_, Ok(expression)) => self.evaluate(source, expression),
+            // report whichever reading got further into the input
+            (Err(command_error), Err(expression_error)) => {
+                let error = if start(&expression_error) > start(&command_error) {
+                    expression_error
+                } else {
+                    command_error
+                };
+                format!("{}\n", error.render(source))
+            }
+        }
+    }
+
+    fn run_command(&mut self, source: &str, command: Command) -> String {
+        let context = context(&self.declarations);
+        let mut diagnostics = Diagnostics::new();
+        let command = command.typecheck(
+            &context.variable_typings,
+            &context.procedure_typings,
+            &mut diagnostics,
+        );
+        if !diagnostics.is_empty() {
+            return format!("{}\n", diagnostics.render(source));
+        }
+        match command.eval(&self.store, &context.environment, self.top) {
+            Ok(store) => {
+                self.store = store;
+                String::new()
+            }
+            Err(error) => format!("{}\n", error.render(source)),
+        }
+    }
+
+    fn evaluate(&self, source: &str, expression: Expression) -> String {
+        let context = context(&self.declarations);
+        let mut diagnostics = Diagnostics::new();
+        let sort = expression.typecheck(&context.variable_typings, &mut diagnostics);
+        if !diagnostics.is_empty() {
+            return format!("{}\n", diagnostics.render(source));
+        }
+        match expression.eval(&self.store, &context.environment) {
+            Ok(value) => format!("{value} : {sort}\n"),
+            Err(error) => format!("{}\n", error.render(source)),
+        }
+    }
+
+    fn type_of(&self, source: &str) -> String {
+        let expression = match Expression::parse(source) {
+            Ok(expression) => expression,
+            Err(error) => return format!("{}\n", error.render(source)),
+        };
+        let context = context(&self.declarations);
+        let mut diagnostics = Diagnostics::new();
+        let sort = expression.typecheck(&context.variable_typings, &mut diagnostics);
+        if diagnostics.is_empty() {
+            format!("{sort}\n")
+        } else {
+            format!("{}\n", diagnostics.render(source))
+        }
+    }
+}
+
+fn start(diagnostic: &Diagnostic<String>) -> usize {
+    diagnostic.span.map_or(0, |span| span.start)
+}
+
+pub fn run(input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut session = Session::new();
+    let mut lines = input.lines();
+    write!(output, "type :help for help\n> ")?;
+    output.flush()?;
+    while let Some(line) = lines.next() {
+        let mut text = line?;
+        // procedures usually span several lines, keep reading while a block is open
+        while text.matches('{').count() > text.matches('}').count() {
+            write!(output, "... ")?;
+            output.flush()?;
+            match lines.next() {
+                Some(line) => {
+                    text.push('\n');
+                    text.push_str(&line?);
+                }
+                None => break,
+            }
+        }
+        if text.trim() == ":quit" {
+            break;
+        }
+        write!(output, "{}> ", session.input(&text))?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session() {
+        let mut session = Session::new();
+        assert_eq!(session.input("var x:Int;"), "");
+        assert_eq!(session.input("var b:Bool; var y:Int;"), "");
+        assert_eq!(session.input("x := 3*4;"), "");
+        assert_eq!(session.input("x"), "12 : Int\n");
+        assert_eq!(session.input("x ≤ 3"), "false : Bool\n");
+        assert_eq!(session.input(":type x + 1"), "Int\n");
+        assert_eq!(session.input(":store"), "{0 ↦ 12, 1 ↦ false, 2 ↦ 0}\n");
+        assert_eq!(session.input("b = false"), "true : Bool\n");
+        assert_eq!(session.input(":env"), "{x ↦ 0, b ↦ 1, y ↦ 2}\n{}\n");
+        assert_eq!(session.input(":reset"), "");
+        assert_eq!(session.input(":env"), "{}\n{}\n");
+    }
+
+    #[test]
+    fn test_session_procedures() {
+        let mut session = Session::new();
+        session.input("var q:Int; var r:Int;");
+        let div = "procedure div(a:Int, b:Int; ref q:Int, r:Int) {
+  q := 0; r := a;
+  while b ≤ r do { q := q+1; r := r-b; }
+}";
+        assert_eq!(session.input(div), "");
+        assert_eq!(session.input("call div(17, 5; q, r);"), "");
+        assert_eq!(session.input("q*5 + r"), "17 : Int\n");
+        assert_eq!(session.input("q"), "3 : Int\n");
+        assert_eq!(
+            session.input(":env"),
+            "{q ↦ 0, r ↦ 1}\n{div(Int,Int;Int,Int)}\n"
+        );
+    }
+
+    #[test]
+    fn test_session_errors() {
+        let mut session = Session::new();
+        session.input("var x:Int;");
+        assert_eq!(
+            session.input("x := true;"),
+            "error: expected expression of sort Int but found Bool\n --> 1:6\n  |\n1 | x := true;\n  |      ^^^^\n"
+        );
+        assert_eq!(
+            session.input("x / 0"),
+            "error: division by 0\n --> 1:1\n  |\n1 | x / 0\n  | ^^^^^\n"
+        );
+        assert_eq!(
+            session.input("x := 1 +;"),
+            "error: expected expression but found `;`\n --> 1:9\n  |\n1 | x := 1 +;\n  |         ^\n"
+        );
+        // a declaration that fails doesn't declare anything
+        session.input("var y:Int; var z:Int; procedure p(; ref a:Int) { a := b; }");
+        assert!(session
+            .input("y")
+            .starts_with("error: identifier y is not defined"));
+        assert_eq!(session.input("x"), "0 : Int\n");
+        // only the declarations that succeeded are kept
+        assert_eq!(session.declarations, ["var x:Int;"]);
+    }
+
+    #[test]
+    fn test_run() -> io::Result<()> {
+        let input = "var x:Int;\nprocedure inc(; ref a:Int) {\n  a := a+1;\n}\ncall inc(;x);\nx\n:quit\nx\n";
+        let mut output = Vec::new();
+        run(input.as_bytes(), &mut output)?;
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "type :help for help\n> > ... ... > > 1 : Int\n> "
+        );
+        Ok(())
+    }
+}
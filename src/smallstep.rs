@@ -0,0 +1,291 @@
+use std::rc::Rc;
+
+use crate::{
+    diagnostic::Diagnostic,
+    eval::{store_arguments, Address, Environment, RuntimeError, Store},
+    integer::Unbounded,
+    types::*,
+};
+
+// what is left to run of a program
+#[derive(Clone)]
+pub enum Term<'a> {
+    // a command together with the environment and next free address it runs in
+    Command(Command<'a>, Rc<Environment<'a>>, Address),
+    Sequence(Box<Term<'a>>, Box<Term<'a>>),
+}
+
+// ⟨C, σ⟩, or just σ once the program has terminated
+#[derive(Clone)]
+pub struct Configuration<'a> {
+    pub term: Option<Term<'a>>,
+    pub store: Store,
+}
+
+impl<'a> Program<'a> {
+    // the configuration the body starts in and the addresses of the parameters
+    pub fn initial_configuration(
+        &self,
+        value_sequence: Vec<Value>,
+    ) -> Result<(Configuration<'a>, Vec<Address>), Diagnostic<RuntimeError<'a>>> {
+        let (store, environment, top, argument_sequence) =
+            self.enter(value_sequence, Store::default())?;
+        let term = Term::Command(self.3.clone(), Rc::new(environment), top);
+        let configuration = Configuration {
+            term: Some(term),
+            store,
+        };
+        Ok((configuration, argument_sequence))
+    }
+
+    // the same as `Program::eval`, by following the transition sequence to its end
+    pub fn eval_small_step(
+        &self,
+        value_sequence: Vec<Value>,
+    ) -> Result<Vec<Value>, Diagnostic<RuntimeError<'a>>> {
+        let (configuration, argument_sequence) = self.initial_configuration(value_sequence)?;
+        let mut store = configuration.store.clone();
+        for configuration in configuration.transitions() {
+            store = configuration?.store;
+        }
+        Ok(store.lookup_sequence(&argument_sequence))
+    }
+}
+
+impl<'a> Configuration<'a> {
+    pub fn is_terminal(&self) -> bool {
+        self.term.is_none()
+    }
+
+    // ⟨C, σ⟩ → ⟨C', σ'⟩ or ⟨C, σ⟩ → σ', terminal configurations don't step
+    pub fn step(&self) -> Option<Result<Configuration<'a>, Diagnostic<RuntimeError<'a>>>> {
+        Some(self.term.as_ref()?.step(&self.store))
+    }
+
+    // every configuration after this one, ending with the terminal one or an error
+    pub fn transitions(self) -> Transitions<'a> {
+        Transitions {
+            configuration: Some(self),
+        }
+    }
+}
+
+pub struct Transitions<'a> {
+    configuration: Option<Configuration<'a>>,
+}
+
+impl<'a> Iterator for Transitions<'a> {
+    type Item = Result<Configuration<'a>, Diagnostic<RuntimeError<'a>>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.configuration.take()?.step()?;
+        if let Ok(configuration) = &next {
+            self.configuration = Some(configuration.clone());
+        }
+        Some(next)
+    }
+}
+
+impl<'a> Term<'a> {
+    fn step(&self, store: &Store) -> Result<Configuration<'a>, Diagnostic<RuntimeError<'a>>> {
+        match self {
+            Term::Command(command, environment, top) => command.step(store, environment, *top),
+            Term::Sequence(first, second) => {
+                let Configuration { term, store } = first.step(store)?;
+                let term = match term {
+                    Some(first) => Term::Sequence(first.into(), second.clone()),
+                    None => (**second).clone(),
+                };
+                Ok(Configuration {
+                    term: Some(term),
+                    store,
+                })
+            }
+        }
+    }
+}
+
+impl<'a> Command<'a> {
+    fn step(
+        &self,
+        store: &Store,
+        environment: &Rc<Environment<'a>>,
+        top: Address,
+    ) -> Result<Configuration<'a>, Diagnostic<RuntimeError<'a>>> {
+        let running = |command: &Command<'a>| Configuration {
+            term: Some(Term::Command(command.clone(), environment.clone(), top)),
+            store: store.clone(),
+        };
+        let terminated = || Configuration {
+            term: None,
+            store: store.clone(),
+        };
+        match self {
+            Command::Assign(identifier, expression) => {
+                let address = environment.0.lookup(*identifier)?;
+                let value = expression.eval(store, environment)?;
+                Ok(Configuration {
+                    term: None,
+                    store: store.update(address, value),
+                })
+            }
+            Command::Var(identifier, sort, command) => {
+                let environment = (
+                    environment.0.update(identifier, &top),
+                    environment.1.clone(),
+                );
+                Ok(Configuration {
+                    term: Some(Term::Command(
+                        (**command).clone(),
+                        Rc::new(environment),
+                        top + 1,
+                    )),
+                    store: store.allocate(top, *sort),
+                })
+            }
+            // ⟨C₁;C₂, σ⟩ → ⟨C₁';C₂, σ'⟩ if ⟨C₁, σ⟩ → ⟨C₁', σ'⟩, and → ⟨C₂, σ'⟩ if ⟨C₁, σ⟩ → σ'
+            Command::Sequence(first, second) => {
+                let first = Term::Command((**first).clone(), environment.clone(), top);
+                let second = Term::Command((**second).clone(), environment.clone(), top);
+                Term::Sequence(first.into(), second.into()).step(store)
+            }
+            Command::IfElse(expression, if_branch, else_branch) => {
+                if expression.eval(store, environment)? == Value::True {
+                    Ok(running(if_branch))
+                } else {
+                    Ok(running(else_branch))
+                }
+            }
+            Command::If(expression, if_branch) => {
+                if expression.eval(store, environment)? == Value::True {
+                    Ok(running(if_branch))
+                } else {
+                    Ok(terminated())
+                }
+            }
+            // ⟨while E do C, σ⟩ → ⟨C while E do C, σ⟩ if E holds in σ
+            Command::While(expression, body, _) => {
+                if expression.eval(store, environment)? == Value::True {
+                    let body = Term::Command((**body).clone(), environment.clone(), top);
+                    let rest = Term::Command(self.clone(), environment.clone(), top);
+                    Ok(Configuration {
+                        term: Some(Term::Sequence(body.into(), rest.into())),
+                        store: store.clone(),
+                    })
+                } else {
+                    Ok(terminated())
+                }
+            }
+            Command::Call(identifier, expressions, variables, signature) => {
+                let Some(signature) = signature else {
+                    return Err(RuntimeError::UnannotatedCall(*identifier).into());
+                };
+                let (store, as1, top) =
+                    store_arguments(expressions, store, environment, top, &Unbounded)?;
+                let as2 = variables.eval(environment)?;
+                let procedure = environment.1.lookup(signature)?;
+                let environment = procedure.environment(&as1, &as2)?;
+                Ok(Configuration {
+                    term: Some(Term::Command(
+                        procedure.body().clone(),
+                        Rc::new(environment),
+                        top,
+                    )),
+                    store,
+                })
+            }
+            // spans aren't a step of their own
+            Command::Spanned(span, command) => command
+                .step(store, environment, top)
+                .map_err(|error| error.or_span(*span)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gcd::{a_b_gcd_parameter_sequence, generate_gcd};
+
+    use super::*;
+
+    #[test]
+    fn test_small_step_agrees_with_eval_on_gcd() -> Result<(), String> {
+        let program = generate_gcd(a_b_gcd_parameter_sequence());
+        let annotated_program = program.typecheck()?;
+        for a in 0..=12 {
+            for b in [1, 4, 12, 24, 60] {
+                let input_sequence = vec![a.into(), b.into(), 0.into(), 0.into()];
+                assert_eq!(
+                    annotated_program.eval_small_step(input_sequence.clone())?,
+                    annotated_program.eval(input_sequence)?
+                );
+            }
+        }
+
+        let program = Program::parse(include_str!("../programs/gcd.fsp"))?;
+        let annotated_program = program.typecheck()?;
+        let input_sequence = vec![24.into(), 60.into(), 0.into(), 0.into()];
+        assert_eq!(
+            annotated_program.eval_small_step(input_sequence)?,
+            vec![24.into(), 60.into(), 12.into(), 2.into()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_small_step_agrees_with_eval_on_recursion() -> Result<(), String> {
+        let program = Program::parse(include_str!("../programs/recursion.fsp"))?;
+        let annotated_program = program.typecheck()?;
+        for n in 0..8 {
+            let input_sequence = vec![n.into(), 0.into(), Value::False];
+            assert_eq!(
+                annotated_program.eval_small_step(input_sequence.clone())?,
+                annotated_program.eval(input_sequence)?
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_transition_sequence() -> Result<(), String> {
+        let program = Program::parse("program p(a:Int) {\n  while ¬(a = 2) do a := a+1;\n}")?;
+        let annotated_program = program.typecheck()?;
+        let (configuration, _) = annotated_program.initial_configuration(vec![1.into()])?;
+        let transitions = configuration
+            .transitions()
+            .map(|configuration| configuration.map(|configuration| configuration.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(
+            transitions,
+            vec![
+                "⟨a:=(a+1);\nwhile ¬(a=2) do {\n  a:=(a+1);\n}, {0 ↦ 1}⟩",
+                "⟨while ¬(a=2) do {\n  a:=(a+1);\n}, {0 ↦ 2}⟩",
+                "{0 ↦ 2}",
+            ]
+        );
+
+        // the first command of a sequence steps in place, without a step of its own to
+        // take the sequence apart
+        let program = Program::parse("program p(a:Int) {\n  a := a+1;\n  a := a*2;\n}")?;
+        let annotated_program = program.typecheck()?;
+        let (configuration, _) = annotated_program.initial_configuration(vec![1.into()])?;
+        let transitions = configuration
+            .transitions()
+            .map(|configuration| configuration.map(|configuration| configuration.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(transitions, vec!["⟨a:=(a*2);, {0 ↦ 2}⟩", "{0 ↦ 4}"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_small_step_error_span() -> Result<(), String> {
+        let source = "program p(a:Int, b:Int) {\n  a := 1 + a / b;\n}";
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        let error = annotated_program
+            .eval_small_step(vec![1.into(), 0.into()])
+            .unwrap_err();
+        assert_eq!(error.error, RuntimeError::DivisionByZero);
+        assert_eq!(error.span, Some(Span { start: 37, end: 42 }));
+        Ok(())
+    }
+}
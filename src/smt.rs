@@ -0,0 +1,196 @@
+use crate::{
+    hoare::VerificationCondition,
+    typecheck::{BOOL_SORT, INT_SORT},
+    types::*,
+};
+
+// the SMT-LIB reserved words, command names among them, and the symbols of the core and
+// integer theories that are also identifiers of a program; variables with these names
+// get a `'` appended, which no identifier of a program contains
+static RESERVED: [&str; 29] = [
+    "_",
+    "as",
+    "exists",
+    "forall",
+    "let",
+    "match",
+    "par",
+    "BINARY",
+    "DECIMAL",
+    "HEXADECIMAL",
+    "NUMERAL",
+    "STRING",
+    "assert",
+    "echo",
+    "exit",
+    "pop",
+    "push",
+    "reset",
+    "true",
+    "false",
+    "not",
+    "and",
+    "or",
+    "xor",
+    "ite",
+    "distinct",
+    "div",
+    "mod",
+    "abs",
+];
+
+// one check per condition, each in its own scope, a condition holds if the solver
+// answers unsat to its check
+pub fn script(conditions: &[VerificationCondition]) -> String {
+    let mut script =
+        String::from("; every check-sat answered with unsat is a condition that holds\n");
+    for condition in conditions {
+        script.push_str(&format!("\n(push 1)\n{}(pop 1)\n", condition.smtlib()));
+    }
+    script
+}
+
+impl<'a> VerificationCondition<'a> {
+    // a condition holds for all values of its variables if its negation is unsatisfiable,
+    // integers are unbounded so overflows of the evaluation aren't taken into account
+    pub fn smtlib(&self) -> String {
+        let mut script = format!("; {}\n", self.obligation);
+        let mut sorts = Vec::new();
+        for (_, sort) in &self.variables {
+            if *sort != INT_SORT && *sort != BOOL_SORT && !sorts.contains(sort) {
+                script.push_str(&format!("(declare-sort {} 0)\n", symbol(sort.0)));
+                sorts.push(*sort);
+            }
+        }
+        for (identifier, sort) in &self.variables {
+            let (identifier, sort) = (symbol(*identifier), symbol(sort.0));
+            script.push_str(&format!("(declare-const {identifier} {sort})\n"));
+        }
+        script.push_str(&format!(
+            "(assert (not {}))\n(check-sat)\n",
+            self.formula.smtlib()
+        ));
+        script
+    }
+}
+
+impl<'a> Expression<'a> {
+    pub fn smtlib(&self) -> String {
+        let application = |operator: &str, left: &Expression<'a>, right: &Expression<'a>| {
+            format!("({operator} {} {})", left.smtlib(), right.smtlib())
+        };
+        match self {
+            Expression::Value(Value::Numeral(numeral)) if numeral.is_negative() => {
+                format!("(- {})", numeral.abs())
+            }
+            Expression::Value(value) => value.to_string(),
+            Expression::Variable(identifier) => symbol(*identifier),
+            Expression::Sum(left, right) => application("+", left, right),
+            Expression::Difference(left, right) => application("-", left, right),
+            Expression::Product(left, right) => application("*", left, right),
+            // div rounds towards negative infinity for positive divisors, the evaluation
+            // truncates towards 0
+            Expression::Division(left, right) => {
+                let (left, right) = (left.smtlib(), right.smtlib());
+                format!("(ite (<= 0 {left}) (div {left} {right}) (- (div (- {left}) {right})))")
+            }
+            // mod is never negative, the remainder has the sign of the dividend
+            Expression::Remainder(left, right) => {
+                let (left, right) = (left.smtlib(), right.smtlib());
+                format!("(ite (<= 0 {left}) (mod {left} {right}) (- (mod (- {left}) {right})))")
+            }
+            Expression::Equal(left, right) | Expression::Equivalent(left, right) => {
+                application("=", left, right)
+            }
+            Expression::NotEqual(left, right) => application("distinct", left, right),
+            Expression::LessThan(left, right) => application("<", left, right),
+            Expression::LessThanOrEqual(left, right) => application("<=", left, right),
+            Expression::GreaterThan(left, right) => application(">", left, right),
+            Expression::GreaterThanOrEqual(left, right) => application(">=", left, right),
+            Expression::And(left, right) => application("and", left, right),
+            Expression::Or(left, right) => application("or", left, right),
+            Expression::Implies(left, right) => application("=>", left, right),
+            Expression::Negative(expression) => format!("(- {})", expression.smtlib()),
+            Expression::Not(expression) => format!("(not {})", expression.smtlib()),
+            Expression::Spanned(_, expression) => expression.smtlib(),
+        }
+    }
+}
+
+// fresh variables contain a `'`, which only quoted symbols may
+fn symbol(identifier: Identifier) -> String {
+    let name = identifier.to_string();
+    let simple = |c: char| c.is_ascii_alphanumeric() || "~!@$%^&*_-+=<>.?/".contains(c);
+    if RESERVED.contains(&name.as_str()) {
+        format!("|{name}'|")
+    } else if name.chars().all(simple) && !name.starts_with(|c: char| c.is_ascii_digit()) {
+        name
+    } else {
+        format!("|{name}|")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expression() -> Result<(), String> {
+        let expression = Expression::parse("¬(x ≤ -3) ∧ y = x/(0-2) ∨ b")?;
+        assert_eq!(
+            expression.smtlib(),
+            "(or (and (not (<= x (- 3))) (= y (ite (<= 0 x) (div x (- 0 2)) (- (div (- x) (- 0 2)))))) b)"
+        );
+        let expression = Expression::parse("x % 2 ≠ 0 ⇒ x > 0 ⇔ x < 1")?;
+        assert_eq!(
+            expression.smtlib(),
+            "(= (=> (distinct (ite (<= 0 x) (mod x 2) (- (mod (- x) 2))) 0) (> x 0)) (< x 1))"
+        );
+        assert_eq!(symbol("x'12".into()), "|x'12|");
+        assert_eq!(symbol("div".into()), "|div'|");
+        assert_eq!(symbol("_".into()), "|_'|");
+        assert_eq!(symbol("push".into()), "|push'|");
+        Ok(())
+    }
+
+    #[test]
+    fn test_declarations() -> Result<(), String> {
+        let source = "var s:Set;
+program p(b:Bool, t:Set, n:Int) ensures b ∨ s = t ∨ 0 ≤ n {
+  n := 1;
+}";
+        let conditions = Program::parse(source)?.verification_conditions()?;
+        assert_eq!(
+            conditions[0].smtlib(),
+            "; program p meets its specification
+(declare-sort Set 0)
+(declare-const b Bool)
+(declare-const s Set)
+(declare-const t Set)
+(assert (not (or (or b (= s t)) (<= 0 1))))
+(check-sat)
+"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_golden_scripts() -> Result<(), String> {
+        // regenerate with `cargo run -- smt programs/NAME.fsp > programs/NAME.smt2`
+        let programs = [
+            (
+                include_str!("../programs/division.fsp"),
+                include_str!("../programs/division.smt2"),
+            ),
+            (
+                include_str!("../programs/recursion.fsp"),
+                include_str!("../programs/recursion.smt2"),
+            ),
+        ];
+        for (source, golden) in programs {
+            let conditions = Program::parse(source)?.verification_conditions()?;
+            assert_eq!(script(&conditions), golden);
+        }
+        Ok(())
+    }
+}
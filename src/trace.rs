@@ -0,0 +1,129 @@
+use crate::{
+    eval::{Address, Store, VariableEnvironment},
+    types::*,
+};
+
+// what an evaluation did, in the order it happened
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Trace<'a>(pub Vec<Event<'a>>);
+
+// `depth` is the number of commands and calls the event is nested in
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event<'a> {
+    // recorded when the command starts, the events it causes follow it one level deeper
+    Command {
+        command: String,
+        top: Address,
+        environment: VariableEnvironment<'a>,
+        changes: Vec<Change>,
+        depth: usize,
+    },
+    Call {
+        name: Identifier<'a>,
+        in_addresses: Vec<Address>,
+        out_addresses: Vec<Address>,
+        depth: usize,
+    },
+    Return {
+        name: Identifier<'a>,
+        in_addresses: Vec<Address>,
+        out_addresses: Vec<Address>,
+        depth: usize,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub address: Address,
+    // none if the address wasn't written, in a strict store
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+impl Change {
+    // the addresses whose value differs between the two stores
+    pub fn between(before: &Store, after: &Store) -> Vec<Change> {
+        let mut addresses: Vec<_> = before.map.keys().chain(after.map.keys()).collect();
+        addresses.sort();
+        addresses.dedup();
+        addresses
+            .into_iter()
+            .filter(|address| before.lookup(address) != after.lookup(address))
+            .map(|address| Change {
+                address: *address,
+                before: before.lookup(address).cloned(),
+                after: after.lookup(address).cloned(),
+            })
+            .collect()
+    }
+}
+
+impl<'a> Event<'a> {
+    pub fn depth(&self) -> usize {
+        match self {
+            Event::Command { depth, .. }
+            | Event::Call { depth, .. }
+            | Event::Return { depth, .. } => *depth,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_log() -> Result<(), String> {
+        let source = "procedure inc(a:Int; ref b:Int) {
+  b := a+1;
+}
+program p(x:Int, y:Int) {
+  if x ≤ 1 then call inc(x; y);
+  x := 0;
+}";
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        let (result, trace) = annotated_program.eval_traced(vec![1.into(), 0.into()]);
+        assert_eq!(result?, vec![0.into(), 2.into()]);
+        assert_eq!(
+            trace.to_string(),
+            "if (x≤1) then { | top 2 | {x ↦ 0, y ↦ 1} | 1: 0 → 2, 2: ? → 1
+  call inc(x;y); | top 2 | {x ↦ 0, y ↦ 1} | 1: 0 → 2, 2: ? → 1
+    call inc in (2) out (1)
+      b:=(a+1); | top 3 | {b ↦ 1, a ↦ 2} | 1: 0 → 2
+    return inc in (2) out (1)
+x:=0; | top 2 | {x ↦ 0, y ↦ 1} | 0: 1 → 0
+"
+        );
+        assert_eq!(trace.0.len(), 6);
+        assert_eq!(
+            trace.0[3],
+            Event::Command {
+                command: "b:=(a+1);".into(),
+                top: 3,
+                environment: VariableEnvironment::init()
+                    .update(&"a".into(), &2)
+                    .update(&"b".into(), &1),
+                changes: vec![Change {
+                    address: 1,
+                    before: Some(0.into()),
+                    after: Some(2.into())
+                }],
+                depth: 3
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_trace_until_error() -> Result<(), String> {
+        let source = "program p(x:Int) {\n  x := x-1;\n  x := 1/x;\n}";
+        let annotated_program = Program::parse(source)?.typecheck()?;
+        let (result, trace) = annotated_program.eval_traced(vec![1.into()]);
+        assert!(result.is_err());
+        assert_eq!(
+            trace.to_string(),
+            "x:=(x-1); | top 1 | {x ↦ 0} | 0: 1 → 0\nx:=(1/x); | top 1 | {x ↦ 0}\n"
+        );
+        Ok(())
+    }
+}
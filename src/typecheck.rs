@@ -1,78 +1,257 @@
-use crate::types::*;
-use std::collections::{HashMap, HashSet};
+use crate::{
+    diagnostic::{Diagnostic, Diagnostics},
+    hoare::free_variables,
+    types::*,
+};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
 
-static INT_SORT: Sort<'static> = Sort(Identifier("Int"));
-static BOOL_SORT: Sort<'static> = Sort(Identifier("Bool"));
+pub static INT_SORT: Sort<'static> = Sort(Identifier("Int", None));
+pub static BOOL_SORT: Sort<'static> = Sort(Identifier("Bool", None));
+// sort of expressions that failed to typecheck, compatible with every sort so that
+// one mistake is reported only once
+pub static ERROR_SORT: Sort<'static> = Sort(Identifier("<error>", None));
 
 pub type Signature<'a> = (Identifier<'a>, (Vec<Sort<'a>>, Vec<Sort<'a>>));
-type VariableTypingMap<'a> = HashMap<Identifier<'a>, Sort<'a>>;
-type ProcedureTypingSet<'a> = HashSet<Signature<'a>>;
+pub(crate) type VariableTypingMap<'a> = HashMap<Identifier<'a>, Sort<'a>>;
+pub(crate) type ProcedureTypingSet<'a> = HashSet<Signature<'a>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeError<'a> {
+    UndefinedIdentifier(Identifier<'a>),
+    DuplicateParameter(Identifier<'a>),
+    SortMismatch {
+        expected: Sort<'a>,
+        found: Sort<'a>,
+    },
+    NoMatchingSignature {
+        name: Identifier<'a>,
+        in_sorts: Vec<Sort<'a>>,
+        out_sorts: Vec<Sort<'a>>,
+    },
+    NonBoolCondition(Sort<'a>),
+    // a variable named like the input value `old_x` of a parameter `x` that a
+    // postcondition of a bounded check refers to
+    AmbiguousOld(Identifier<'a>),
+    // a check of `Lints` at level error
+    Aliasing(Aliasing<'a>),
+}
+
+impl std::error::Error for TypeError<'_> {}
+
+// a variable a call can reach through more than one of its parameters, so that what the
+// call does depends on the order the procedure assigns them in
+#[derive(Debug, Clone, PartialEq)]
+pub enum Aliasing<'a> {
+    // passed as more than one ref argument
+    DuplicateOutput(Identifier<'a>),
+    // passed as a ref argument and read by an in argument
+    OutputInInput(Identifier<'a>),
+}
+
+impl<'a> Aliasing<'a> {
+    // names the check, e.g. for `Lints::set`
+    pub fn code(&self) -> &'static str {
+        match self {
+            Aliasing::DuplicateOutput(_) => "duplicate-output",
+            Aliasing::OutputInInput(_) => "output-in-input",
+        }
+    }
+}
+
+// the aliasing findings below level error, with their level
+pub type Findings<'a> = Vec<(Level, Diagnostic<Aliasing<'a>>)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Note,
+    Warning,
+    Error,
+}
+
+// how seriously each aliasing check is taken
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lints {
+    pub duplicate_output: Level,
+    pub output_in_input: Level,
+}
+
+// duplicate outputs are warned about, outputs that are also inputs only noted since
+// e.g. `call div(a, b; c, a)` is a common way to update a variable
+impl Default for Lints {
+    fn default() -> Self {
+        Lints {
+            duplicate_output: Level::Warning,
+            output_in_input: Level::Note,
+        }
+    }
+}
+
+impl Lints {
+    pub fn level(&self, aliasing: &Aliasing) -> Level {
+        match aliasing {
+            Aliasing::DuplicateOutput(_) => self.duplicate_output,
+            Aliasing::OutputInInput(_) => self.output_in_input,
+        }
+    }
+
+    // false if no check has that code
+    pub fn set(&mut self, code: &str, level: Level) -> bool {
+        match code {
+            "duplicate-output" => self.duplicate_output = level,
+            "output-in-input" => self.output_in_input = level,
+            _ => return false,
+        }
+        true
+    }
+}
 
 impl<'a> Program<'a> {
-    pub fn typecheck(&self) -> Result<Program, String> {
-        let Program(declarations, identifier, parameters, body) = self;
-        let (mut variable_typings, procedure_typings, declarations) = declarations.typecheck()?;
-        let (variable_typings_1, _) = parameters.typecheck()?;
+    pub fn typecheck(&self) -> Result<Program<'a>, Diagnostics<TypeError<'a>>> {
+        let (program, diagnostics) = self.typecheck_partial();
+        if diagnostics.is_empty() {
+            Ok(program)
+        } else {
+            Err(diagnostics)
+        }
+    }
+
+    // `Program::typecheck` that also checks the calls for aliasing, findings at level error
+    // fail it like type errors and the others are returned with their level
+    pub fn typecheck_with(
+        &self,
+        lints: &Lints,
+    ) -> Result<(Program<'a>, Findings<'a>), Diagnostics<TypeError<'a>>> {
+        let (program, mut diagnostics) = self.typecheck_partial();
+        let mut findings = Vec::new();
+        for finding in self.aliasing().0 {
+            match lints.level(&finding.error) {
+                Level::Allow => {}
+                Level::Error => diagnostics.push(Diagnostic {
+                    error: TypeError::Aliasing(finding.error),
+                    span: finding.span,
+                }),
+                level => findings.push((level, finding)),
+            }
+        }
+        if diagnostics.is_empty() {
+            Ok((program, findings))
+        } else {
+            Err(diagnostics)
+        }
+    }
+
+    // every call in the program that passes a variable through more than one parameter
+    pub fn aliasing(&self) -> Diagnostics<Aliasing<'a>> {
+        let Program(declarations, _, _, body, _) = self;
+        let mut diagnostics = Diagnostics::new();
+        for declaration in declarations.sequence() {
+            declaration.aliasing(&mut diagnostics);
+        }
+        body.aliasing(&mut diagnostics);
+        diagnostics
+    }
+
+    // typechecks the whole program however many errors it has, calls that couldn't be
+    // resolved are left unannotated
+    pub fn typecheck_partial(&self) -> (Program<'a>, Diagnostics<TypeError<'a>>) {
+        let mut diagnostics = Diagnostics::new();
+        let Program(declarations, identifier, parameters, body, specification) = self;
+        let (mut variable_typings, procedure_typings, declarations) =
+            declarations.typecheck(&mut diagnostics);
+        let (variable_typings_1, _) = parameters.typecheck(&mut diagnostics);
         variable_typings.extend(variable_typings_1);
-        let body = body.typecheck(&variable_typings, &procedure_typings)?;
-        Ok(Program(declarations, *identifier, parameters.clone(), body))
+        specification.typecheck(&variable_typings, &mut diagnostics);
+        let body = body.typecheck(&variable_typings, &procedure_typings, &mut diagnostics);
+        let program = Program(
+            declarations,
+            *identifier,
+            parameters.clone(),
+            body,
+            specification.clone(),
+        );
+        (program, diagnostics)
     }
 }
 
 impl<'a> Declarations<'a> {
     fn typecheck(
         &self,
-    ) -> Result<
-        (
-            VariableTypingMap<'a>,
-            ProcedureTypingSet<'a>,
-            Declarations<'a>,
-        ),
-        String,
-    > {
-        match self {
-            Declarations::Empty => Ok((HashMap::new(), HashSet::new(), self.clone())),
-            Declarations::Sequence(other, declaration) => {
-                let (variable_typings, procedure_typings, declarations) = other.typecheck()?;
-                let (variable_typings, procedure_typings, declaration) =
-                    declaration.typecheck(&variable_typings, &procedure_typings)?;
+        diagnostics: &mut Diagnostics<TypeError<'a>>,
+    ) -> (
+        VariableTypingMap<'a>,
+        ProcedureTypingSet<'a>,
+        Declarations<'a>,
+    ) {
+        let (variable_typings, procedure_typings, declarations) = Declaration::typecheck_all(
+            &self.sequence(),
+            &HashMap::new(),
+            &HashSet::new(),
+            diagnostics,
+        );
+        let declarations = declarations
+            .into_iter()
+            .fold(Declarations::Empty, |declarations, declaration| {
+                Declarations::Sequence(declarations.into(), declaration)
+            });
+        (variable_typings, procedure_typings, declarations)
+    }
+}
 
-                Ok((
-                    variable_typings,
-                    procedure_typings,
-                    Declarations::Sequence(declarations.into(), declaration),
-                ))
+impl<'a> Declaration<'a> {
+    // consecutive procedures may call each other, so the signatures of a whole run of
+    // them are known before any of their bodies is checked
+    pub(crate) fn typecheck_all(
+        declarations: &[&Declaration<'a>],
+        variable_typings: &VariableTypingMap<'a>,
+        procedure_typings: &ProcedureTypingSet<'a>,
+        diagnostics: &mut Diagnostics<TypeError<'a>>,
+    ) -> (
+        VariableTypingMap<'a>,
+        ProcedureTypingSet<'a>,
+        Vec<Declaration<'a>>,
+    ) {
+        let mut variable_typings = variable_typings.clone();
+        let mut procedure_typings = procedure_typings.clone();
+        let mut annotated_declarations = Vec::new();
+        for (index, declaration) in declarations.iter().enumerate() {
+            let starts_run = index == 0 || declarations[index - 1].signature().is_none();
+            if starts_run {
+                let run = declarations[index..].iter().map_while(|d| d.signature());
+                procedure_typings.extend(run);
             }
+            let (variable_typings_1, procedure_typings_1, declaration) =
+                declaration.typecheck(&variable_typings, &procedure_typings, diagnostics);
+            variable_typings = variable_typings_1;
+            procedure_typings = procedure_typings_1;
+            annotated_declarations.push(declaration);
         }
+        (variable_typings, procedure_typings, annotated_declarations)
     }
-}
 
-impl<'a> Declaration<'a> {
-    fn typecheck(
+    pub(crate) fn typecheck(
         &self,
         variable_typings: &VariableTypingMap<'a>,
         procedure_typings: &ProcedureTypingSet<'a>,
-    ) -> Result<
-        (
-            VariableTypingMap<'a>,
-            ProcedureTypingSet<'a>,
-            Declaration<'a>,
-        ),
-        String,
-    > {
+        diagnostics: &mut Diagnostics<TypeError<'a>>,
+    ) -> (
+        VariableTypingMap<'a>,
+        ProcedureTypingSet<'a>,
+        Declaration<'a>,
+    ) {
         match self {
             Declaration::Variable(identifier, sort) => {
                 let mut variable_typings = variable_typings.clone();
                 variable_typings.insert(*identifier, *sort);
-                Ok((variable_typings, procedure_typings.clone(), self.clone()))
+                (variable_typings, procedure_typings.clone(), self.clone())
             }
-            Declaration::Procedure(identifier, in_params, out_params, body) => {
-                let x1 = in_params.typecheck()?;
-                let mut x2 = out_params.typecheck()?;
+            Declaration::Procedure(identifier, in_params, out_params, body, specification) => {
+                let x1 = in_params.typecheck(diagnostics);
+                let mut x2 = out_params.typecheck(diagnostics);
 
                 for (key, value) in &x1.0 {
                     if x2.0.contains_key(key) {
-                        return Err(format!("function parameter {key} declared twice"));
+                        diagnostics.push(TypeError::DuplicateParameter(*key));
                     } else {
                         x2.0.insert(*key, *value);
                     }
@@ -80,13 +259,15 @@ impl<'a> Declaration<'a> {
 
                 let mut variable_typings_3 = variable_typings.clone();
                 variable_typings_3.extend(x2.0);
+                specification.typecheck(&variable_typings_3, diagnostics);
 
-                let body = body.typecheck(&variable_typings_3, procedure_typings)?;
-
+                // the procedure is in scope in its own body
                 let mut procedure_typings = procedure_typings.clone();
                 procedure_typings.insert((*identifier, (x1.1, x2.1)));
 
-                Ok((
+                let body = body.typecheck(&variable_typings_3, &procedure_typings, diagnostics);
+
+                (
                     variable_typings.clone(),
                     procedure_typings,
                     Declaration::Procedure(
@@ -94,93 +275,169 @@ impl<'a> Declaration<'a> {
                         in_params.clone(),
                         out_params.clone(),
                         body,
+                        specification.clone(),
                     ),
-                ))
+                )
+            }
+            Declaration::Spanned(span, declaration) => {
+                let (variable_typings, procedure_typings, declaration) =
+                    diagnostics.within(*span, |diagnostics| {
+                        declaration.typecheck(variable_typings, procedure_typings, diagnostics)
+                    });
+                (
+                    variable_typings,
+                    procedure_typings,
+                    Declaration::Spanned(*span, declaration.into()),
+                )
             }
         }
     }
 }
 
 impl<'a> Command<'a> {
-    fn typecheck(
+    pub(crate) fn typecheck(
         &self,
         variable_typings: &VariableTypingMap<'a>,
         procedure_typings: &ProcedureTypingSet<'a>,
-    ) -> Result<Command<'a>, String> {
+        diagnostics: &mut Diagnostics<TypeError<'a>>,
+    ) -> Command<'a> {
         match self {
             Command::Assign(identifier, expression) => {
                 if let Some(variable_sort) = variable_typings.get(identifier) {
-                    let expression_sort = expression.typecheck(variable_typings)?;
-                    if expression_sort != *variable_sort {
-                        Err(format!("expression of type {expression_sort} can't be assigned to variable of type {variable_sort}"))
-                    } else {
-                        Ok(self.clone())
-                    }
+                    expression.typecheck_as(*variable_sort, variable_typings, diagnostics);
                 } else {
-                    Err(format!("identifier {identifier} is not defined"))
+                    diagnostics.push(TypeError::UndefinedIdentifier(*identifier));
+                    expression.typecheck(variable_typings, diagnostics);
                 }
+                self.clone()
             }
             Command::Var(identifier, sort, command) => {
                 let mut variable_typings = variable_typings.clone();
                 variable_typings.insert(*identifier, *sort);
-                let command = command.typecheck(&variable_typings, procedure_typings)?;
-                Ok(Command::Var(*identifier, *sort, command.into()))
+                let command = command.typecheck(&variable_typings, procedure_typings, diagnostics);
+                Command::Var(*identifier, *sort, command.into())
             }
             Command::Sequence(first, second) => {
-                let first = first.typecheck(variable_typings, procedure_typings)?;
-                let second = second.typecheck(variable_typings, procedure_typings)?;
-                Ok(Command::Sequence(first.into(), second.into()))
+                let first = first.typecheck(variable_typings, procedure_typings, diagnostics);
+                let second = second.typecheck(variable_typings, procedure_typings, diagnostics);
+                Command::Sequence(first.into(), second.into())
             }
             Command::IfElse(expression, branch_if, branch_else) => {
-                if expression.typecheck(variable_typings)? != BOOL_SORT {
-                    Err("if requires boolean expression".into())
-                } else {
-                    let branch_if = branch_if.typecheck(variable_typings, procedure_typings)?;
-                    let branch_else = branch_else.typecheck(variable_typings, procedure_typings)?;
-                    Ok(Command::IfElse(
-                        expression.clone(),
-                        branch_if.into(),
-                        branch_else.into(),
-                    ))
-                }
+                expression.typecheck_condition(variable_typings, diagnostics);
+                let branch_if =
+                    branch_if.typecheck(variable_typings, procedure_typings, diagnostics);
+                let branch_else =
+                    branch_else.typecheck(variable_typings, procedure_typings, diagnostics);
+                Command::IfElse(expression.clone(), branch_if.into(), branch_else.into())
             }
             Command::If(expression, branch_if) => {
-                if expression.typecheck(variable_typings)? != BOOL_SORT {
-                    Err("if requires boolean expressions".into())
-                } else {
-                    let branch_if = branch_if.typecheck(variable_typings, procedure_typings)?;
-                    Ok(Command::If(expression.clone(), branch_if.into()))
-                }
+                expression.typecheck_condition(variable_typings, diagnostics);
+                let branch_if =
+                    branch_if.typecheck(variable_typings, procedure_typings, diagnostics);
+                Command::If(expression.clone(), branch_if.into())
             }
-            Command::While(expression, body) => {
-                if expression.typecheck(variable_typings)? != BOOL_SORT {
-                    Err("while requires boolean expressions".into())
-                } else {
-                    let body = body.typecheck(variable_typings, procedure_typings)?;
-                    Ok(Command::While(expression.clone(), body.into()))
+            Command::While(expression, body, invariant) => {
+                expression.typecheck_condition(variable_typings, diagnostics);
+                if let Some(invariant) = invariant {
+                    invariant.typecheck_condition(variable_typings, diagnostics);
                 }
+                let body = body.typecheck(variable_typings, procedure_typings, diagnostics);
+                Command::While(expression.clone(), body.into(), invariant.clone())
             }
             Command::Call(identifier, expressions, variables, _) => {
-                let expression_sorts = expressions.typecheck(variable_typings)?;
-                let variable_sorts = variables.typecheck(variable_typings)?;
+                let expression_sorts = expressions.typecheck(variable_typings, diagnostics);
+                let variable_sorts = variables.typecheck(variable_typings, diagnostics);
                 let signature: Signature = (
                     *identifier,
                     (expression_sorts.clone(), variable_sorts.clone()),
                 );
 
+                let erroneous = expression_sorts
+                    .iter()
+                    .chain(&variable_sorts)
+                    .any(|sort| *sort == ERROR_SORT);
                 if procedure_typings.contains(&signature) {
-                    Ok(Command::Call(
+                    Command::Call(
                         *identifier,
                         expressions.clone(),
                         variables.clone(),
                         Some(signature),
-                    ))
+                    )
                 } else {
-                    Err(format!(
-                        "no function matches signature {identifier}({expressions:?};{variables:?})"
-                    ))
+                    if !erroneous {
+                        diagnostics.push(TypeError::NoMatchingSignature {
+                            name: *identifier,
+                            in_sorts: expression_sorts,
+                            out_sorts: variable_sorts,
+                        });
+                    }
+                    Command::Call(*identifier, expressions.clone(), variables.clone(), None)
                 }
             }
+            Command::Spanned(span, command) => {
+                let command = diagnostics.within(*span, |diagnostics| {
+                    command.typecheck(variable_typings, procedure_typings, diagnostics)
+                });
+                Command::Spanned(*span, command.into())
+            }
+        }
+    }
+}
+
+impl<'a> Declaration<'a> {
+    fn aliasing(&self, diagnostics: &mut Diagnostics<Aliasing<'a>>) {
+        match self {
+            Declaration::Variable(..) => {}
+            Declaration::Procedure(_, _, _, body, _) => body.aliasing(diagnostics),
+            Declaration::Spanned(span, declaration) => {
+                diagnostics.within(*span, |diagnostics| declaration.aliasing(diagnostics))
+            }
+        }
+    }
+}
+
+impl<'a> Command<'a> {
+    // each variable is reported once per call
+    fn aliasing(&self, diagnostics: &mut Diagnostics<Aliasing<'a>>) {
+        match self {
+            Command::Assign(..) => {}
+            Command::Var(_, _, command)
+            | Command::If(_, command)
+            | Command::While(_, command, _) => command.aliasing(diagnostics),
+            Command::Sequence(first, second) | Command::IfElse(_, first, second) => {
+                first.aliasing(diagnostics);
+                second.aliasing(diagnostics);
+            }
+            Command::Call(_, expressions, variables, _) => {
+                let mut inputs = Vec::new();
+                for expression in expressions.sequence() {
+                    free_variables(&expression, &mut inputs);
+                }
+                let outputs = variables.sequence();
+                for (index, output) in outputs.iter().enumerate() {
+                    let earlier = outputs[..index].iter().filter(|o| *o == output).count();
+                    if earlier == 1 {
+                        diagnostics.push(Aliasing::DuplicateOutput(*output));
+                    } else if earlier == 0 && inputs.contains(output) {
+                        diagnostics.push(Aliasing::OutputInInput(*output));
+                    }
+                }
+            }
+            Command::Spanned(span, command) => {
+                diagnostics.within(*span, |diagnostics| command.aliasing(diagnostics))
+            }
+        }
+    }
+}
+
+impl<'a> Specification<'a> {
+    fn typecheck(
+        &self,
+        variable_typings: &VariableTypingMap<'a>,
+        diagnostics: &mut Diagnostics<TypeError<'a>>,
+    ) {
+        for clause in self.requires.iter().chain(&self.ensures) {
+            clause.typecheck_condition(variable_typings, diagnostics);
         }
     }
 }
@@ -189,15 +446,16 @@ impl<'a> Expressions<'a> {
     pub fn typecheck(
         &self,
         variable_typings: &VariableTypingMap<'a>,
-    ) -> Result<Vec<Sort<'a>>, String> {
+        diagnostics: &mut Diagnostics<TypeError<'a>>,
+    ) -> Vec<Sort<'a>> {
         match self {
-            Expressions::Empty => Ok(Vec::new()),
+            Expressions::Empty => Vec::new(),
             Expressions::Sequence(first, rest) => {
-                let expression_sort = first.typecheck(variable_typings)?;
-                let rest = rest.typecheck(variable_typings)?;
+                let expression_sort = first.typecheck(variable_typings, diagnostics);
+                let rest = rest.typecheck(variable_typings, diagnostics);
                 let mut result = vec![expression_sort];
                 result.extend(rest);
-                Ok(result)
+                result
             }
         }
     }
@@ -207,106 +465,142 @@ impl<'a> Variables<'a> {
     pub fn typecheck(
         &self,
         variable_typings: &VariableTypingMap<'a>,
-    ) -> Result<Vec<Sort<'a>>, String> {
+        diagnostics: &mut Diagnostics<TypeError<'a>>,
+    ) -> Vec<Sort<'a>> {
         match self {
-            Variables::Empty => Ok(Vec::new()),
+            Variables::Empty => Vec::new(),
             Variables::Sequence(first, rest) => {
-                if let Some(variable_sort) = variable_typings.get(&first.0) {
-                    let rest = rest.typecheck(variable_typings)?;
-                    let mut result = vec![*variable_sort];
-                    result.extend(rest);
-                    Ok(result)
+                let variable_sort = if let Some(variable_sort) = variable_typings.get(&first.0) {
+                    *variable_sort
                 } else {
-                    Err(format!("variable {first} is not defined"))
-                }
+                    diagnostics.push(TypeError::UndefinedIdentifier(first.0));
+                    ERROR_SORT
+                };
+                let rest = rest.typecheck(variable_typings, diagnostics);
+                let mut result = vec![variable_sort];
+                result.extend(rest);
+                result
             }
         }
     }
 }
 
 impl<'a> Expression<'a> {
-    fn typecheck(&self, variable_typings: &VariableTypingMap<'a>) -> Result<Sort<'a>, String> {
+    pub(crate) fn typecheck(
+        &self,
+        variable_typings: &VariableTypingMap<'a>,
+        diagnostics: &mut Diagnostics<TypeError<'a>>,
+    ) -> Sort<'a> {
         match self {
             Expression::Value(literal) => match literal {
-                Value::Numeral(_) => Ok(INT_SORT),
-                Value::True | Value::False => Ok(BOOL_SORT),
+                Value::Numeral(_) => INT_SORT,
+                Value::True | Value::False => BOOL_SORT,
             },
             Expression::Variable(identifier) => {
                 if let Some(identifier_sort) = variable_typings.get(identifier) {
-                    Ok(*identifier_sort)
+                    *identifier_sort
                 } else {
-                    Err(format!("identifier {identifier} is not defined"))
+                    diagnostics.push(TypeError::UndefinedIdentifier(*identifier));
+                    ERROR_SORT
                 }
             }
             Expression::Sum(left, right)
             | Expression::Difference(left, right)
             | Expression::Product(left, right)
-            | Expression::Division(left, right) => {
-                if left.typecheck(variable_typings)? != INT_SORT {
-                    return Err("expected int expression".into());
-                }
-                if right.typecheck(variable_typings)? != INT_SORT {
-                    return Err("expected int expression".into());
-                }
-                Ok(INT_SORT)
+            | Expression::Division(left, right)
+            | Expression::Remainder(left, right) => {
+                left.typecheck_as(INT_SORT, variable_typings, diagnostics);
+                right.typecheck_as(INT_SORT, variable_typings, diagnostics);
+                INT_SORT
             }
             Expression::Negative(expression) => {
-                if expression.typecheck(variable_typings)? != INT_SORT {
-                    return Err("expected int expression".into());
-                }
-                Ok(INT_SORT)
+                expression.typecheck_as(INT_SORT, variable_typings, diagnostics);
+                INT_SORT
             }
-            Expression::Equal(left, right) => {
-                let left = left.typecheck(variable_typings)?;
-                let right = right.typecheck(variable_typings)?;
-                if left != right {
-                    return Err("expression type mismatch: {left}, {right}".into());
-                }
-                Ok(left)
+            Expression::Equal(left, right) | Expression::NotEqual(left, right) => {
+                let left = left.typecheck(variable_typings, diagnostics);
+                right.typecheck_as(left, variable_typings, diagnostics);
+                BOOL_SORT
             }
-            Expression::LessThanOrEqual(left, right) => {
-                if left.typecheck(variable_typings)? != INT_SORT {
-                    return Err("expected int expression".into());
-                }
-                if right.typecheck(variable_typings)? != INT_SORT {
-                    return Err("expected int expression".into());
-                }
-                Ok(BOOL_SORT)
+            Expression::LessThan(left, right)
+            | Expression::LessThanOrEqual(left, right)
+            | Expression::GreaterThan(left, right)
+            | Expression::GreaterThanOrEqual(left, right) => {
+                left.typecheck_as(INT_SORT, variable_typings, diagnostics);
+                right.typecheck_as(INT_SORT, variable_typings, diagnostics);
+                BOOL_SORT
             }
-            Expression::And(left, right) | Expression::Or(left, right) => {
-                if left.typecheck(variable_typings)? != BOOL_SORT {
-                    return Err("expected boolean expression".into());
-                }
-                if right.typecheck(variable_typings)? != BOOL_SORT {
-                    return Err("expected boolean expression".into());
-                }
-                Ok(BOOL_SORT)
+            Expression::And(left, right)
+            | Expression::Or(left, right)
+            | Expression::Implies(left, right)
+            | Expression::Equivalent(left, right) => {
+                left.typecheck_as(BOOL_SORT, variable_typings, diagnostics);
+                right.typecheck_as(BOOL_SORT, variable_typings, diagnostics);
+                BOOL_SORT
             }
             Expression::Not(expression) => {
-                if expression.typecheck(variable_typings)? != BOOL_SORT {
-                    return Err("expected boolean expression".into());
-                }
-                Ok(BOOL_SORT)
+                expression.typecheck_as(BOOL_SORT, variable_typings, diagnostics);
+                BOOL_SORT
             }
+            Expression::Spanned(span, expression) => diagnostics.within(*span, |diagnostics| {
+                expression.typecheck(variable_typings, diagnostics)
+            }),
+        }
+    }
+
+    // mismatches are reported at the expression rather than at its parent
+    fn typecheck_as(
+        &self,
+        expected: Sort<'a>,
+        variable_typings: &VariableTypingMap<'a>,
+        diagnostics: &mut Diagnostics<TypeError<'a>>,
+    ) {
+        let found = self.typecheck(variable_typings, diagnostics);
+        if found != expected && found != ERROR_SORT && expected != ERROR_SORT {
+            diagnostics.push(Diagnostic {
+                error: TypeError::SortMismatch { expected, found },
+                span: self.span(),
+            });
+        }
+    }
+
+    pub(crate) fn typecheck_condition(
+        &self,
+        variable_typings: &VariableTypingMap<'a>,
+        diagnostics: &mut Diagnostics<TypeError<'a>>,
+    ) {
+        let found = self.typecheck(variable_typings, diagnostics);
+        if found != BOOL_SORT && found != ERROR_SORT {
+            diagnostics.push(Diagnostic {
+                error: TypeError::NonBoolCondition(found),
+                span: self.span(),
+            });
         }
     }
 }
 
 impl<'a> Parameters<'a> {
-    pub fn typecheck(&self) -> Result<(VariableTypingMap<'a>, Vec<Sort<'a>>), String> {
+    pub fn typecheck(
+        &self,
+        diagnostics: &mut Diagnostics<TypeError<'a>>,
+    ) -> (VariableTypingMap<'a>, Vec<Sort<'a>>) {
         match self {
-            Parameters::Empty => Ok((HashMap::new(), Vec::new())),
+            Parameters::Empty => (HashMap::new(), Vec::new()),
             Parameters::Sequence(rest, variable, sort) => {
-                let (variable_typings, sort_sequence) = rest.typecheck()?;
-                let mut sort_sequence = sort_sequence.to_owned();
-                let mut variable_typings = variable_typings.clone();
-                if variable_typings.contains_key(&variable.0) {
-                    let variable = variable.0;
-                    return Err(format!("parameter {variable} declared twice"));
+                let (mut variable_typings, mut sort_sequence) = rest.typecheck(diagnostics);
+                match variable_typings.entry(variable.0) {
+                    Entry::Occupied(_) => {
+                        diagnostics.push(TypeError::DuplicateParameter(variable.0))
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(*sort);
+                    }
                 }
                 sort_sequence.push(*sort);
-                variable_typings.insert(variable.0, *sort);
-                Ok((variable_typings, sort_sequence).to_owned())
+                (variable_typings, sort_sequence)
+            }
+            Parameters::Spanned(span, parameters) => {
+                diagnostics.within(*span, |diagnostics| parameters.typecheck(diagnostics))
             }
         }
     }
@@ -314,10 +608,12 @@ impl<'a> Parameters<'a> {
 
 #[cfg(test)]
 mod tests {
+    use super::{Aliasing, Level, Lints, TypeError, BOOL_SORT, INT_SORT};
     use crate::gcd::{
         a_a_gcd_parameter_sequence, a_b_gcd_parameter_sequence, a_g_gcd_parameter_sequence,
         generate_gcd, x_y_gcd_parameter_sequence,
     };
+    use crate::types::{Command, Program, Span};
 
     #[test]
     fn test_typecheck_gcd() -> Result<(), String> {
@@ -332,7 +628,7 @@ mod tests {
         match program.typecheck() {
             Ok(_) => Err("should fail".into()),
             Err(reason) => {
-                assert_eq!("parameter a declared twice", reason);
+                assert_eq!(TypeError::DuplicateParameter("a".into()), reason.0[0].error);
                 Ok(())
             }
         }
@@ -344,7 +640,10 @@ mod tests {
         match program.typecheck() {
             Ok(_) => Err("should fail".into()),
             Err(reason) => {
-                assert_eq!("identifier a is not defined", reason);
+                assert_eq!(
+                    TypeError::UndefinedIdentifier("a".into()),
+                    reason.0[0].error
+                );
                 Ok(())
             }
         }
@@ -356,9 +655,193 @@ mod tests {
         match program.typecheck() {
             Ok(_) => Err("should fail".into()),
             Err(reason) => {
-                assert_eq!("function parameter g declared twice", reason);
+                assert_eq!(TypeError::DuplicateParameter("g".into()), reason.0[0].error);
                 Ok(())
             }
         }
     }
+
+    #[test]
+    fn test_typecheck_error_span() -> Result<(), String> {
+        let source = "program p(a:Int) {\n  a := a + b;\n}";
+        let program = Program::parse(source)?;
+        match program.typecheck() {
+            Ok(_) => Err("should fail".into()),
+            Err(reason) => {
+                assert_eq!(reason.0[0].span, Some(Span { start: 30, end: 31 }));
+                assert_eq!(
+                    reason.render(source),
+                    "error: identifier b is not defined\n --> 2:12\n  |\n2 |   a := a + b;\n  |            ^"
+                );
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn test_typecheck_structured_errors() -> Result<(), String> {
+        let program = Program::parse("program p(a:Int, b:Bool) {\n  a := b;\n}")?;
+        assert_eq!(
+            program.typecheck().unwrap_err().0[0].error,
+            TypeError::SortMismatch {
+                expected: INT_SORT,
+                found: BOOL_SORT
+            }
+        );
+
+        let program = Program::parse("program p(a:Int) {\n  while a do a := 1;\n}")?;
+        assert_eq!(
+            program.typecheck().unwrap_err().0[0].error,
+            TypeError::NonBoolCondition(INT_SORT)
+        );
+
+        let program = Program::parse("program p(a:Int) {\n  if a = 1 then call f(a;a);\n}")?;
+        assert_eq!(
+            program.typecheck().unwrap_err().0[0].error,
+            TypeError::NoMatchingSignature {
+                name: "f".into(),
+                in_sorts: vec![INT_SORT],
+                out_sorts: vec![INT_SORT]
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_typecheck_all_errors() -> Result<(), String> {
+        let source = "procedure p(a:Int; ref b:Bool) {
+  b := a;
+  call q(a;b);
+}
+program main(x:Int, y:Bool) {
+  x := y + 1;
+  if z then call p(x;y); else call p(y;x);
+  while x do x := 1;
+}";
+        let program = Program::parse(source)?;
+        let (annotated_program, diagnostics) = program.typecheck_partial();
+        let errors: Vec<_> = diagnostics.0.iter().map(|d| d.error.clone()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                TypeError::SortMismatch {
+                    expected: BOOL_SORT,
+                    found: INT_SORT
+                },
+                TypeError::NoMatchingSignature {
+                    name: "q".into(),
+                    in_sorts: vec![INT_SORT],
+                    out_sorts: vec![BOOL_SORT]
+                },
+                TypeError::SortMismatch {
+                    expected: INT_SORT,
+                    found: BOOL_SORT
+                },
+                TypeError::UndefinedIdentifier("z".into()),
+                TypeError::NoMatchingSignature {
+                    name: "p".into(),
+                    in_sorts: vec![BOOL_SORT],
+                    out_sorts: vec![INT_SORT]
+                },
+                TypeError::NonBoolCondition(INT_SORT),
+            ]
+        );
+        assert!(diagnostics.0.iter().all(|d| d.span.is_some()));
+
+        // the call that could be resolved is annotated anyway
+        let Program(_, _, _, body, _) = annotated_program;
+        let calls: Vec<_> = body
+            .sequence()
+            .into_iter()
+            .filter_map(|command| match command.unspanned() {
+                Command::IfElse(_, if_branch, _) => match if_branch.unspanned() {
+                    Command::Call(_, _, _, signature) => Some(signature.is_some()),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+        assert_eq!(calls, vec![true]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_typecheck_recursion() -> Result<(), String> {
+        let program = Program::parse(include_str!("../programs/recursion.fsp"))?;
+        program.typecheck()?;
+
+        // only consecutive procedures can call each other
+        let source = "procedure even(n:Int; ref b:Bool) {
+  if n = 0 then b := true; else call odd(n-1; b);
+}
+var x:Int;
+procedure odd(n:Int; ref b:Bool) {
+  if n = 0 then b := false; else call even(n-1; b);
+}
+program p(y:Int) { y := 0; }";
+        let program = Program::parse(source)?;
+        let errors: Vec<_> = program.typecheck().unwrap_err().0;
+        assert_eq!(
+            errors.iter().map(|d| d.error.clone()).collect::<Vec<_>>(),
+            vec![TypeError::NoMatchingSignature {
+                name: "odd".into(),
+                in_sorts: vec![INT_SORT],
+                out_sorts: vec![BOOL_SORT]
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_aliasing() -> Result<(), String> {
+        let source = "procedure p(a:Int; ref b:Int, c:Int) { b := a; c := a; }
+program q(x:Int, y:Int) {
+  call p(x; y, y);
+  call p(y + 1; y, x);
+  call p(x; x, x);
+}";
+        let program = Program::parse(source)?;
+        let findings: Vec<_> = program
+            .aliasing()
+            .0
+            .into_iter()
+            .map(|d| (d.error, d.span.map_or(0, |span| span.start)))
+            .collect();
+        assert_eq!(
+            findings,
+            vec![
+                (Aliasing::DuplicateOutput("y".into()), 85),
+                (Aliasing::OutputInInput("y".into()), 104),
+                (Aliasing::OutputInInput("x".into()), 127),
+                (Aliasing::DuplicateOutput("x".into()), 127),
+            ]
+        );
+
+        let (_, findings) = program.typecheck_with(&Lints::default())?;
+        let levels: Vec<_> = findings.iter().map(|(level, _)| *level).collect();
+        assert_eq!(
+            levels,
+            vec![Level::Warning, Level::Note, Level::Note, Level::Warning]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_aliasing_levels() -> Result<(), String> {
+        let program = Program::parse(include_str!("../programs/gcd.fsp"))?;
+        let mut lints = Lints::default();
+        assert!(!lints.set("shadowing", Level::Error));
+        lints.set("output-in-input", Level::Allow);
+        assert_eq!(program.typecheck_with(&lints)?.1, vec![]);
+        lints.set("output-in-input", Level::Error);
+        let errors = program.typecheck_with(&lints).unwrap_err().0;
+        assert_eq!(
+            errors.iter().map(|d| d.error.clone()).collect::<Vec<_>>(),
+            vec![
+                TypeError::Aliasing(Aliasing::OutputInInput("a".into())),
+                TypeError::Aliasing(Aliasing::OutputInInput("b".into()))
+            ]
+        );
+        Ok(())
+    }
 }
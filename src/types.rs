@@ -1,39 +1,58 @@
-use crate::typecheck::Signature;
+use crate::{integer::Integer, typecheck::Signature};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
-    Numeral(i32),
+    Numeral(Integer),
     True,
     False,
 }
-#[derive(Debug, Hash, PartialEq, Eq, Copy, Clone)]
-pub struct Identifier<'a>(pub &'a str);
-#[derive(Debug, Clone)]
+// a name from the source, or a fresh one `x'n` made from it, which no identifier in the
+// source can be
+#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+pub struct Identifier<'a>(pub &'a str, pub Option<usize>);
+#[derive(Debug, Clone, PartialEq)]
 pub struct Variable<'a>(pub Identifier<'a>);
 #[derive(Debug, Hash, PartialEq, Eq, Copy, Clone)]
 pub struct Sort<'a>(pub Identifier<'a>);
 
-#[derive(Debug)]
+// byte range in the source a node was parsed from
+#[derive(Debug, Hash, PartialEq, Eq, Copy, Clone)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Program<'a>(
     pub Declarations<'a>,
     pub Identifier<'a>,
     pub Parameters<'a>,
     pub Command<'a>,
+    pub Specification<'a>,
 );
 
-#[derive(Debug, Clone)]
+// the `requires` and `ensures` clauses of a procedure or program, a missing clause
+// stands for true
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Specification<'a> {
+    pub requires: Option<Expression<'a>>,
+    pub ensures: Option<Expression<'a>>,
+}
+
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Declarations<'a> {
     Empty,
     Sequence(Box<Declarations<'a>>, Declaration<'a>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expressions<'a> {
     Empty,
     Sequence(Expression<'a>, Box<Expressions<'a>>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Variables<'a> {
     Empty,
     Sequence(Variable<'a>, Box<Variables<'a>>),
@@ -47,12 +66,21 @@ pub enum Expression<'a> {
     Difference(Box<Expression<'a>>, Box<Expression<'a>>),
     Product(Box<Expression<'a>>, Box<Expression<'a>>),
     Division(Box<Expression<'a>>, Box<Expression<'a>>),
+    // has the sign of the dividend, like the quotient rounds towards 0
+    Remainder(Box<Expression<'a>>, Box<Expression<'a>>),
     Negative(Box<Expression<'a>>),
     Equal(Box<Expression<'a>>, Box<Expression<'a>>),
+    NotEqual(Box<Expression<'a>>, Box<Expression<'a>>),
+    LessThan(Box<Expression<'a>>, Box<Expression<'a>>),
     LessThanOrEqual(Box<Expression<'a>>, Box<Expression<'a>>),
+    GreaterThan(Box<Expression<'a>>, Box<Expression<'a>>),
+    GreaterThanOrEqual(Box<Expression<'a>>, Box<Expression<'a>>),
     And(Box<Expression<'a>>, Box<Expression<'a>>),
     Or(Box<Expression<'a>>, Box<Expression<'a>>),
+    Implies(Box<Expression<'a>>, Box<Expression<'a>>),
+    Equivalent(Box<Expression<'a>>, Box<Expression<'a>>),
     Not(Box<Expression<'a>>),
+    Spanned(Span, Box<Expression<'a>>),
 }
 
 #[derive(Debug, Clone)]
@@ -62,31 +90,54 @@ pub enum Command<'a> {
     Sequence(Box<Command<'a>>, Box<Command<'a>>),
     IfElse(Expression<'a>, Box<Command<'a>>, Box<Command<'a>>),
     If(Expression<'a>, Box<Command<'a>>),
-    While(Expression<'a>, Box<Command<'a>>),
-    Call(Identifier<'a>, Expressions<'a>, Variables<'a>, Option<Signature<'a>>),
+    // with an optional loop invariant
+    While(Expression<'a>, Box<Command<'a>>, Option<Expression<'a>>),
+    Call(
+        Identifier<'a>,
+        Expressions<'a>,
+        Variables<'a>,
+        Option<Signature<'a>>,
+    ),
+    Spanned(Span, Box<Command<'a>>),
 }
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone)]
 pub enum Declaration<'a> {
     Variable(Identifier<'a>, Sort<'a>),
-    Procedure(Identifier<'a>, Parameters<'a>, Parameters<'a>, Command<'a>),
+    Procedure(
+        Identifier<'a>,
+        Parameters<'a>,
+        Parameters<'a>,
+        Command<'a>,
+        Specification<'a>,
+    ),
+    Spanned(Span, Box<Declaration<'a>>),
 }
 
 #[derive(Debug, Clone)]
 pub enum Parameters<'a> {
     Empty,
     Sequence(Box<Parameters<'a>>, Variable<'a>, Sort<'a>),
+    Spanned(Span, Box<Parameters<'a>>),
 }
 
 impl<'a> From<&'a str> for Identifier<'a> {
     fn from(value: &'a str) -> Self {
-        Identifier(value)
+        Identifier(value, None)
+    }
+}
+
+impl<'a> Identifier<'a> {
+    // `x'n` for the identifier `x`, or for the one `x` was made from if it is fresh
+    pub(crate) fn fresh(self, n: usize) -> Identifier<'a> {
+        Identifier(self.0, Some(n))
     }
 }
 
 impl From<i32> for Value {
     fn from(value: i32) -> Self {
-        Value::Numeral(value)
+        Value::Numeral(value.into())
     }
 }
 
@@ -125,3 +176,247 @@ impl<'a> From<i32> for Box<Expression<'a>> {
         Expression::Value(value.into()).into()
     }
 }
+
+impl<'a> Expression<'a> {
+    pub fn unspanned(&self) -> &Expression<'a> {
+        match self {
+            Expression::Spanned(_, expression) => expression.unspanned(),
+            _ => self,
+        }
+    }
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Expression::Spanned(span, _) => Some(*span),
+            _ => None,
+        }
+    }
+    // the comparisons and connectives the book doesn't have in terms of those it has,
+    // e.g. `a < b` as `¬(b ≤ a)`, none for the others
+    pub fn desugared(&self) -> Option<Expression<'a>> {
+        let not = |expression| Expression::Not(Box::new(expression));
+        Some(match self {
+            Expression::NotEqual(left, right) => {
+                not(Expression::Equal(left.clone(), right.clone()))
+            }
+            Expression::LessThan(left, right) => {
+                not(Expression::LessThanOrEqual(right.clone(), left.clone()))
+            }
+            Expression::GreaterThan(left, right) => {
+                not(Expression::LessThanOrEqual(left.clone(), right.clone()))
+            }
+            Expression::GreaterThanOrEqual(left, right) => {
+                Expression::LessThanOrEqual(right.clone(), left.clone())
+            }
+            Expression::Implies(left, right) => {
+                Expression::Or(not(*left.clone()).into(), right.clone())
+            }
+            Expression::Equivalent(left, right) => Expression::Equal(left.clone(), right.clone()),
+            _ => return None,
+        })
+    }
+}
+
+impl<'a> Command<'a> {
+    pub fn unspanned(&self) -> &Command<'a> {
+        match self {
+            Command::Spanned(_, command) => command.unspanned(),
+            _ => self,
+        }
+    }
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Command::Spanned(span, _) => Some(*span),
+            _ => None,
+        }
+    }
+    // the commands of a sequence in execution order, regardless of how it is nested
+    pub fn sequence(&self) -> Vec<&Command<'a>> {
+        match self.unspanned() {
+            Command::Sequence(first, second) => {
+                let mut sequence = first.sequence();
+                sequence.extend(second.sequence());
+                sequence
+            }
+            _ => vec![self],
+        }
+    }
+}
+
+impl<'a> Expressions<'a> {
+    pub fn sequence(&self) -> Vec<Expression<'a>> {
+        match self {
+            Expressions::Empty => Vec::new(),
+            Expressions::Sequence(first, rest) => {
+                let mut sequence = vec![first.clone()];
+                sequence.extend(rest.sequence());
+                sequence
+            }
+        }
+    }
+}
+
+impl<'a> Variables<'a> {
+    pub fn sequence(&self) -> Vec<Identifier<'a>> {
+        match self {
+            Variables::Empty => Vec::new(),
+            Variables::Sequence(first, rest) => {
+                let mut sequence = vec![first.0];
+                sequence.extend(rest.sequence());
+                sequence
+            }
+        }
+    }
+}
+
+impl<'a> Declarations<'a> {
+    // the declarations in the order they were written
+    pub fn sequence(&self) -> Vec<&Declaration<'a>> {
+        match self {
+            Declarations::Empty => Vec::new(),
+            Declarations::Sequence(rest, declaration) => {
+                let mut sequence = rest.sequence();
+                sequence.push(declaration);
+                sequence
+            }
+        }
+    }
+}
+
+impl<'a> Declaration<'a> {
+    pub fn unspanned(&self) -> &Declaration<'a> {
+        match self {
+            Declaration::Spanned(_, declaration) => declaration.unspanned(),
+            _ => self,
+        }
+    }
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Declaration::Spanned(span, _) => Some(*span),
+            _ => None,
+        }
+    }
+    pub fn signature(&self) -> Option<Signature<'a>> {
+        match self.unspanned() {
+            Declaration::Procedure(identifier, in_params, out_params, ..) => {
+                Some((*identifier, (in_params.sorts(), out_params.sorts())))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Parameters<'a> {
+    pub fn unspanned(&self) -> &Parameters<'a> {
+        match self {
+            Parameters::Spanned(_, parameters) => parameters.unspanned(),
+            _ => self,
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        matches!(self.unspanned(), Parameters::Empty)
+    }
+    pub fn len(&self) -> usize {
+        match self.unspanned() {
+            Parameters::Sequence(rest, ..) => rest.len() + 1,
+            _ => 0,
+        }
+    }
+    pub fn identifiers(&self) -> Vec<Identifier<'a>> {
+        match self.unspanned() {
+            Parameters::Sequence(rest, variable, _) => {
+                let mut identifiers = rest.identifiers();
+                identifiers.push(variable.0);
+                identifiers
+            }
+            _ => Vec::new(),
+        }
+    }
+    pub fn sorts(&self) -> Vec<Sort<'a>> {
+        match self.unspanned() {
+            Parameters::Sequence(rest, _, sort) => {
+                let mut sorts = rest.sorts();
+                sorts.push(*sort);
+                sorts
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+// Spans only record where a node came from, so they don't take part in equality.
+impl<'a> PartialEq for Expression<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.unspanned(), other.unspanned()) {
+            (Expression::Value(v1), Expression::Value(v2)) => v1 == v2,
+            (Expression::Variable(i1), Expression::Variable(i2)) => i1 == i2,
+            (Expression::Negative(e1), Expression::Negative(e2))
+            | (Expression::Not(e1), Expression::Not(e2)) => e1 == e2,
+            (Expression::Sum(l1, r1), Expression::Sum(l2, r2))
+            | (Expression::Difference(l1, r1), Expression::Difference(l2, r2))
+            | (Expression::Product(l1, r1), Expression::Product(l2, r2))
+            | (Expression::Division(l1, r1), Expression::Division(l2, r2))
+            | (Expression::Remainder(l1, r1), Expression::Remainder(l2, r2))
+            | (Expression::Equal(l1, r1), Expression::Equal(l2, r2))
+            | (Expression::NotEqual(l1, r1), Expression::NotEqual(l2, r2))
+            | (Expression::LessThan(l1, r1), Expression::LessThan(l2, r2))
+            | (Expression::LessThanOrEqual(l1, r1), Expression::LessThanOrEqual(l2, r2))
+            | (Expression::GreaterThan(l1, r1), Expression::GreaterThan(l2, r2))
+            | (Expression::GreaterThanOrEqual(l1, r1), Expression::GreaterThanOrEqual(l2, r2))
+            | (Expression::And(l1, r1), Expression::And(l2, r2))
+            | (Expression::Or(l1, r1), Expression::Or(l2, r2))
+            | (Expression::Implies(l1, r1), Expression::Implies(l2, r2))
+            | (Expression::Equivalent(l1, r1), Expression::Equivalent(l2, r2)) => {
+                l1 == l2 && r1 == r2
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<'a> PartialEq for Command<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.unspanned(), other.unspanned()) {
+            (Command::Assign(i1, e1), Command::Assign(i2, e2)) => i1 == i2 && e1 == e2,
+            (Command::Var(i1, s1, c1), Command::Var(i2, s2, c2)) => {
+                i1 == i2 && s1 == s2 && c1 == c2
+            }
+            (Command::Sequence(c1, d1), Command::Sequence(c2, d2)) => c1 == c2 && d1 == d2,
+            (Command::IfElse(e1, c1, d1), Command::IfElse(e2, c2, d2)) => {
+                e1 == e2 && c1 == c2 && d1 == d2
+            }
+            (Command::If(e1, c1), Command::If(e2, c2)) => e1 == e2 && c1 == c2,
+            (Command::While(e1, c1, i1), Command::While(e2, c2, i2)) => {
+                e1 == e2 && c1 == c2 && i1 == i2
+            }
+            (Command::Call(i1, e1, v1, s1), Command::Call(i2, e2, v2, s2)) => {
+                i1 == i2 && e1 == e2 && v1 == v2 && s1 == s2
+            }
+            _ => false,
+        }
+    }
+}
+
+impl<'a> PartialEq for Declaration<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.unspanned(), other.unspanned()) {
+            (Declaration::Variable(i1, s1), Declaration::Variable(i2, s2)) => i1 == i2 && s1 == s2,
+            (
+                Declaration::Procedure(i1, p1, q1, c1, s1),
+                Declaration::Procedure(i2, p2, q2, c2, s2),
+            ) => i1 == i2 && p1 == p2 && q1 == q2 && c1 == c2 && s1 == s2,
+            _ => false,
+        }
+    }
+}
+
+impl<'a> PartialEq for Parameters<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.unspanned(), other.unspanned()) {
+            (Parameters::Empty, Parameters::Empty) => true,
+            (Parameters::Sequence(r1, v1, s1), Parameters::Sequence(r2, v2, s2)) => {
+                r1 == r2 && v1 == v2 && s1 == s2
+            }
+            _ => false,
+        }
+    }
+}